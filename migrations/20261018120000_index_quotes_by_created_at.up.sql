@@ -0,0 +1,2 @@
+-- Backs the keyset pagination of /19/list.
+CREATE INDEX IF NOT EXISTS quotes_created_at_id_idx ON quotes (created_at, id);
@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS quotes_archive_version ON quotes;
+DROP FUNCTION IF EXISTS archive_quote_version();
+DROP TABLE IF EXISTS quote_versions;
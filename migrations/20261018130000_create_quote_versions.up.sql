@@ -0,0 +1,22 @@
+-- Every version of a quote that has since been replaced or deleted.
+CREATE TABLE IF NOT EXISTS quote_versions (
+    quote_id UUID NOT NULL,
+    version INT NOT NULL,
+    author TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (quote_id, version)
+);
+
+CREATE OR REPLACE FUNCTION archive_quote_version() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO quote_versions (quote_id, version, author, quote, created_at)
+    VALUES (OLD.id, OLD.version, OLD.author, OLD.quote, OLD.created_at);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER quotes_archive_version
+    AFTER UPDATE OR DELETE ON quotes
+    FOR EACH ROW EXECUTE FUNCTION archive_quote_version();
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS quotes_author_idx;
+DROP INDEX IF EXISTS quotes_search_idx;
+DROP FUNCTION IF EXISTS quote_document(TEXT, TEXT);
@@ -0,0 +1,9 @@
+-- What full-text search runs against, with the author weighted above the
+-- quote itself. Immutable so that it can be indexed.
+CREATE OR REPLACE FUNCTION quote_document(author TEXT, quote TEXT) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english', author), 'A')
+        || setweight(to_tsvector('english', quote), 'B')
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE INDEX IF NOT EXISTS quotes_search_idx ON quotes USING GIN (quote_document(author, quote));
+CREATE INDEX IF NOT EXISTS quotes_author_idx ON quotes (lower(author));
@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS quotes_prune_events ON quotes;
+DROP TRIGGER IF EXISTS quotes_record_reset ON quotes;
+DROP TRIGGER IF EXISTS quotes_record_event ON quotes;
+DROP FUNCTION IF EXISTS prune_quote_events();
+DROP FUNCTION IF EXISTS record_quote_event();
+DROP TABLE IF EXISTS quote_events;
@@ -0,0 +1,52 @@
+-- Every change to quotes, kept for a day so that event stream clients can
+-- catch up on what they missed.
+CREATE TABLE IF NOT EXISTS quote_events (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('created', 'updated', 'deleted', 'reset')),
+    -- The row after the change, or before a delete. NULL for resets.
+    quote JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS quote_events_created_at_idx ON quote_events (created_at);
+
+-- Listeners are only told the event's id, as a quote might not fit in a
+-- notification payload.
+CREATE OR REPLACE FUNCTION record_quote_event() RETURNS trigger AS $$
+DECLARE
+    event_id BIGINT;
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        INSERT INTO quote_events (kind) VALUES ('reset') RETURNING id INTO event_id;
+    ELSIF TG_OP = 'DELETE' THEN
+        INSERT INTO quote_events (kind, quote) VALUES ('deleted', to_jsonb(OLD))
+            RETURNING id INTO event_id;
+    ELSE
+        INSERT INTO quote_events (kind, quote)
+            VALUES (CASE TG_OP WHEN 'INSERT' THEN 'created' ELSE 'updated' END, to_jsonb(NEW))
+            RETURNING id INTO event_id;
+    END IF;
+
+    PERFORM pg_notify('quote_events', event_id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION prune_quote_events() RETURNS trigger AS $$
+BEGIN
+    DELETE FROM quote_events WHERE created_at < CURRENT_TIMESTAMP - INTERVAL '1 day';
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER quotes_record_event
+    AFTER INSERT OR UPDATE OR DELETE ON quotes
+    FOR EACH ROW EXECUTE FUNCTION record_quote_event();
+
+CREATE TRIGGER quotes_record_reset
+    AFTER TRUNCATE ON quotes
+    FOR EACH STATEMENT EXECUTE FUNCTION record_quote_event();
+
+CREATE TRIGGER quotes_prune_events
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON quotes
+    FOR EACH STATEMENT EXECUTE FUNCTION prune_quote_events();
@@ -0,0 +1,3 @@
+DROP FUNCTION IF EXISTS quote_tag_names(UUID);
+DROP TABLE IF EXISTS quote_tags;
+DROP TABLE IF EXISTS tags;
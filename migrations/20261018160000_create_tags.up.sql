@@ -0,0 +1,20 @@
+-- Tags are shared between quotes, which carry any number of them.
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS quote_tags (
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    tag_id INT NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (quote_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS quote_tags_tag_id_idx ON quote_tags (tag_id);
+
+-- The names of a quote's tags in byte order, for queries returning quotes.
+CREATE OR REPLACE FUNCTION quote_tag_names(UUID) RETURNS TEXT[] AS $$
+    SELECT COALESCE(array_agg(t.name ORDER BY t.name COLLATE "C"), '{}')
+    FROM quote_tags qt JOIN tags t ON t.id = qt.tag_id
+    WHERE qt.quote_id = $1
+$$ LANGUAGE sql STABLE;
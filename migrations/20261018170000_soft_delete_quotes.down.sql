@@ -0,0 +1,34 @@
+-- Trashed quotes were deleted as far as clients could tell, so they go for
+-- good.
+DELETE FROM quotes WHERE deleted_at IS NOT NULL;
+
+CREATE OR REPLACE FUNCTION record_quote_event() RETURNS trigger AS $$
+DECLARE
+    event_id BIGINT;
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        INSERT INTO quote_events (kind) VALUES ('reset') RETURNING id INTO event_id;
+    ELSIF TG_OP = 'DELETE' THEN
+        INSERT INTO quote_events (kind, quote) VALUES ('deleted', to_jsonb(OLD))
+            RETURNING id INTO event_id;
+    ELSE
+        INSERT INTO quote_events (kind, quote)
+            VALUES (CASE TG_OP WHEN 'INSERT' THEN 'created' ELSE 'updated' END, to_jsonb(NEW))
+            RETURNING id INTO event_id;
+    END IF;
+
+    PERFORM pg_notify('quote_events', event_id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION archive_quote_version() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO quote_versions (quote_id, version, author, quote, created_at)
+    VALUES (OLD.id, OLD.version, OLD.author, OLD.quote, OLD.created_at);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP INDEX IF EXISTS quotes_trash_idx;
+ALTER TABLE quotes DROP COLUMN IF EXISTS deleted_at;
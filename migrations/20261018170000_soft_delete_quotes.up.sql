@@ -0,0 +1,52 @@
+-- Deleting a quote moves it to the trash, from which it can be restored or
+-- purged for good.
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- Backs the keyset pagination of /19/trash.
+CREATE INDEX IF NOT EXISTS quotes_trash_idx ON quotes (created_at, id)
+    WHERE deleted_at IS NOT NULL;
+
+-- Trashing or restoring a quote leaves its content, and so its version, as it
+-- was; only edits are archived.
+CREATE OR REPLACE FUNCTION archive_quote_version() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.version = OLD.version THEN
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO quote_versions (quote_id, version, author, quote, created_at)
+    VALUES (OLD.id, OLD.version, OLD.author, OLD.quote, OLD.created_at);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Trashing a quote is what clients see as deleting it, and restoring it as
+-- creating it again. Purging a trashed quote changes nothing they can see.
+CREATE OR REPLACE FUNCTION record_quote_event() RETURNS trigger AS $$
+DECLARE
+    event_id BIGINT;
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        INSERT INTO quote_events (kind) VALUES ('reset') RETURNING id INTO event_id;
+    ELSIF TG_OP = 'DELETE' THEN
+        IF OLD.deleted_at IS NOT NULL THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO quote_events (kind, quote) VALUES ('deleted', to_jsonb(OLD))
+            RETURNING id INTO event_id;
+    ELSIF TG_OP = 'UPDATE' AND NEW.deleted_at IS NOT NULL THEN
+        INSERT INTO quote_events (kind, quote) VALUES ('deleted', to_jsonb(OLD))
+            RETURNING id INTO event_id;
+    ELSIF TG_OP = 'UPDATE' AND OLD.deleted_at IS NOT NULL THEN
+        INSERT INTO quote_events (kind, quote) VALUES ('created', to_jsonb(NEW))
+            RETURNING id INTO event_id;
+    ELSE
+        INSERT INTO quote_events (kind, quote)
+            VALUES (CASE TG_OP WHEN 'INSERT' THEN 'created' ELSE 'updated' END, to_jsonb(NEW))
+            RETURNING id INTO event_id;
+    END IF;
+
+    PERFORM pg_notify('quote_events', event_id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
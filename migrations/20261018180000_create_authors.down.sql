@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS quotes_link_author ON quotes;
+DROP FUNCTION IF EXISTS link_quote_author();
+ALTER TABLE quotes DROP COLUMN IF EXISTS author_id;
+DROP TABLE IF EXISTS authors;
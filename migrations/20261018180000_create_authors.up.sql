@@ -0,0 +1,37 @@
+-- Authors are told apart ignoring case and surrounding whitespace, so every
+-- spelling of a name links to the same author, shown under the first spelling
+-- seen. Quotes keep the spelling they were written with.
+CREATE TABLE IF NOT EXISTS authors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS authors_name_key ON authors (lower(name));
+
+INSERT INTO authors (name)
+SELECT DISTINCT ON (lower(btrim(author))) btrim(author)
+FROM quotes
+ORDER BY lower(btrim(author)), created_at, id;
+
+-- Linking the existing quotes changes nothing clients can see, so it neither
+-- records events nor archives versions.
+ALTER TABLE quotes ADD COLUMN IF NOT EXISTS author_id UUID REFERENCES authors (id);
+ALTER TABLE quotes DISABLE TRIGGER USER;
+UPDATE quotes q SET author_id = a.id FROM authors a WHERE lower(a.name) = lower(btrim(q.author));
+ALTER TABLE quotes ENABLE TRIGGER USER;
+ALTER TABLE quotes ALTER COLUMN author_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS quotes_author_id_idx ON quotes (author_id);
+
+CREATE OR REPLACE FUNCTION link_quote_author() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO authors (name) VALUES (btrim(NEW.author))
+        ON CONFLICT ((lower(name))) DO NOTHING;
+    SELECT id INTO NEW.author_id FROM authors WHERE lower(name) = lower(btrim(NEW.author));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER quotes_link_author
+    BEFORE INSERT OR UPDATE OF author ON quotes
+    FOR EACH ROW EXECUTE FUNCTION link_quote_author();
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS quote_audit;
+DROP FUNCTION IF EXISTS forbid_quote_audit_changes();
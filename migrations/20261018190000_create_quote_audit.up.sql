@@ -0,0 +1,28 @@
+-- Who changed which quote through the API, and how. Unlike the quotes
+-- themselves, entries are never changed or removed, not even by a reset.
+CREATE TABLE IF NOT EXISTS quote_audit (
+    id BIGSERIAL PRIMARY KEY,
+    at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    operation TEXT NOT NULL
+        CHECK (operation IN ('draft', 'undo', 'remove', 'restore', 'purge', 'revert', 'reset')),
+    actor TEXT,
+    client_ip INET,
+    -- NULL for resets.
+    quote_id UUID,
+    -- The quote as clients saw it before and after the change.
+    before JSONB,
+    after JSONB
+);
+
+CREATE INDEX IF NOT EXISTS quote_audit_quote_id_idx ON quote_audit (quote_id);
+CREATE INDEX IF NOT EXISTS quote_audit_actor_idx ON quote_audit (actor);
+
+CREATE OR REPLACE FUNCTION forbid_quote_audit_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'quote_audit is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER quote_audit_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON quote_audit
+    FOR EACH STATEMENT EXECUTE FUNCTION forbid_quote_audit_changes();
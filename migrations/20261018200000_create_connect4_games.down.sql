@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS connect4_moves;
+DROP TABLE IF EXISTS connect4_rounds;
+DROP TABLE IF EXISTS connect4_games;
@@ -0,0 +1,39 @@
+-- A game lives as long as its ID, and starts a new round, on a fresh board, on
+-- every reset. Rounds keep their moves once finished, as the game archive.
+CREATE TABLE IF NOT EXISTS connect4_games (
+    id UUID PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- When a round was last started or played in, to tell idle games apart.
+    active_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS connect4_rounds (
+    game_id UUID NOT NULL REFERENCES connect4_games (id) ON DELETE CASCADE,
+    round INTEGER NOT NULL CHECK (round >= 1),
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    connect INTEGER NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ,
+    -- NULL in a finished round means nobody won.
+    winner TEXT CHECK (winner IN ('cookie', 'milk')),
+    PRIMARY KEY (game_id, round),
+    CHECK (finished_at IS NOT NULL OR winner IS NULL)
+);
+
+CREATE INDEX IF NOT EXISTS connect4_rounds_finished_at_idx
+    ON connect4_rounds (finished_at DESC)
+    WHERE finished_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS connect4_moves (
+    game_id UUID NOT NULL,
+    round INTEGER NOT NULL,
+    -- From 0, in the order the moves were played.
+    seq INTEGER NOT NULL CHECK (seq >= 0),
+    team TEXT NOT NULL CHECK (team IN ('cookie', 'milk')),
+    -- 1-based, as clients name them.
+    col INTEGER NOT NULL,
+    played_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (game_id, round, seq),
+    FOREIGN KEY (game_id, round) REFERENCES connect4_rounds (game_id, round) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Fails if any import was logged, as the log can't be trimmed.
+ALTER TABLE quote_audit DROP CONSTRAINT IF EXISTS quote_audit_operation_check;
+ALTER TABLE quote_audit ADD CONSTRAINT quote_audit_operation_check
+    CHECK (operation IN ('draft', 'undo', 'remove', 'restore', 'purge', 'revert', 'reset'));
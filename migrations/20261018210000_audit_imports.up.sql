@@ -0,0 +1,4 @@
+-- Imports are logged too, an entry per imported quote.
+ALTER TABLE quote_audit DROP CONSTRAINT IF EXISTS quote_audit_operation_check;
+ALTER TABLE quote_audit ADD CONSTRAINT quote_audit_operation_check
+    CHECK (operation IN ('draft', 'undo', 'remove', 'restore', 'purge', 'revert', 'reset', 'import'));
@@ -0,0 +1,6 @@
+-- The quote picked for each day, so that the pick holds while quotes come and
+-- go. A pick that's gone to the trash is made again.
+CREATE TABLE IF NOT EXISTS quotes_of_the_day (
+    date DATE PRIMARY KEY,
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE
+);
@@ -0,0 +1,90 @@
+//! Runs the service without the Shuttle runtime, e.g. under systemd or in a
+//! plain container.
+
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use poem::{listener::TcpListener, Server};
+use shuttlings_cch24::config::{Backend, Config};
+use sqlx::PgPool;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Configuration file; defaults to `config.toml` if it exists.
+    #[arg(long, env = "CCH24_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on, overriding `server.bind`.
+    #[arg(long, env = "BIND")]
+    bind: Option<SocketAddr>,
+    /// Postgres connection string, overriding `database.url`.
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: Option<String>,
+    /// Log filter, either a level or a full `tracing` directive, overriding
+    /// `server.log_level`.
+    #[arg(long, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+    /// How long to wait for in-flight requests when shutting down, in seconds,
+    /// overriding `server.shutdown_timeout_secs`.
+    #[arg(long, env = "SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(bind) = args.bind {
+        config.server.bind = bind;
+    }
+    if let Some(url) = args.database_url {
+        config.database.url = url;
+    }
+    if let Some(log_level) = args.log_level {
+        config.server.log_level = log_level;
+    }
+    if let Some(timeout) = args.shutdown_timeout {
+        config.server.shutdown_timeout_secs = timeout;
+    }
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&config.server.log_level)?)
+        .init();
+
+    if config.quotes.backend == Backend::Memory {
+        tracing::warn!("Keeping quotes in memory, they will be lost on shutdown");
+    }
+    if config.connect4.backend == Backend::Memory {
+        tracing::warn!("Keeping Connect 4 games in memory, they will be lost on shutdown");
+    }
+    let pool = if [config.quotes.backend, config.connect4.backend].contains(&Backend::Postgres) {
+        let pool = PgPool::connect(&config.database.url).await?;
+        shuttlings_cch24::MIGRATOR.run(&pool).await?;
+        Some(pool)
+    } else {
+        None
+    };
+
+    Server::new(TcpListener::bind(config.server.bind))
+        .run_with_graceful_shutdown(
+            shuttlings_cch24::app(pool, &config),
+            shutdown_signal(),
+            Some(Duration::from_secs(config.server.shutdown_timeout_secs)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Resolves on SIGTERM or Ctrl-C, whichever comes first.
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+
+    tokio::select! {
+        _ = sigterm.recv() => tracing::info!("Received SIGTERM, shutting down"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received Ctrl-C, shutting down"),
+    }
+}
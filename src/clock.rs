@@ -0,0 +1,9 @@
+//! The time, as the stores keep it.
+
+use chrono::{DateTime, SubsecRound as _, Utc};
+
+/// The current time, truncated to the microseconds Postgres keeps, so that
+/// the memory stores hand out the same timestamps the Postgres ones would.
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
@@ -0,0 +1,323 @@
+//! Typed configuration, layered from built-in defaults, a TOML file and
+//! `CCH24_`-prefixed environment variables (nested keys are separated by a
+//! double underscore, e.g. `CCH24_MILK__MAX=10`).
+
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono_tz::Tz;
+use figment::{
+    providers::{Env, Format as _, Toml},
+    Figment,
+};
+use jsonwebtoken::DecodingKey;
+use serde::Deserialize;
+
+use crate::day12::Shape;
+
+/// The file read when neither a path nor `CCH24_CONFIG` is given. It's fine
+/// for it not to exist.
+pub const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to load configuration: {0}")]
+    Load(#[from] Box<figment::Error>),
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub milk: MilkConfig,
+    pub connect4: Connect4Config,
+    pub gifts: GiftsConfig,
+    pub quotes: QuotesConfig,
+}
+
+/// Only used by the standalone server; Shuttle takes care of these itself.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    pub log_level: String,
+    /// How long to wait for in-flight requests when shutting down.
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: ([0, 0, 0, 0], 8000).into(),
+            log_level: "info".to_owned(),
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+/// Only used by the standalone server, and only with the Postgres quote
+/// backend; under Shuttle the database is provisioned by the platform.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "postgres://localhost:5432/".to_owned(),
+        }
+    }
+}
+
+/// The day 9 milk bucket.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MilkConfig {
+    /// Liters in the bucket when it's (re)filled.
+    pub initial: usize,
+    /// Capacity of the bucket.
+    pub max: usize,
+    /// How often a liter trickles back into the bucket.
+    pub refill_interval_ms: u64,
+}
+
+impl MilkConfig {
+    pub fn refill_interval(&self) -> Duration {
+        Duration::from_millis(self.refill_interval_ms)
+    }
+}
+
+impl Default for MilkConfig {
+    fn default() -> Self {
+        Self {
+            initial: 5,
+            max: 5,
+            refill_interval_ms: 1000,
+        }
+    }
+}
+
+/// The day 12 Connect 4 game.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Connect4Config {
+    /// Where games and their moves are stored.
+    pub backend: Backend,
+    /// Seed of the random board generator, restored on every reset.
+    pub seed: u64,
+    /// Columns of a board when the client doesn't ask for a size.
+    pub width: usize,
+    /// Rows of a board when the client doesn't ask for a size.
+    pub height: usize,
+    /// Tiles in a row that win when the client doesn't say.
+    pub connect: usize,
+    /// How long a game created through `/12/games` is kept after it was last
+    /// played or looked at. The default game never expires.
+    pub idle_game_timeout_secs: u64,
+    /// How many moves ahead the `depth-limited` computer opponent looks.
+    pub ai_depth: usize,
+    /// How long the computer opponent may think about a move. It plays the
+    /// best move found so far when time runs out.
+    pub ai_time_budget_ms: u64,
+    /// How long solving a position for `/12/analysis` may take before giving
+    /// up.
+    pub analysis_time_budget_ms: u64,
+    /// How many computer moves and analyses may be worked out at once, each
+    /// on a thread of its own. Any more are turned away until one finishes.
+    pub max_searches: usize,
+}
+
+impl Connect4Config {
+    pub fn idle_game_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_game_timeout_secs)
+    }
+
+    pub fn ai_time_budget(&self) -> Duration {
+        Duration::from_millis(self.ai_time_budget_ms)
+    }
+
+    pub fn analysis_time_budget(&self) -> Duration {
+        Duration::from_millis(self.analysis_time_budget_ms)
+    }
+}
+
+impl Default for Connect4Config {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Postgres,
+            seed: 2024,
+            width: 4,
+            height: 4,
+            connect: 4,
+            idle_game_timeout_secs: 3600,
+            ai_depth: 6,
+            ai_time_budget_ms: 1000,
+            analysis_time_budget_ms: 5000,
+            max_searches: 4,
+        }
+    }
+}
+
+/// The day 16 gift wrapping.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GiftsConfig {
+    /// HMAC secret used to sign and verify wrapped gifts.
+    pub jwt_secret: String,
+    /// PEM-encoded RSA key that Santa's old gifts are verified with, in place
+    /// of the one shipped in `day16_santa_public_key.pem`.
+    pub santa_public_key: Option<String>,
+}
+
+impl Default for GiftsConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: "a".to_owned(),
+            santa_public_key: None,
+        }
+    }
+}
+
+/// The day 19 quote book.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotesConfig {
+    /// Where quotes are stored.
+    pub backend: Backend,
+    /// Quotes per page of `/19/list` when the client doesn't ask for a
+    /// `limit`.
+    pub page_size: usize,
+    /// The largest `limit` clients may ask for.
+    pub max_page_size: usize,
+    /// HMAC secret used to sign `/19/list` page tokens. Must be shared by all
+    /// replicas for tokens to work across them. Without one, a random secret
+    /// is made up on startup.
+    pub cursor_secret: Option<String>,
+    /// IANA time zone whose calendar days `/19/today` follows, e.g.
+    /// `Europe/Berlin`, daylight saving time included.
+    pub today_time_zone: String,
+    /// Mixed into the hash of the date that picks the quote of the day.
+    /// Changing it reshuffles which quote each day gets.
+    pub today_seed: u64,
+    /// HMAC secret that bearer tokens are verified with. Their `sub` claim
+    /// names who made a change in the audit log. Without one, a random
+    /// secret is made up on startup, which no token can be signed with.
+    pub actor_token_secret: Option<String>,
+    /// Proxies whose `X-Real-IP`, `X-Forwarded-For` and `X-Actor` headers are
+    /// believed for the audit log. Anyone else is taken to be the client.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for QuotesConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Postgres,
+            page_size: 3,
+            max_page_size: 100,
+            cursor_secret: None,
+            today_time_zone: "UTC".to_owned(),
+            today_seed: 2024,
+            actor_token_secret: None,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+/// Where a service keeps its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Postgres,
+    /// Process memory, lost on restart. Lets the service run without a
+    /// database.
+    Memory,
+}
+
+impl Config {
+    /// Loads the configuration from `path` (falling back to `CCH24_CONFIG`,
+    /// then [`DEFAULT_PATH`]) and the environment, then validates it.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os("CCH24_CONFIG").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_PATH));
+        let config: Config = Figment::new()
+            .merge(Toml::file(path))
+            .merge(Env::prefixed("CCH24_").ignore(&["config"]).split("__"))
+            .extract()
+            .map_err(Box::new)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Invalid(msg.to_owned()));
+
+        if self.milk.max == 0 {
+            return invalid("milk.max must be at least 1");
+        }
+        if self.milk.initial > self.milk.max {
+            return invalid("milk.initial cannot exceed milk.max");
+        }
+        if self.milk.refill_interval_ms == 0 {
+            return invalid("milk.refill_interval_ms must be positive");
+        }
+        if let Err(msg) = Shape::new(
+            self.connect4.width,
+            self.connect4.height,
+            self.connect4.connect,
+        ) {
+            return invalid(&format!("connect4.{msg}"));
+        }
+        if self.connect4.idle_game_timeout_secs == 0 {
+            return invalid("connect4.idle_game_timeout_secs must be positive");
+        }
+        if self.connect4.ai_depth == 0 {
+            return invalid("connect4.ai_depth must be at least 1");
+        }
+        if self.connect4.ai_time_budget_ms == 0 {
+            return invalid("connect4.ai_time_budget_ms must be positive");
+        }
+        if self.connect4.analysis_time_budget_ms == 0 {
+            return invalid("connect4.analysis_time_budget_ms must be positive");
+        }
+        if self.connect4.max_searches == 0 {
+            return invalid("connect4.max_searches must be at least 1");
+        }
+        if self.gifts.jwt_secret.is_empty() {
+            return invalid("gifts.jwt_secret cannot be empty");
+        }
+        if let Some(pem) = &self.gifts.santa_public_key {
+            if DecodingKey::from_rsa_pem(pem.as_bytes()).is_err() {
+                return invalid("gifts.santa_public_key is not a PEM-encoded RSA key");
+            }
+        }
+        if self.quotes.page_size == 0 {
+            return invalid("quotes.page_size must be at least 1");
+        }
+        if self.quotes.page_size > self.quotes.max_page_size {
+            return invalid("quotes.page_size cannot exceed quotes.max_page_size");
+        }
+        if self.quotes.cursor_secret.as_deref() == Some("") {
+            return invalid("quotes.cursor_secret cannot be empty");
+        }
+        if self.quotes.today_time_zone.parse::<Tz>().is_err() {
+            return invalid("quotes.today_time_zone must be an IANA time zone like Europe/Berlin");
+        }
+        if self.quotes.actor_token_secret.as_deref() == Some("") {
+            return invalid("quotes.actor_token_secret cannot be empty");
+        }
+
+        Ok(())
+    }
+}
@@ -1,29 +1,29 @@
 //! Called day0 instead of day-1 because the latter isn't a valid identifier.
 
-use poem::{
-    get, handler,
-    http::{header, StatusCode}, Response, Route,
-};
+use poem_openapi::{payload::PlainText, ApiResponse, OpenApi};
 
-pub fn route() -> Route {
-    Route::new()
-        .at("/", get(hello_bird))
-        .at("/-1/seek", get(seek))
-}
+use crate::ApiTags;
 
-#[handler]
-fn hello_bird() -> &'static str {
-    "Hello, bird!"
-}
+pub struct Api;
 
-#[handler]
-fn seek() -> Response {
-    Response::builder()
-        .status(StatusCode::FOUND)
-        .header(
-            header::LOCATION,
-            "https://www.youtube.com/watch?v=9Gc4QTqslN4",
-        )
-        .body(())
+#[derive(ApiResponse)]
+enum SeekResponse {
+    /// Off you go.
+    #[oai(status = 302)]
+    Found(#[oai(header = "Location")] String),
 }
 
+#[OpenApi(tag = "ApiTags::Day0")]
+impl Api {
+    /// Greet the bird.
+    #[oai(path = "/", method = "get")]
+    async fn hello_bird(&self) -> PlainText<&'static str> {
+        PlainText("Hello, bird!")
+    }
+
+    /// Redirect to the seek video.
+    #[oai(path = "/-1/seek", method = "get")]
+    async fn seek(&self) -> SeekResponse {
+        SeekResponse::Found("https://www.youtube.com/watch?v=9Gc4QTqslN4".to_owned())
+    }
+}
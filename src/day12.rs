@@ -1,72 +1,742 @@
+mod ai;
 mod connect4;
+mod games;
+mod solver;
+mod store;
 
-use connect4::{Connect4, MoveError, Tile};
-use poem::{
-    get, handler,
-    http::StatusCode,
-    post,
-    web::{Data, Path},
-    EndpointExt as _, IntoEndpoint, Response, Route,
+use chrono::{DateTime, Utc};
+use connect4::{Connect4, GameStatus, MoveError, Tile};
+use games::{Games, LiveGame, LockedGame, DEFAULT_GAME};
+use poem_openapi::{
+    param::{Path, Query},
+    payload::{Json, PlainText},
+    ApiResponse, Enum, Object, OpenApi,
 };
 use rand::SeedableRng as _;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-
-pub fn route() -> impl IntoEndpoint {
-    Route::new()
-        .at("/board", get(get_connect4_board))
-        .at("/reset", post(reset_connect4_board))
-        .at("/place/:team/:column", post(play_connect4))
-        .at("/random-board", get(get_random_connect4))
-        .data(Arc::new(RwLock::new(Connect4::empty())))
-        .data(Connect4Rng(Arc::new(RwLock::new(
-            rand::rngs::StdRng::seed_from_u64(2024),
-        ))))
-}
-
-#[derive(Clone)]
-struct Connect4Rng(Arc<RwLock<rand::rngs::StdRng>>);
-
-#[handler]
-async fn get_connect4_board(board: Data<&Arc<RwLock<Connect4>>>) -> String {
-    format!("{}", board.0.read().await)
-}
-
-#[handler]
-async fn reset_connect4_board(
-    board: Data<&Arc<RwLock<Connect4>>>,
-    rng: Data<&Connect4Rng>,
-) -> String {
-    *board.0.write().await = Connect4::empty();
-    *rng.0 .0.write().await = rand::rngs::StdRng::seed_from_u64(2024);
-    format!("{}", board.0.read().await)
-}
-
-#[handler]
-async fn play_connect4(
-    board: Data<&Arc<RwLock<Connect4>>>,
-    Path((team, column)): Path<(String, String)>,
-) -> Response {
-    let team = match team.as_str() {
-        "cookie" => Tile::Cookie,
-        "milk" => Tile::Milk,
-        _ => return StatusCode::BAD_REQUEST.into(),
-    };
-    let Ok(column) = column.parse() else {
-        return StatusCode::BAD_REQUEST.into();
-    };
-
-    let mut board = board.0.write().await;
-    match board.play(team, column) {
-        Err(MoveError::InvalidColumn) => StatusCode::BAD_REQUEST.into(),
-        Err(MoveError::ColumnFull) | Err(MoveError::GameOver) => Response::builder()
-            .status(StatusCode::SERVICE_UNAVAILABLE)
-            .body(format!("{}", board)),
-        _ => format!("{}", board).into(),
-    }
-}
-
-#[handler]
-async fn get_random_connect4(rng: Data<&Connect4Rng>) -> String {
-    format!("{}", Connect4::random(&mut *rng.0 .0.write().await))
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use store::Outcome;
+use tokio::sync::{Mutex, RwLock, Semaphore};
+use uuid::Uuid;
+
+use crate::{config::Connect4Config, error::AppError, metrics::Metrics, ApiTags};
+
+pub(crate) use connect4::Shape;
+pub use store::{GameStore, MemoryGameStore, PgGameStore, SharedGameStore};
+
+/// Archived games per page when the client doesn't ask for a `limit`.
+const ARCHIVE_PAGE_SIZE: usize = 20;
+/// The largest `limit` clients may ask for.
+const MAX_ARCHIVE_PAGE_SIZE: usize = 100;
+
+pub struct Api {
+    seed: u64,
+    /// What boards look like unless the client asks otherwise.
+    shape: Shape,
+    ai_depth: usize,
+    ai_time_budget: Duration,
+    analysis_time_budget: Duration,
+    /// A permit per computer move or analysis that may be worked out at once.
+    searches: Arc<Semaphore>,
+    /// Locked only to find a game; each game has a lock of its own, held
+    /// while its moves are stored.
+    games: Arc<Mutex<Games>>,
+    store: SharedGameStore,
+    rng: Arc<RwLock<rand::rngs::StdRng>>,
+    metrics: Metrics,
+}
+
+impl Api {
+    pub fn new(config: &Connect4Config, store: SharedGameStore, metrics: &Metrics) -> Self {
+        let shape = Shape::new(config.width, config.height, config.connect)
+            .expect("The board shape is valid");
+
+        Self {
+            seed: config.seed,
+            shape,
+            ai_depth: config.ai_depth,
+            ai_time_budget: config.ai_time_budget(),
+            analysis_time_budget: config.analysis_time_budget(),
+            searches: Arc::new(Semaphore::new(config.max_searches)),
+            games: Arc::new(Mutex::new(Games::new(config.idle_game_timeout()))),
+            store,
+            rng: Arc::new(RwLock::new(rand::rngs::StdRng::seed_from_u64(config.seed))),
+            metrics: metrics.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub(crate) enum Team {
+    Cookie,
+    Milk,
+}
+
+impl Team {
+    /// As it appears in paths and JSON.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Team::Cookie => "cookie",
+            Team::Milk => "milk",
+        }
+    }
+
+    fn opponent(self) -> Self {
+        match self {
+            Team::Cookie => Team::Milk,
+            Team::Milk => Team::Cookie,
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cookie" => Some(Team::Cookie),
+            "milk" => Some(Team::Milk),
+            _ => None,
+        }
+    }
+}
+
+impl From<Team> for Tile {
+    fn from(team: Team) -> Self {
+        match team {
+            Team::Cookie => Tile::Cookie,
+            Team::Milk => Tile::Milk,
+        }
+    }
+}
+
+/// How hard the computer opponent tries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "kebab-case")]
+enum Difficulty {
+    /// Plays any column with room left.
+    Random,
+    /// Takes a win when it sees one, or else blocks one, but looks no
+    /// further.
+    Greedy,
+    /// Looks a configured number of moves ahead.
+    DepthLimited,
+    /// Looks ahead to the end of the game, which it only gets to in time on
+    /// small boards such as the 4x4 one.
+    Perfect,
+}
+
+#[derive(ApiResponse)]
+enum PlayResponse {
+    /// The move was played; the board after it.
+    #[oai(status = 200)]
+    Played(PlainText<String>),
+    /// The column is full or the game is already over; the unchanged board.
+    #[oai(status = 503)]
+    Rejected(PlainText<String>),
+}
+
+#[derive(ApiResponse)]
+enum ComputerPlayResponse {
+    /// The computer played; the board after its move.
+    #[oai(status = 200)]
+    Played(
+        PlainText<String>,
+        /// The column played, 1-based.
+        #[oai(header = "X-Column")]
+        usize,
+    ),
+    /// The game is already over; the unchanged board.
+    #[oai(status = 503)]
+    Rejected(PlainText<String>),
+}
+
+#[derive(Object)]
+struct NewGame {
+    /// Addresses the game under `/12/games/{id}`.
+    id: Uuid,
+    board: String,
+}
+
+#[derive(ApiResponse)]
+enum CreateGameResponse {
+    /// The new game, on an empty board.
+    #[oai(status = 201)]
+    Created(Json<NewGame>),
+}
+
+/// A finished round of a game.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct ArchivedGame {
+    /// The game's ID; the default game's is all zeros.
+    id: Uuid,
+    /// Every reset of a game starts a new round, counting from 1.
+    round: i32,
+    width: usize,
+    height: usize,
+    connect: usize,
+    /// Null if nobody won.
+    winner: Option<Team>,
+    /// How many moves were played.
+    moves: usize,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Object)]
+pub(crate) struct PlayedMove {
+    team: Team,
+    /// 1-based, left to right.
+    column: usize,
+    played_at: DateTime<Utc>,
+}
+
+/// What a position is worth with perfect play from both teams.
+#[derive(Object)]
+struct Analysis {
+    /// The team to move.
+    team: Team,
+    /// Null if the game ends in a draw.
+    winner: Option<Team>,
+    /// How many moves, both teams', are left until the game ends.
+    moves: usize,
+    /// Positive if `team` wins, negative if it loses and 0 for a draw. The
+    /// further from 0, the sooner the game ends.
+    score: i32,
+    /// The columns scoring best for `team`, 1-based, left to right.
+    best_columns: Vec<usize>,
+    /// Every column with room left, left to right, as played by `team`.
+    columns: Vec<ColumnScore>,
+    /// How the game goes from here, starting with `team`'s move.
+    principal_variation: Vec<PlannedMove>,
+}
+
+/// What playing a column is worth with perfect play from there on.
+#[derive(Object)]
+struct ColumnScore {
+    /// 1-based, left to right.
+    column: usize,
+    /// As for the whole position.
+    score: i32,
+    /// Null if the game ends in a draw.
+    winner: Option<Team>,
+    /// How many moves, this one included, are left until the game ends.
+    moves: usize,
+}
+
+#[derive(Object)]
+struct PlannedMove {
+    team: Team,
+    /// 1-based, left to right.
+    column: usize,
+}
+
+#[derive(Object)]
+struct ReplayedGame {
+    #[oai(flatten)]
+    game: ArchivedGame,
+    /// In the order they were played.
+    moves: Vec<PlayedMove>,
+    /// The board as the game ended.
+    board: String,
+}
+
+#[OpenApi(prefix_path = "/12", tag = "ApiTags::Day12")]
+impl Api {
+    /// Render the board of the default game.
+    #[oai(path = "/board", method = "get")]
+    async fn get_connect4_board(&self) -> Result<PlainText<String>, AppError> {
+        self.render(DEFAULT_GAME).await
+    }
+
+    /// Start the default game over on an empty board, of the configured size
+    /// unless asked otherwise, and reseed the random board generator.
+    #[oai(path = "/reset", method = "post")]
+    async fn reset_connect4_board(
+        &self,
+        /// Columns, up to 16.
+        width: Query<Option<usize>>,
+        /// Rows, up to 16.
+        height: Query<Option<usize>>,
+        /// Tiles in a row that win, up to the longer side.
+        connect: Query<Option<usize>>,
+    ) -> Result<PlainText<String>, AppError> {
+        let shape = resize(self.shape, width.0, height.0, connect.0)?;
+        let board = self.restart(DEFAULT_GAME, |_| Ok(shape)).await?;
+        *self.rng.write().await = rand::rngs::StdRng::seed_from_u64(self.seed);
+        Ok(board)
+    }
+
+    /// Drop a tile for `team` into `column` (1-based, left to right) in the
+    /// default game.
+    #[oai(path = "/place/:team/:column", method = "post")]
+    async fn play_connect4(
+        &self,
+        team: Path<Team>,
+        column: Path<usize>,
+    ) -> Result<PlayResponse, AppError> {
+        self.play(DEFAULT_GAME, team.0, *column, None).await
+    }
+
+    /// Have the computer play a move for `team` in the default game.
+    #[oai(path = "/ai/:team", method = "post")]
+    async fn play_connect4_computer(
+        &self,
+        team: Path<Team>,
+        /// Defaults to `depth-limited`.
+        difficulty: Query<Option<Difficulty>>,
+    ) -> Result<ComputerPlayResponse, AppError> {
+        self.play_computer(DEFAULT_GAME, team.0, difficulty.0).await
+    }
+
+    /// Start a game of its own for the client, of the configured size unless
+    /// asked otherwise. It's forgotten once left alone for a while.
+    #[oai(path = "/games", method = "post")]
+    async fn create_connect4_game(
+        &self,
+        /// Columns, up to 16.
+        width: Query<Option<usize>>,
+        /// Rows, up to 16.
+        height: Query<Option<usize>>,
+        /// Tiles in a row that win, up to the longer side.
+        connect: Query<Option<usize>>,
+    ) -> Result<CreateGameResponse, AppError> {
+        let shape = resize(self.shape, width.0, height.0, connect.0)?;
+        let id = Uuid::new_v4();
+        self.store.create(id, shape).await?;
+
+        let board = Connect4::empty(shape);
+        let rendered = format!("{}", board);
+        self.games
+            .lock()
+            .await
+            .insert(id, LiveGame::new(board, 1, 0));
+        Ok(CreateGameResponse::Created(Json(NewGame {
+            id,
+            board: rendered,
+        })))
+    }
+
+    /// Render the board of game `id`.
+    #[oai(path = "/games/:id/board", method = "get")]
+    async fn get_connect4_game_board(&self, id: Path<Uuid>) -> Result<PlainText<String>, AppError> {
+        self.render(*id).await
+    }
+
+    /// Start game `id` over on an empty board, of the same size unless asked
+    /// otherwise. Unlike the default game's reset, the random board generator
+    /// is left alone.
+    #[oai(path = "/games/:id/reset", method = "post")]
+    async fn reset_connect4_game(
+        &self,
+        id: Path<Uuid>,
+        /// Columns, up to 16.
+        width: Query<Option<usize>>,
+        /// Rows, up to 16.
+        height: Query<Option<usize>>,
+        /// Tiles in a row that win, up to the longer side.
+        connect: Query<Option<usize>>,
+    ) -> Result<PlainText<String>, AppError> {
+        self.restart(*id, |current| resize(current, width.0, height.0, connect.0))
+            .await
+    }
+
+    /// Drop a tile for `team` into `column` (1-based, left to right) of game
+    /// `id`.
+    #[oai(path = "/games/:id/place/:team/:column", method = "post")]
+    async fn play_connect4_game(
+        &self,
+        id: Path<Uuid>,
+        team: Path<Team>,
+        column: Path<usize>,
+    ) -> Result<PlayResponse, AppError> {
+        self.play(*id, team.0, *column, None).await
+    }
+
+    /// Have the computer play a move for `team` in game `id`. It expects the
+    /// teams to take turns from there on.
+    #[oai(path = "/games/:id/ai/:team", method = "post")]
+    async fn play_connect4_game_computer(
+        &self,
+        id: Path<Uuid>,
+        team: Path<Team>,
+        /// Defaults to `depth-limited`.
+        difficulty: Query<Option<Difficulty>>,
+    ) -> Result<ComputerPlayResponse, AppError> {
+        self.play_computer(*id, team.0, difficulty.0).await
+    }
+
+    /// Solve the default game's position: who wins with perfect play from
+    /// here, how soon, and how.
+    #[oai(path = "/analysis", method = "get")]
+    async fn analyse_connect4_board(
+        &self,
+        /// Defaults to whichever team has fewer tiles on the board, cookie if
+        /// neither.
+        team: Query<Option<Team>>,
+    ) -> Result<Json<Analysis>, AppError> {
+        self.analyse_game(DEFAULT_GAME, team.0).await
+    }
+
+    /// Solve a board as rendered by the board routes, won by `connect` tiles
+    /// in a row.
+    #[oai(path = "/analysis", method = "post")]
+    async fn analyse_connect4_position(
+        &self,
+        board: PlainText<String>,
+        /// Defaults to whichever team has fewer tiles on the board, cookie if
+        /// neither.
+        team: Query<Option<Team>>,
+        /// Defaults to the configured number.
+        connect: Query<Option<usize>>,
+    ) -> Result<Json<Analysis>, AppError> {
+        let board = Connect4::parse(&board.0, connect.unwrap_or(self.shape.connect()))
+            .map_err(AppError::BadRequest)?;
+        self.analyse(board, team.0).await
+    }
+
+    /// Solve the position of game `id`.
+    #[oai(path = "/games/:id/analysis", method = "get")]
+    async fn analyse_connect4_game(
+        &self,
+        id: Path<Uuid>,
+        /// Defaults to whichever team has fewer tiles on the board, cookie if
+        /// neither.
+        team: Query<Option<Team>>,
+    ) -> Result<Json<Analysis>, AppError> {
+        self.analyse_game(*id, team.0).await
+    }
+
+    /// List finished games, most recently finished first. Each round of a
+    /// game, as started by a reset, is archived on its own, including those
+    /// of the default game and of games that have since expired.
+    #[oai(path = "/archive", method = "get")]
+    async fn list_connect4_archive(
+        &self,
+        /// Games per page; defaults to 20.
+        limit: Query<Option<usize>>,
+        /// How many games to skip.
+        offset: Query<Option<usize>>,
+    ) -> Result<Json<Vec<ArchivedGame>>, AppError> {
+        let limit = limit.0.unwrap_or(ARCHIVE_PAGE_SIZE);
+        if !(1..=MAX_ARCHIVE_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {MAX_ARCHIVE_PAGE_SIZE}"
+            )));
+        }
+
+        Ok(Json(
+            self.store.archive(offset.0.unwrap_or(0), limit).await?,
+        ))
+    }
+
+    /// Replay a finished round of game `id`, move by move.
+    #[oai(path = "/archive/:id/:round", method = "get")]
+    async fn get_connect4_archived_game(
+        &self,
+        id: Path<Uuid>,
+        round: Path<i32>,
+    ) -> Result<Json<ReplayedGame>, AppError> {
+        let (game, moves) = self.store.archived(*id, *round).await?.ok_or_else(|| {
+            AppError::NotFound(format!("No finished round {} of game {}", *round, *id))
+        })?;
+        let shape = Shape::new(game.width, game.height, game.connect)
+            .map_err(|err| AppError::Internal(format!("Unplayable board archived: {err}")))?;
+        let board = replay(*id, shape, &moves)?;
+
+        Ok(Json(ReplayedGame {
+            game,
+            moves,
+            board: format!("{}", board),
+        }))
+    }
+
+    /// Generate the next board from the seeded random generator, of the
+    /// configured size unless asked otherwise.
+    #[oai(path = "/random-board", method = "get")]
+    async fn get_random_connect4(
+        &self,
+        /// Columns, up to 16.
+        width: Query<Option<usize>>,
+        /// Rows, up to 16.
+        height: Query<Option<usize>>,
+        /// Tiles in a row that win, up to the longer side.
+        connect: Query<Option<usize>>,
+    ) -> Result<PlainText<String>, AppError> {
+        let shape = resize(self.shape, width.0, height.0, connect.0)?;
+        Ok(PlainText(format!(
+            "{}",
+            Connect4::random(shape, &mut *self.rng.write().await)
+        )))
+    }
+}
+
+impl Api {
+    async fn render(&self, id: Uuid) -> Result<PlainText<String>, AppError> {
+        let game = self.live(id).await?;
+        Ok(PlainText(format!("{}", game.board)))
+    }
+
+    /// Starts the next round of game `id`, on a board shaped by `shape` from
+    /// the current one.
+    async fn restart(
+        &self,
+        id: Uuid,
+        shape: impl FnOnce(Shape) -> Result<Shape, AppError>,
+    ) -> Result<PlainText<String>, AppError> {
+        let mut game = self.live(id).await?;
+        let shape = shape(game.board.shape())?;
+        let round = game.round + 1;
+        if let Err(err) = self.store.start_round(id, round, shape).await {
+            game.forget();
+            return Err(err);
+        }
+
+        *game = LiveGame::new(Connect4::empty(shape), round, 0);
+        Ok(PlainText(format!("{}", game.board)))
+    }
+
+    /// Plays `column` for `team`, as long as the game is still at
+    /// `expected` round and move if given.
+    async fn play(
+        &self,
+        id: Uuid,
+        team: Team,
+        column: usize,
+        expected: Option<(i32, usize)>,
+    ) -> Result<PlayResponse, AppError> {
+        let mut game = self.live(id).await?;
+        if expected.is_some_and(|expected| expected != (game.round, game.moves)) {
+            return Err(AppError::Conflict(format!(
+                "Game {id} changed while the computer was thinking"
+            )));
+        }
+        let mut board = game.board.clone();
+        match board.play(team.into(), column) {
+            Err(MoveError::InvalidColumn) => Err(AppError::BadRequest(format!(
+                "Column {} is outside the board",
+                column
+            ))),
+            // Not an error as far as the client is concerned: they still get to see the board.
+            Err(MoveError::ColumnFull) | Err(MoveError::GameOver) => {
+                Ok(PlayResponse::Rejected(PlainText(format!("{}", game.board))))
+            }
+            _ => {
+                let outcome = match board.winner() {
+                    GameStatus::Ongoing => None,
+                    GameStatus::NoWinner => Some(Outcome::NoWinner),
+                    GameStatus::Winner(Tile::Cookie) => Some(Outcome::Winner(Team::Cookie)),
+                    GameStatus::Winner(Tile::Milk) => Some(Outcome::Winner(Team::Milk)),
+                    GameStatus::Winner(Tile::Empty) => unreachable!("Empty tiles never win"),
+                };
+                let recorded = self
+                    .store
+                    .record_move(id, game.round, game.moves, team, column, outcome)
+                    .await;
+                if let Err(err) = recorded {
+                    // Another replica may have played in the meantime.
+                    game.forget();
+                    return Err(err);
+                }
+                game.board = board;
+                game.moves += 1;
+
+                if let Some(outcome) = outcome {
+                    self.metrics
+                        .connect4_games_finished
+                        .with_label_values(&[outcome.winner().map_or("none", Team::name)])
+                        .inc();
+                }
+
+                Ok(PlayResponse::Played(PlainText(format!("{}", game.board))))
+            }
+        }
+    }
+
+    async fn play_computer(
+        &self,
+        id: Uuid,
+        team: Team,
+        difficulty: Option<Difficulty>,
+    ) -> Result<ComputerPlayResponse, AppError> {
+        let (board, at) = {
+            let game = self.live(id).await?;
+            (game.board.clone(), (game.round, game.moves))
+        };
+        let strategy = match difficulty.unwrap_or(Difficulty::DepthLimited) {
+            Difficulty::Random => ai::Strategy::Random,
+            Difficulty::Greedy => ai::Strategy::Greedy,
+            Difficulty::DepthLimited => ai::Strategy::Search {
+                depth: self.ai_depth,
+            },
+            Difficulty::Perfect => ai::Strategy::Search { depth: usize::MAX },
+        };
+
+        let deadline = Instant::now() + self.ai_time_budget;
+        let rendered = format!("{}", board);
+        let column = self
+            .search(move || ai::choose(&board, team.into(), strategy, deadline))
+            .await?;
+        let Some(column) = column else {
+            return Ok(ComputerPlayResponse::Rejected(PlainText(rendered)));
+        };
+
+        Ok(match self.play(id, team, column, Some(at)).await? {
+            PlayResponse::Played(board) => ComputerPlayResponse::Played(board, column),
+            PlayResponse::Rejected(board) => ComputerPlayResponse::Rejected(board),
+        })
+    }
+
+    /// Runs `search` on a thread of its own, as it takes up to a whole time
+    /// budget, which is too long to hold up other requests. Fails if as many
+    /// searches as allowed are running already.
+    async fn search<T: Send + 'static>(
+        &self,
+        search: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, AppError> {
+        let permit = self.searches.clone().try_acquire_owned().map_err(|_| {
+            AppError::TooManyRequests("Too many searches are running; try again shortly".to_owned())
+        })?;
+
+        // The permit goes with the search, which runs to its deadline even if
+        // the client gives up waiting.
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            search()
+        })
+        .await
+        .map_err(|err| AppError::Internal(format!("The search failed: {err}")))
+    }
+
+    async fn analyse_game(&self, id: Uuid, team: Option<Team>) -> Result<Json<Analysis>, AppError> {
+        let board = self.live(id).await?.board.clone();
+        self.analyse(board, team).await
+    }
+
+    async fn analyse(
+        &self,
+        board: Connect4,
+        team: Option<Team>,
+    ) -> Result<Json<Analysis>, AppError> {
+        if board.winner() != GameStatus::Ongoing {
+            return Err(AppError::Unprocessable(
+                "The game is already over".to_owned(),
+            ));
+        }
+        let team = team.unwrap_or(if board.tiles(Tile::Milk) < board.tiles(Tile::Cookie) {
+            Team::Milk
+        } else {
+            Team::Cookie
+        });
+
+        let budget = self.analysis_time_budget;
+        let deadline = Instant::now() + budget;
+        let solution = self
+            .search(move || solver::solve(&board, team.into(), deadline))
+            .await?
+            .map_err(|solver::OutOfTime| {
+                AppError::Unprocessable(format!(
+                    "The position couldn't be solved within {} ms",
+                    budget.as_millis()
+                ))
+            })?;
+
+        let winner = |score: i32| match score.signum() {
+            1 => Some(team),
+            -1 => Some(team.opponent()),
+            _ => None,
+        };
+        let mut principal_variation = Vec::new();
+        let mut mover = team;
+        for &column in &solution.variation {
+            principal_variation.push(PlannedMove {
+                team: mover,
+                column,
+            });
+            mover = mover.opponent();
+        }
+
+        Ok(Json(Analysis {
+            team,
+            winner: winner(solution.score),
+            moves: solution.moves(solution.score),
+            score: solution.score,
+            best_columns: solution
+                .columns
+                .iter()
+                .filter(|&&(_, score)| score == solution.score)
+                .map(|&(column, _)| column)
+                .collect(),
+            columns: solution
+                .columns
+                .iter()
+                .map(|&(column, score)| ColumnScore {
+                    column,
+                    score,
+                    winner: winner(score),
+                    moves: solution.moves(score),
+                })
+                .collect(),
+            principal_variation,
+        }))
+    }
+
+    /// Game `id`, locked, and loaded from the store unless it's being played
+    /// already.
+    async fn live(&self, id: Uuid) -> Result<LockedGame, AppError> {
+        let (slot, idle_timeout) = {
+            let mut games = self.games.lock().await;
+            (games.slot(id), games.idle_timeout())
+        };
+        slot.lock(|| self.load(id, idle_timeout)).await
+    }
+
+    /// Rebuilds game `id` from its moves. The default game is created on
+    /// first use, and never expires.
+    async fn load(&self, id: Uuid, idle_timeout: Duration) -> Result<LiveGame, AppError> {
+        let not_found = || AppError::NotFound(format!("No game {id}; it may have expired"));
+        let saved = match self.store.load(id).await? {
+            Some(saved) => saved,
+            None if id == DEFAULT_GAME => {
+                self.store.create(id, self.shape).await?;
+                self.store.load(id).await?.ok_or_else(not_found)?
+            }
+            None => return Err(not_found()),
+        };
+        // Only moves and resets are stored, so after a restart a game that
+        // was merely looked at expires a little early.
+        let idle = (Utc::now() - saved.active_at).to_std().unwrap_or_default();
+        if id != DEFAULT_GAME && idle >= idle_timeout {
+            return Err(not_found());
+        }
+
+        let board = replay(id, saved.shape, &saved.moves)?;
+        Ok(LiveGame::new(board, saved.round, saved.moves.len()))
+    }
+}
+
+/// The board of game `id` after `moves`.
+fn replay(id: Uuid, shape: Shape, moves: &[PlayedMove]) -> Result<Connect4, AppError> {
+    let mut board = Connect4::empty(shape);
+    for played in moves {
+        board.play(played.team.into(), played.column).map_err(|_| {
+            AppError::Internal(format!("Game {id} has a move that can't be replayed"))
+        })?;
+    }
+
+    Ok(board)
+}
+
+/// `shape` with whatever the client asked to change about it.
+fn resize(
+    shape: Shape,
+    width: Option<usize>,
+    height: Option<usize>,
+    connect: Option<usize>,
+) -> Result<Shape, AppError> {
+    Shape::new(
+        width.unwrap_or(shape.width()),
+        height.unwrap_or(shape.height()),
+        connect.unwrap_or(shape.connect()),
+    )
+    .map_err(AppError::BadRequest)
 }
@@ -0,0 +1,235 @@
+//! The computer opponent: negamax with alpha-beta pruning over [`Connect4`],
+//! assuming the teams take turns from here on.
+
+use rand::seq::IteratorRandom as _;
+use std::time::Instant;
+
+use super::connect4::{Connect4, GameStatus, Tile};
+
+/// How hard the computer thinks about a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Any column with room left.
+    Random,
+    /// Wins right away if it can, else blocks the opponent from doing so,
+    /// else plays whatever leaves the best looking board.
+    Greedy,
+    /// Looks `depth` moves ahead, or as far as the time allows. Deep enough
+    /// to fill the board, it plays perfectly.
+    Search { depth: usize },
+}
+
+/// Beats any score [`evaluate`] gives. Wins are worth this less the moves
+/// they take, so quick wins and slow losses are preferred.
+const WIN: i32 = 1_000_000;
+
+/// Same for alpha and beta, out of reach of any score.
+const INFINITY: i32 = WIN + 1;
+
+/// How many nodes are searched between looks at the clock.
+const CLOCK_INTERVAL: u64 = 256;
+
+/// The column (1-based) for `team` to play, or `None` if the game is over.
+/// A search that runs into `deadline` settles for the deepest look ahead it
+/// finished.
+pub fn choose(
+    board: &Connect4,
+    team: Tile,
+    strategy: Strategy,
+    deadline: Instant,
+) -> Option<usize> {
+    if board.winner() != GameStatus::Ongoing {
+        return None;
+    }
+
+    let order = center_first(board);
+    match strategy {
+        Strategy::Random => order
+            .into_iter()
+            .filter(|&col| board.is_open(col))
+            .choose(&mut rand::thread_rng()),
+        Strategy::Greedy => greedy(board.clone(), team, &order),
+        Strategy::Search { depth } => {
+            let mut search = Search {
+                board: board.clone(),
+                order: order.clone(),
+                deadline,
+                nodes: 0,
+            };
+            search.iterate(team, depth, order)
+        }
+    }
+}
+
+/// Open or not, middle columns first, as they take part in the most lines.
+pub fn center_first(board: &Connect4) -> Vec<usize> {
+    let width = board.shape().width();
+    let mut order: Vec<usize> = (1..=width).collect();
+    order.sort_by_key(|&col| (2 * col).abs_diff(width + 1));
+    order
+}
+
+fn greedy(mut board: Connect4, team: Tile, order: &[usize]) -> Option<usize> {
+    let win_or_block = board
+        .winning_column(team)
+        .or_else(|| board.winning_column(team.opponent()));
+    if win_or_block.is_some() {
+        return win_or_block;
+    }
+
+    let mut best = None;
+    for &col in order {
+        if board.drop_tile(team, col).is_none() {
+            continue;
+        }
+        let score = evaluate(&board, team);
+        board.undo(col);
+        if best.is_none_or(|(_, best)| score > best) {
+            best = Some((col, score));
+        }
+    }
+
+    best.map(|(col, _)| col)
+}
+
+struct OutOfTime;
+
+struct Search {
+    board: Connect4,
+    /// Middle columns first make for more cutoffs.
+    order: Vec<usize>,
+    deadline: Instant,
+    nodes: u64,
+}
+
+impl Search {
+    /// Searches one move deeper at a time, up to `depth` or the end of the
+    /// game, starting each round from the best move of the last.
+    fn iterate(&mut self, team: Tile, depth: usize, mut order: Vec<usize>) -> Option<usize> {
+        let empty = self.board.tiles(Tile::Empty);
+
+        let mut best = order.iter().copied().find(|&col| self.board.is_open(col));
+        for depth in 1..=depth.min(empty) {
+            let Ok(Some((col, score))) = self.root(team, depth, &order) else {
+                break;
+            };
+            best = Some(col);
+            // A forced win or loss won't change by looking further.
+            if score.abs() > WIN / 2 {
+                break;
+            }
+            let at = order
+                .iter()
+                .position(|&c| c == col)
+                .expect("Searched columns are ordered");
+            order[..=at].rotate_right(1);
+        }
+
+        best
+    }
+
+    fn root(
+        &mut self,
+        team: Tile,
+        depth: usize,
+        order: &[usize],
+    ) -> Result<Option<(usize, i32)>, OutOfTime> {
+        let mut best = None;
+        let mut alpha = -INFINITY;
+        for &col in order {
+            let score = match self.board.drop_tile(team, col) {
+                None => continue,
+                Some(row) if self.board.in_line(col - 1, row) => WIN,
+                Some(_) => -self.negamax(team.opponent(), depth - 1, 1, -INFINITY, -alpha)?,
+            };
+            self.board.undo(col);
+            if score > alpha {
+                alpha = score;
+                best = Some((col, score));
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// The score of the board for `team`, to move after `ply` moves of look
+    /// ahead, if it's worth between `alpha` and `beta`.
+    fn negamax(
+        &mut self,
+        team: Tile,
+        depth: usize,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Result<i32, OutOfTime> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= self.deadline {
+            return Err(OutOfTime);
+        }
+        // Looking for a win on the spot first saves searching everything else.
+        if self.board.winning_column(team).is_some() {
+            return Ok(WIN - ply);
+        }
+        if depth == 0 {
+            return Ok(evaluate(&self.board, team));
+        }
+
+        let mut best = None;
+        for i in 0..self.order.len() {
+            let col = self.order[i];
+            if self.board.drop_tile(team, col).is_none() {
+                continue;
+            }
+            let score = -self.negamax(team.opponent(), depth - 1, ply + 1, -beta, -alpha)?;
+            self.board.undo(col);
+            best = Some(best.map_or(score, |best: i32| best.max(score)));
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        // Nowhere left to play: a draw.
+        Ok(best.unwrap_or(0))
+    }
+}
+
+/// How good the board looks for `team`: every stretch of `connect` tiles that
+/// only one team has tiles in counts for them, the more so the more tiles.
+fn evaluate(board: &Connect4, team: Tile) -> i32 {
+    let shape = board.shape();
+    let (width, height, connect) = (
+        shape.width() as isize,
+        shape.height() as isize,
+        shape.connect() as isize,
+    );
+
+    let mut score = 0;
+    for (dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)] {
+        for y in 0..height {
+            for x in 0..width {
+                let (end_x, end_y) = (x + dx * (connect - 1), y + dy * (connect - 1));
+                if !(0..width).contains(&end_x) || !(0..height).contains(&end_y) {
+                    continue;
+                }
+
+                let (mut mine, mut theirs) = (0, 0);
+                for step in 0..connect {
+                    let tile = board.tile((x + dx * step) as usize, (y + dy * step) as usize);
+                    if tile == team {
+                        mine += 1;
+                    } else if tile != Tile::Empty {
+                        theirs += 1;
+                    }
+                }
+                match (mine, theirs) {
+                    (0, theirs) => score -= theirs * theirs,
+                    (mine, 0) => score += mine * mine,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    score
+}
@@ -2,7 +2,10 @@ use std::fmt::Display;
 
 use rand::Rng;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// The longest side a board may have.
+pub const MAX_SIDE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tile {
     Empty,
     Cookie,
@@ -10,6 +13,15 @@ pub enum Tile {
 }
 
 impl Tile {
+    /// The other team's tile.
+    pub fn opponent(self) -> Tile {
+        match self {
+            Tile::Cookie => Tile::Milk,
+            Tile::Milk => Tile::Cookie,
+            Tile::Empty => Tile::Empty,
+        }
+    }
+
     fn emoji(&self) -> &str {
         match self {
             Tile::Empty => "⬛",
@@ -26,95 +38,187 @@ pub enum MoveError {
 }
 
 #[derive(PartialEq, Eq)]
-enum GameStatus {
+pub enum GameStatus {
     Ongoing,
     NoWinner,
     Winner(Tile),
 }
 
+/// How big a board is, and how many tiles in a row win on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shape {
+    width: usize,
+    height: usize,
+    connect: usize,
+}
+
+impl Shape {
+    /// Fails with what's wrong if no game could be played on such a board.
+    pub fn new(width: usize, height: usize, connect: usize) -> Result<Self, String> {
+        if !(1..=MAX_SIDE).contains(&width) {
+            return Err(format!("width must be between 1 and {MAX_SIDE}"));
+        }
+        if !(1..=MAX_SIDE).contains(&height) {
+            return Err(format!("height must be between 1 and {MAX_SIDE}"));
+        }
+        if !(2..=width.max(height)).contains(&connect) {
+            return Err("connect must be between 2 and the longer side of the board".to_owned());
+        }
+
+        Ok(Self {
+            width,
+            height,
+            connect,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn connect(&self) -> usize {
+        self.connect
+    }
+}
+
+#[derive(Clone)]
 pub struct Connect4 {
-    board: [[Tile; 4]; 4],
+    shape: Shape,
+    /// Columns left to right, each from the top down.
+    board: Vec<Vec<Tile>>,
 }
 
 impl Connect4 {
-    pub fn empty() -> Self {
+    pub fn empty(shape: Shape) -> Self {
         Connect4 {
-            board: [[Tile::Empty; 4]; 4],
+            shape,
+            board: vec![vec![Tile::Empty; shape.height]; shape.width],
         }
     }
 
-    pub fn random(rng: &mut rand::rngs::StdRng) -> Self {
-        let mut board = [[Tile::Milk; 4]; 4];
+    // Tiles must be drawn row by row for the seeded boards to stay stable.
+    #[allow(clippy::needless_range_loop)]
+    pub fn random(shape: Shape, rng: &mut rand::rngs::StdRng) -> Self {
+        let mut board = vec![vec![Tile::Milk; shape.height]; shape.width];
 
-        for y in 0..4 {
-            for x in 0..4 {
+        for y in 0..shape.height {
+            for x in 0..shape.width {
                 if rng.gen() {
                     board[x][y] = Tile::Cookie;
                 }
             }
         }
 
-        Self { board }
+        Self { shape, board }
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.shape
     }
 
     pub fn play(&mut self, team: Tile, col_idx: usize) -> Result<(), MoveError> {
-        if col_idx < 1 || col_idx > 4 {
+        if !(1..=self.shape.width).contains(&col_idx) {
             return Err(MoveError::InvalidColumn);
         }
         if self.winner() != GameStatus::Ongoing {
             return Err(MoveError::GameOver);
         }
 
-        let column = &mut self.board[col_idx - 1];
-        let Some(drop_idx) = column.iter().rev().position(|&t| t == Tile::Empty) else {
-            return Err(MoveError::ColumnFull);
-        };
-        column[3 - drop_idx] = team;
+        self.drop_tile(team, col_idx)
+            .map(|_| ())
+            .ok_or(MoveError::ColumnFull)
+    }
 
-        Ok(())
+    /// Drops a tile into `col_idx` (1-based) whether or not the game is over,
+    /// for looking ahead. Returns the row it landed in, counting from the top,
+    /// or `None` if the column is full.
+    pub fn drop_tile(&mut self, team: Tile, col_idx: usize) -> Option<usize> {
+        let column = &mut self.board[col_idx - 1];
+        let row = column.iter().rposition(|&t| t == Tile::Empty)?;
+        column[row] = team;
+        Some(row)
     }
 
-    fn winner(&self) -> GameStatus {
-        // Rows
-        for y in 0..4 {
-            let initial = self.board[0][y];
-            if initial != Tile::Empty
-                && initial == self.board[1][y]
-                && initial == self.board[2][y]
-                && initial == self.board[3][y]
-            {
-                return GameStatus::Winner(initial);
-            }
+    /// Takes back the top tile of `col_idx` (1-based).
+    pub fn undo(&mut self, col_idx: usize) {
+        let column = &mut self.board[col_idx - 1];
+        if let Some(top) = column.iter_mut().find(|t| **t != Tile::Empty) {
+            *top = Tile::Empty;
         }
+    }
 
-        // Columns
-        for x in 0..4 {
-            let initial = self.board[x][0];
-            if initial != Tile::Empty
-                && initial == self.board[x][1]
-                && initial == self.board[x][2]
-                && initial == self.board[x][3]
-            {
-                return GameStatus::Winner(initial);
-            }
-        }
+    /// Whether `col_idx` (1-based) has room for another tile.
+    pub fn is_open(&self, col_idx: usize) -> bool {
+        self.board[col_idx - 1][0] == Tile::Empty
+    }
 
-        // Diagonals
-        let initial = self.board[0][0];
-        if initial != Tile::Empty
-            && initial == self.board[1][1]
-            && initial == self.board[2][2]
-            && initial == self.board[3][3]
-        {
-            return GameStatus::Winner(initial);
+    /// The tile in column `x` and row `y`, both from 0 and from the top left.
+    pub fn tile(&self, x: usize, y: usize) -> Tile {
+        self.board[x][y]
+    }
+
+    /// How many of the board's cells hold `tile`.
+    pub fn tiles(&self, tile: Tile) -> usize {
+        self.board.iter().flatten().filter(|&&t| t == tile).count()
+    }
+
+    /// Whether the tile at `x`, `y` is part of a winning line, which is
+    /// cheaper to tell than [`Connect4::winner`] right after it was dropped.
+    pub fn in_line(&self, x: usize, y: usize) -> bool {
+        self.completes_line(x, y, self.board[x][y])
+    }
+
+    /// The leftmost column (1-based) where `team` would win by dropping a
+    /// tile, if there is one.
+    pub fn winning_column(&self, team: Tile) -> Option<usize> {
+        (0..self.shape.width)
+            .find(|&x| {
+                let column = &self.board[x];
+                column
+                    .iter()
+                    .rposition(|&t| t == Tile::Empty)
+                    .is_some_and(|y| self.completes_line(x, y, team))
+            })
+            .map(|x| x + 1)
+    }
+
+    /// Whether `tile` at `x`, `y` would be part of a winning line, whatever
+    /// is there now.
+    fn completes_line(&self, x: usize, y: usize, tile: Tile) -> bool {
+        if tile == Tile::Empty {
+            return false;
         }
-        let initial = self.board[3][0];
-        if initial != Tile::Empty
-            && initial == self.board[2][1]
-            && initial == self.board[1][2]
-            && initial == self.board[0][3]
-        {
-            return GameStatus::Winner(initial);
+
+        let run = |dx: isize, dy: isize| {
+            (1..)
+                .take_while(|step| {
+                    let x = x.checked_add_signed(dx * step);
+                    let y = y.checked_add_signed(dy * step);
+                    x.zip(y).and_then(|(x, y)| self.board.get(x)?.get(y)) == Some(&tile)
+                })
+                .count()
+        };
+        [(1, 0), (0, 1), (1, 1), (-1, 1)]
+            .into_iter()
+            .any(|(dx, dy)| 1 + run(dx, dy) + run(-dx, -dy) >= self.shape.connect)
+    }
+
+    pub fn winner(&self) -> GameStatus {
+        // Rows, columns, then both diagonals, each from the top left. When
+        // both teams have a line, as random boards may, the first one found
+        // wins.
+        for (dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)] {
+            for y in 0..self.shape.height {
+                for x in 0..self.shape.width {
+                    if let Some(tile) = self.line_from(x, y, dx, dy) {
+                        return GameStatus::Winner(tile);
+                    }
+                }
+            }
         }
 
         // All filled
@@ -129,22 +233,90 @@ impl Connect4 {
 
         GameStatus::Ongoing
     }
+
+    /// The team with `connect` tiles in a row starting at `x`, `y` and going
+    /// `dx`, `dy`, if there is one.
+    fn line_from(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<Tile> {
+        let initial = self.board[x][y];
+        if initial == Tile::Empty {
+            return None;
+        }
+
+        (1..self.shape.connect as isize)
+            .all(|step| {
+                let x = x.checked_add_signed(dx * step);
+                let y = y.checked_add_signed(dy * step);
+                let tile = x.zip(y).and_then(|(x, y)| self.board.get(x)?.get(y));
+                tile == Some(&initial)
+            })
+            .then_some(initial)
+    }
+
+    /// Reads a board back from what [`Display`] writes, with or without the
+    /// line saying who won. That doesn't tell how many tiles in a row win, so
+    /// `connect` does.
+    pub fn parse(text: &str, connect: usize) -> Result<Self, String> {
+        let mut rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        if rows.last().is_some_and(|line| !line.starts_with('⬜')) {
+            rows.pop();
+        }
+        let width = rows
+            .pop()
+            .filter(|bottom| bottom.chars().all(|c| c == '⬜'))
+            .and_then(|bottom| bottom.chars().count().checked_sub(2))
+            .ok_or("The board must end in a row of ⬜")?;
+        let height = rows.len();
+        let shape = Shape::new(width, height, connect)?;
+
+        let mut board = vec![vec![Tile::Empty; height]; width];
+        for (y, row) in rows.iter().enumerate() {
+            let tiles: Vec<char> = row.chars().collect();
+            if tiles.len() != width + 2 || tiles[0] != '⬜' || tiles[width + 1] != '⬜' {
+                return Err(format!(
+                    "Row {} must have {width} tiles between ⬜ borders",
+                    y + 1
+                ));
+            }
+            for (x, tile) in tiles[1..=width].iter().enumerate() {
+                board[x][y] = match tile {
+                    '⬛' => Tile::Empty,
+                    '🍪' => Tile::Cookie,
+                    '🥛' => Tile::Milk,
+                    other => return Err(format!("Row {} has an unknown tile {other}", y + 1)),
+                };
+            }
+        }
+        for (x, column) in board.iter().enumerate() {
+            if column
+                .windows(2)
+                .any(|pair| pair[0] != Tile::Empty && pair[1] == Tile::Empty)
+            {
+                return Err(format!("Column {} has a tile over an empty space", x + 1));
+            }
+        }
+
+        Ok(Self { shape, board })
+    }
 }
 
 impl Display for Connect4 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..self.board.len() {
+        for y in 0..self.shape.height {
             f.write_str("⬜")?;
-            for x in 0..self.board[0].len() {
+            for x in 0..self.shape.width {
                 f.write_str(self.board[x][y].emoji())?;
             }
             f.write_str("⬜\n")?;
         }
-        f.write_str("⬜⬜⬜⬜⬜⬜\n")?;
+        writeln!(f, "{}", "⬜".repeat(self.shape.width + 2))?;
 
         match self.winner() {
-            GameStatus::Winner(tile) => write!(f, "{} wins!\n", tile.emoji())?,
-            GameStatus::NoWinner => write!(f, "No winner.\n")?,
+            GameStatus::Winner(tile) => writeln!(f, "{} wins!", tile.emoji())?,
+            GameStatus::NoWinner => writeln!(f, "No winner.")?,
             _ => {}
         }
 
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use uuid::Uuid;
+
+use super::connect4::Connect4;
+use crate::error::AppError;
+
+/// The game played through the routes without a game ID. It never expires.
+pub const DEFAULT_GAME: Uuid = Uuid::nil();
+
+/// The games being played, as rebuilt from the store. Each is forgotten once
+/// nobody has looked at it for the idle timeout.
+pub struct Games {
+    games: HashMap<Uuid, Entry>,
+    idle_timeout: Duration,
+}
+
+struct Entry {
+    slot: Slot,
+    last_used: Instant,
+}
+
+/// Where a live game is kept, behind a lock of its own, so that waiting on
+/// the store for one game holds up no other. Empty until the game is loaded,
+/// and again once it's forgotten.
+#[derive(Clone, Default)]
+pub struct Slot(Arc<Mutex<Option<LiveGame>>>);
+
+/// A live game, locked until dropped.
+pub struct LockedGame(OwnedMutexGuard<Option<LiveGame>>);
+
+pub struct LiveGame {
+    pub board: Connect4,
+    pub round: i32,
+    /// Played in this round so far.
+    pub moves: usize,
+}
+
+impl LiveGame {
+    pub fn new(board: Connect4, round: i32, moves: usize) -> Self {
+        Self {
+            board,
+            round,
+            moves,
+        }
+    }
+}
+
+impl Games {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            games: HashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    pub fn insert(&mut self, id: Uuid, game: LiveGame) {
+        self.expire();
+        self.games.insert(
+            id,
+            Entry {
+                slot: Slot(Arc::new(Mutex::new(Some(game)))),
+                last_used: Instant::now(),
+            },
+        );
+    }
+
+    /// The slot of game `id`, empty if the game isn't loaded. Counts as using
+    /// it.
+    pub fn slot(&mut self, id: Uuid) -> Slot {
+        self.expire();
+
+        let entry = self.games.entry(id).or_insert_with(|| Entry {
+            slot: Slot::default(),
+            last_used: Instant::now(),
+        });
+        entry.last_used = Instant::now();
+        entry.slot.clone()
+    }
+
+    // Expired games are dropped whenever the games are touched, rather than
+    // by a timer: nobody can tell the difference. Games locked at the time
+    // are kept, lest they be loaded again while their moves are stored.
+    fn expire(&mut self) {
+        let idle_timeout = self.idle_timeout;
+        self.games.retain(|&id, entry| {
+            id == DEFAULT_GAME
+                || Arc::strong_count(&entry.slot.0) > 1
+                || entry.last_used.elapsed() < idle_timeout
+        });
+    }
+}
+
+impl Slot {
+    /// Waits for the game's lock, loading the game with `load` if the slot is
+    /// empty. If that fails, the slot stays empty.
+    pub async fn lock<F>(self, load: impl FnOnce() -> F) -> Result<LockedGame, AppError>
+    where
+        F: Future<Output = Result<LiveGame, AppError>>,
+    {
+        let mut game = self.0.lock_owned().await;
+        if game.is_none() {
+            *game = Some(load().await?);
+        }
+
+        Ok(LockedGame(game))
+    }
+}
+
+impl LockedGame {
+    /// Empties the game's slot, so that the game is loaded again from the
+    /// store.
+    pub fn forget(mut self) {
+        *self.0 = None;
+    }
+}
+
+impl Deref for LockedGame {
+    type Target = LiveGame;
+
+    fn deref(&self) -> &LiveGame {
+        self.0.as_ref().expect("Locked games are loaded")
+    }
+}
+
+impl DerefMut for LockedGame {
+    fn deref_mut(&mut self) -> &mut LiveGame {
+        self.0.as_mut().expect("Locked games are loaded")
+    }
+}
@@ -0,0 +1,295 @@
+//! Solves positions outright: who wins with perfect play, and how soon.
+//!
+//! Scores are from the point of view of the team to move. A win scores the
+//! number of empty cells left just before the winning tile is dropped, so
+//! sooner wins score higher; a loss scores the same, negated; a draw scores
+//! 0.
+
+use rand::{Rng as _, SeedableRng as _};
+use std::time::Instant;
+
+use super::{
+    ai::center_first,
+    connect4::{Connect4, Tile},
+};
+
+/// Beyond any score, which is at most the number of cells on a board.
+const INFINITY: i32 = 1_000;
+
+/// How many nodes are searched between looks at the clock.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// The transposition table has 2 to the power of the empty cells slots, within
+/// these bounds. Few positions fill less of a board, and the largest table, at
+/// 16 bytes a slot, takes 8 MiB.
+const MIN_TABLE_BITS: usize = 10;
+const MAX_TABLE_BITS: usize = 19;
+
+/// Fixed so that the same position always hashes the same.
+const ZOBRIST_SEED: u64 = 12;
+
+/// The position couldn't be solved before the deadline.
+#[derive(Debug)]
+pub struct OutOfTime;
+
+/// The game-theoretic value of a position.
+pub struct Solution {
+    /// Empty cells on the board solved.
+    empty: usize,
+    /// For the team to move.
+    pub score: i32,
+    /// Every open column (1-based, left to right) with its score for the team
+    /// to move, when played.
+    pub columns: Vec<(usize, i32)>,
+    /// One way the game goes with perfect play from both teams, starting with
+    /// the team to move.
+    pub variation: Vec<usize>,
+}
+
+impl Solution {
+    /// How many moves, both teams', a game scoring `score` lasts from the
+    /// position solved.
+    pub fn moves(&self, score: i32) -> usize {
+        match score.unsigned_abs() as usize {
+            0 => self.empty,
+            cells => self.empty + 1 - cells,
+        }
+    }
+}
+
+/// Solves `board`, on which the game isn't over yet, for `team` to move.
+pub fn solve(board: &Connect4, team: Tile, deadline: Instant) -> Result<Solution, OutOfTime> {
+    let mut solver = Solver::new(board.clone(), team, deadline);
+
+    let score = solver.exact(team)?;
+    let mut columns = Vec::new();
+    for col in 1..=board.shape().width() {
+        if let Some(score) = solver.score_move(team, col)? {
+            columns.push((col, score));
+        }
+    }
+    let variation = solver.variation(team, score)?;
+
+    Ok(Solution {
+        empty: board.tiles(Tile::Empty),
+        score,
+        columns,
+        variation,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The score is at least the one stored.
+    Lower,
+    /// The score is at most the one stored.
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u64,
+    score: i16,
+    bound: Bound,
+    /// The column that scored best, tried first when the position comes
+    /// round again.
+    best: u8,
+}
+
+struct Solver {
+    board: Connect4,
+    empty: usize,
+    /// The Zobrist hash of `board` and the team to move.
+    key: u64,
+    /// A random number per cell and team, hashed in when a tile is dropped.
+    keys: Vec<[u64; 2]>,
+    /// Hashed in when milk is to move.
+    milk_key: u64,
+    table: Vec<Option<Entry>>,
+    order: Vec<usize>,
+    deadline: Instant,
+    nodes: u64,
+}
+
+impl Solver {
+    fn new(board: Connect4, team: Tile, deadline: Instant) -> Self {
+        let shape = board.shape();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(ZOBRIST_SEED);
+        let keys: Vec<[u64; 2]> = (0..shape.width() * shape.height())
+            .map(|_| [rng.gen(), rng.gen()])
+            .collect();
+        let milk_key = rng.gen();
+        let empty = board.tiles(Tile::Empty);
+
+        let mut solver = Self {
+            empty,
+            key: if team == Tile::Milk { milk_key } else { 0 },
+            keys,
+            milk_key,
+            table: vec![None; 1 << empty.clamp(MIN_TABLE_BITS, MAX_TABLE_BITS)],
+            order: center_first(&board),
+            board,
+            deadline,
+            nodes: 0,
+        };
+        for x in 0..shape.width() {
+            for y in 0..shape.height() {
+                solver.key ^= solver.cell_key(x, y, solver.board.tile(x, y));
+            }
+        }
+
+        solver
+    }
+
+    fn cell_key(&self, x: usize, y: usize, tile: Tile) -> u64 {
+        let keys = self.keys[x * self.board.shape().height() + y];
+        match tile {
+            Tile::Empty => 0,
+            Tile::Cookie => keys[0],
+            Tile::Milk => keys[1],
+        }
+    }
+
+    /// Drops a tile for `team` into `col` (1-based), handing the move over.
+    /// Returns the row it landed in, or `None` if the column is full.
+    fn drop_tile(&mut self, team: Tile, col: usize) -> Option<usize> {
+        let row = self.board.drop_tile(team, col)?;
+        self.key ^= self.cell_key(col - 1, row, team) ^ self.milk_key;
+        self.empty -= 1;
+        Some(row)
+    }
+
+    fn undo(&mut self, team: Tile, col: usize, row: usize) {
+        self.board.undo(col);
+        self.key ^= self.cell_key(col - 1, row, team) ^ self.milk_key;
+        self.empty += 1;
+    }
+
+    fn exact(&mut self, team: Tile) -> Result<i32, OutOfTime> {
+        self.negamax(team, -INFINITY, INFINITY)
+    }
+
+    /// The score for `team` of playing `col`, or `None` if it's full.
+    fn score_move(&mut self, team: Tile, col: usize) -> Result<Option<i32>, OutOfTime> {
+        let empty = self.empty as i32;
+        let Some(row) = self.drop_tile(team, col) else {
+            return Ok(None);
+        };
+        let score = if self.board.in_line(col - 1, row) {
+            Ok(empty)
+        } else {
+            self.exact(team.opponent()).map(|score| -score)
+        };
+        self.undo(team, col, row);
+
+        score.map(Some)
+    }
+
+    /// Follows moves scoring `score` for whoever is to move until the game
+    /// ends, or until none does, which only a hash collision in the table
+    /// could cause. The board is left where the variation stops.
+    fn variation(&mut self, mut team: Tile, mut score: i32) -> Result<Vec<usize>, OutOfTime> {
+        let mut variation = Vec::new();
+        while self.empty > 0 {
+            let mut next = None;
+            for i in 0..self.order.len() {
+                let col = self.order[i];
+                if self.score_move(team, col)? == Some(score) {
+                    next = Some(col);
+                    break;
+                }
+            }
+            let Some((col, row)) = next.and_then(|col| Some((col, self.drop_tile(team, col)?)))
+            else {
+                break;
+            };
+            variation.push(col);
+            if self.board.in_line(col - 1, row) {
+                break;
+            }
+            (team, score) = (team.opponent(), -score);
+        }
+
+        Ok(variation)
+    }
+
+    /// The score of the board for `team` to move, if it's between `alpha`
+    /// and `beta`; otherwise a bound past the one it's outside of.
+    fn negamax(&mut self, team: Tile, mut alpha: i32, mut beta: i32) -> Result<i32, OutOfTime> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= self.deadline {
+            return Err(OutOfTime);
+        }
+        if self.empty == 0 {
+            return Ok(0);
+        }
+        let empty = self.empty as i32;
+        if self.board.winning_column(team).is_some() {
+            return Ok(empty);
+        }
+
+        // Winning takes another move of ours at least, and losing one of
+        // theirs.
+        beta = beta.min((empty - 2).max(0));
+        alpha = alpha.max(-(empty - 1));
+        if alpha >= beta {
+            return Ok(beta);
+        }
+
+        let slot = (self.key as usize) & (self.table.len() - 1);
+        let mut order = self.order.clone();
+        if let Some(entry) = self.table[slot].filter(|entry| entry.key == self.key) {
+            let score = i32::from(entry.score);
+            match entry.bound {
+                Bound::Exact => return Ok(score),
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return Ok(score);
+            }
+            // A collision may have stored a column this board doesn't have.
+            if let Some(at) = order.iter().position(|&col| col == usize::from(entry.best)) {
+                order[..=at].rotate_right(1);
+            }
+        }
+
+        let window = (alpha, beta);
+        let mut best = (-INFINITY, 0);
+        for col in order {
+            let Some(row) = self.drop_tile(team, col) else {
+                continue;
+            };
+            let score = self
+                .negamax(team.opponent(), -beta, -alpha)
+                .map(|score| -score);
+            self.undo(team, col, row);
+            let score = score?;
+            if score > best.0 {
+                best = (score, col);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let (score, col) = best;
+        let bound = if score <= window.0 {
+            Bound::Upper
+        } else if score >= window.1 {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table[slot] = Some(Entry {
+            key: self.key,
+            score: score as i16,
+            bound,
+            best: col as u8,
+        });
+
+        Ok(score)
+    }
+}
@@ -0,0 +1,93 @@
+//! Where games and their moves are kept, so they outlive the process. Boards
+//! are rebuilt from the moves rather than stored.
+
+mod memory;
+mod postgres;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{connect4::Shape, ArchivedGame, PlayedMove, Team};
+use crate::error::AppError;
+
+pub use memory::MemoryGameStore;
+pub use postgres::PgGameStore;
+
+/// How a round ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Winner(Team),
+    NoWinner,
+}
+
+impl Outcome {
+    pub fn winner(self) -> Option<Team> {
+        match self {
+            Outcome::Winner(team) => Some(team),
+            Outcome::NoWinner => None,
+        }
+    }
+}
+
+/// The round a game is at, as far as it got.
+#[derive(Debug, Clone)]
+pub struct SavedGame {
+    pub round: i32,
+    pub shape: Shape,
+    /// In the order they were played.
+    pub moves: Vec<PlayedMove>,
+    /// When a round of the game was last started or played in.
+    pub active_at: DateTime<Utc>,
+}
+
+/// Backends must agree on semantics: a game starts at round 1, and each reset
+/// starts the next one. Only moves that went through are recorded, numbered
+/// from 0 within their round. Recording a round or move that was recorded
+/// already, e.g. by another replica, fails with [`AppError::Conflict`].
+/// Nothing is ever deleted: a game that was left idle is simply no longer
+/// loaded, and its finished rounds stay in the archive.
+#[async_trait]
+pub trait GameStore: Send + Sync {
+    /// Starts game `id` at round 1, unless it exists already.
+    async fn create(&self, id: Uuid, shape: Shape) -> Result<(), AppError>;
+
+    /// Starts round `round` of game `id` on an empty board.
+    async fn start_round(&self, id: Uuid, round: i32, shape: Shape) -> Result<(), AppError>;
+
+    /// Records move `seq` of a round, along with how the round ended if the
+    /// move ended it. Only the game's latest round takes moves, and only
+    /// until it's over.
+    async fn record_move(
+        &self,
+        id: Uuid,
+        round: i32,
+        seq: usize,
+        team: Team,
+        column: usize,
+        outcome: Option<Outcome>,
+    ) -> Result<(), AppError>;
+
+    /// The latest round of game `id`, or `None` if there's no such game.
+    async fn load(&self, id: Uuid) -> Result<Option<SavedGame>, AppError>;
+
+    /// Up to `limit` finished rounds, skipping the first `offset`, most
+    /// recently finished first.
+    async fn archive(&self, offset: usize, limit: usize) -> Result<Vec<ArchivedGame>, AppError>;
+
+    /// Round `round` of game `id` with its moves, if it's finished.
+    async fn archived(
+        &self,
+        id: Uuid,
+        round: i32,
+    ) -> Result<Option<(ArchivedGame, Vec<PlayedMove>)>, AppError>;
+}
+
+pub type SharedGameStore = Arc<dyn GameStore>;
+
+/// Round `round` of game `id` no longer takes moves, e.g. as another replica
+/// started the next one.
+fn round_over(id: Uuid, round: i32) -> AppError {
+    AppError::Conflict(format!("Round {round} of game {id} is over"))
+}
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{cmp::Reverse, collections::HashMap};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{round_over, GameStore, Outcome, SavedGame};
+use crate::{
+    clock::now,
+    day12::{connect4::Shape, ArchivedGame, PlayedMove, Team},
+    error::AppError,
+};
+
+/// Keeps games in process memory, for running without Postgres. Nothing
+/// survives a restart.
+#[derive(Default)]
+pub struct MemoryGameStore {
+    games: RwLock<HashMap<Uuid, Game>>,
+}
+
+struct Game {
+    active_at: DateTime<Utc>,
+    /// Round `n` at index `n - 1`.
+    rounds: Vec<Round>,
+}
+
+struct Round {
+    shape: Shape,
+    started_at: DateTime<Utc>,
+    finished: Option<(DateTime<Utc>, Outcome)>,
+    moves: Vec<PlayedMove>,
+}
+
+impl Round {
+    fn new(shape: Shape, now: DateTime<Utc>) -> Self {
+        Self {
+            shape,
+            started_at: now,
+            finished: None,
+            moves: Vec::new(),
+        }
+    }
+
+    /// `None` if it isn't finished.
+    fn archived(&self, id: Uuid, round: i32) -> Option<ArchivedGame> {
+        let (finished_at, outcome) = self.finished?;
+        Some(ArchivedGame {
+            id,
+            round,
+            width: self.shape.width(),
+            height: self.shape.height(),
+            connect: self.shape.connect(),
+            winner: outcome.winner(),
+            moves: self.moves.len(),
+            started_at: self.started_at,
+            finished_at,
+        })
+    }
+}
+
+fn number(index: usize) -> i32 {
+    i32::try_from(index + 1).expect("Fewer than 2^31 rounds")
+}
+
+#[async_trait]
+impl GameStore for MemoryGameStore {
+    async fn create(&self, id: Uuid, shape: Shape) -> Result<(), AppError> {
+        let now = now();
+        self.games.write().await.entry(id).or_insert_with(|| Game {
+            active_at: now,
+            rounds: vec![Round::new(shape, now)],
+        });
+
+        Ok(())
+    }
+
+    async fn start_round(&self, id: Uuid, round: i32, shape: Shape) -> Result<(), AppError> {
+        let mut games = self.games.write().await;
+        let game = games
+            .get_mut(&id)
+            .ok_or_else(|| AppError::NotFound(format!("No game {id}")))?;
+        if round != number(game.rounds.len()) {
+            return Err(AppError::Conflict(format!(
+                "Round {round} of game {id} was already started"
+            )));
+        }
+
+        let now = now();
+        game.rounds.push(Round::new(shape, now));
+        game.active_at = now;
+
+        Ok(())
+    }
+
+    async fn record_move(
+        &self,
+        id: Uuid,
+        round: i32,
+        seq: usize,
+        team: Team,
+        column: usize,
+        outcome: Option<Outcome>,
+    ) -> Result<(), AppError> {
+        let mut games = self.games.write().await;
+        let game = games
+            .get_mut(&id)
+            .ok_or_else(|| AppError::NotFound(format!("No game {id}")))?;
+        let latest = number(game.rounds.len() - 1);
+        let played = usize::try_from(round - 1)
+            .ok()
+            .and_then(|index| game.rounds.get_mut(index))
+            .ok_or_else(|| AppError::NotFound(format!("No round {round} of game {id}")))?;
+        if round != latest || played.finished.is_some() {
+            return Err(round_over(id, round));
+        }
+        if seq != played.moves.len() {
+            return Err(AppError::Conflict(format!(
+                "Move {seq} of round {round} of game {id} was already played"
+            )));
+        }
+
+        let now = now();
+        played.moves.push(PlayedMove {
+            team,
+            column,
+            played_at: now,
+        });
+        if let Some(outcome) = outcome {
+            played.finished = Some((now, outcome));
+        }
+        game.active_at = now;
+
+        Ok(())
+    }
+
+    async fn load(&self, id: Uuid) -> Result<Option<SavedGame>, AppError> {
+        let games = self.games.read().await;
+        let Some(game) = games.get(&id) else {
+            return Ok(None);
+        };
+        let current = game.rounds.last().expect("Games have a round");
+
+        Ok(Some(SavedGame {
+            round: number(game.rounds.len() - 1),
+            shape: current.shape,
+            moves: current.moves.clone(),
+            active_at: game.active_at,
+        }))
+    }
+
+    async fn archive(&self, offset: usize, limit: usize) -> Result<Vec<ArchivedGame>, AppError> {
+        let games = self.games.read().await;
+        let mut archive: Vec<_> = games
+            .iter()
+            .flat_map(|(&id, game)| {
+                game.rounds
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(index, round)| round.archived(id, number(index)))
+            })
+            .collect();
+        archive.sort_by_key(|game| Reverse((game.finished_at, game.id, game.round)));
+
+        Ok(archive.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn archived(
+        &self,
+        id: Uuid,
+        round: i32,
+    ) -> Result<Option<(ArchivedGame, Vec<PlayedMove>)>, AppError> {
+        let games = self.games.read().await;
+        let played = games.get(&id).and_then(|game| {
+            let index = usize::try_from(round - 1).ok()?;
+            game.rounds.get(index)
+        });
+
+        Ok(played.and_then(|played| Some((played.archived(id, round)?, played.moves.clone()))))
+    }
+}
@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{round_over, GameStore, Outcome, SavedGame};
+use crate::{
+    day12::{connect4::Shape, ArchivedGame, PlayedMove, Team},
+    error::AppError,
+};
+
+pub struct PgGameStore {
+    pool: PgPool,
+}
+
+impl PgGameStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Board sizes are small enough for both.
+fn to_db(n: usize) -> i32 {
+    i32::try_from(n).expect("Board sizes fit an INTEGER")
+}
+
+fn from_db(n: i32) -> Result<usize, AppError> {
+    usize::try_from(n).map_err(|_| AppError::Internal(format!("Negative size {n} stored")))
+}
+
+fn shape_from_db(width: i32, height: i32, connect: i32) -> Result<Shape, AppError> {
+    Shape::new(from_db(width)?, from_db(height)?, from_db(connect)?)
+        .map_err(|err| AppError::Internal(format!("Unplayable board stored: {err}")))
+}
+
+fn team_from_db(team: &str) -> Result<Team, AppError> {
+    Team::from_name(team).ok_or_else(|| AppError::Internal(format!("Unknown team {team} stored")))
+}
+
+fn moves_from_db(
+    rows: impl IntoIterator<Item = (String, i32, DateTime<Utc>)>,
+) -> Result<Vec<PlayedMove>, AppError> {
+    rows.into_iter()
+        .map(|(team, column, played_at)| {
+            Ok(PlayedMove {
+                team: team_from_db(&team)?,
+                column: from_db(column)?,
+                played_at,
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl GameStore for PgGameStore {
+    async fn create(&self, id: Uuid, shape: Shape) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let created = sqlx::query!(
+            "INSERT INTO connect4_games (id) VALUES ($1) ON CONFLICT (id) DO NOTHING",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if created.rows_affected() == 1 {
+            sqlx::query!(
+                "INSERT INTO connect4_rounds (game_id, round, width, height, connect)
+                VALUES ($1, 1, $2, $3, $4)",
+                id,
+                to_db(shape.width()),
+                to_db(shape.height()),
+                to_db(shape.connect())
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn start_round(&self, id: Uuid, round: i32, shape: Shape) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO connect4_rounds (game_id, round, width, height, connect)
+            VALUES ($1, $2, $3, $4, $5)",
+            id,
+            round,
+            to_db(shape.width()),
+            to_db(shape.height()),
+            to_db(shape.connect())
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE connect4_games SET active_at = now() WHERE id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn record_move(
+        &self,
+        id: Uuid,
+        round: i32,
+        seq: usize,
+        team: Team,
+        column: usize,
+        outcome: Option<Outcome>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        // Locks the game first, so that a round started meanwhile by another
+        // replica either shows below or starts after this move.
+        sqlx::query!(
+            "UPDATE connect4_games SET active_at = now() WHERE id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let recorded = sqlx::query!(
+            "INSERT INTO connect4_moves (game_id, round, seq, team, col)
+            SELECT $1, $2, $3, $4, $5 FROM connect4_rounds
+            WHERE game_id = $1 AND round = $2 AND finished_at IS NULL
+                AND round = (SELECT max(round) FROM connect4_rounds WHERE game_id = $1)",
+            id,
+            round,
+            to_db(seq),
+            team.name(),
+            to_db(column)
+        )
+        .execute(&mut *tx)
+        .await?;
+        if recorded.rows_affected() == 0 {
+            return Err(round_over(id, round));
+        }
+        if let Some(outcome) = outcome {
+            sqlx::query!(
+                "UPDATE connect4_rounds SET finished_at = now(), winner = $3
+                WHERE game_id = $1 AND round = $2",
+                id,
+                round,
+                outcome.winner().map(Team::name)
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, id: Uuid) -> Result<Option<SavedGame>, AppError> {
+        // Both reads see the same snapshot, so the moves are those of the
+        // round read.
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await?;
+        let Some(current) = sqlx::query!(
+            "SELECT r.round, r.width, r.height, r.connect, g.active_at
+            FROM connect4_games g
+            JOIN connect4_rounds r ON r.game_id = g.id
+            WHERE g.id = $1
+            ORDER BY r.round DESC
+            LIMIT 1",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let moves = sqlx::query!(
+            "SELECT team, col, played_at FROM connect4_moves
+            WHERE game_id = $1 AND round = $2
+            ORDER BY seq",
+            id,
+            current.round
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(SavedGame {
+            round: current.round,
+            shape: shape_from_db(current.width, current.height, current.connect)?,
+            moves: moves_from_db(moves.into_iter().map(|m| (m.team, m.col, m.played_at)))?,
+            active_at: current.active_at,
+        }))
+    }
+
+    async fn archive(&self, offset: usize, limit: usize) -> Result<Vec<ArchivedGame>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT r.game_id, r.round, r.width, r.height, r.connect, r.winner,
+                r.started_at, r.finished_at AS "finished_at!",
+                (SELECT count(*) FROM connect4_moves m
+                    WHERE m.game_id = r.game_id AND m.round = r.round) AS "moves!"
+            FROM connect4_rounds r
+            WHERE r.finished_at IS NOT NULL
+            ORDER BY r.finished_at DESC, r.game_id DESC, r.round DESC
+            OFFSET $1 LIMIT $2"#,
+            i64::try_from(offset).unwrap_or(i64::MAX),
+            i64::try_from(limit).unwrap_or(i64::MAX)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(ArchivedGame {
+                    id: row.game_id,
+                    round: row.round,
+                    width: from_db(row.width)?,
+                    height: from_db(row.height)?,
+                    connect: from_db(row.connect)?,
+                    winner: row.winner.as_deref().map(team_from_db).transpose()?,
+                    moves: usize::try_from(row.moves).unwrap_or_default(),
+                    started_at: row.started_at,
+                    finished_at: row.finished_at,
+                })
+            })
+            .collect()
+    }
+
+    async fn archived(
+        &self,
+        id: Uuid,
+        round: i32,
+    ) -> Result<Option<(ArchivedGame, Vec<PlayedMove>)>, AppError> {
+        // Finished rounds don't change, so no snapshot is needed.
+        let Some(row) = sqlx::query!(
+            r#"SELECT width, height, connect, winner, started_at,
+                finished_at AS "finished_at!"
+            FROM connect4_rounds
+            WHERE game_id = $1 AND round = $2 AND finished_at IS NOT NULL"#,
+            id,
+            round
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let moves = sqlx::query!(
+            "SELECT team, col, played_at FROM connect4_moves
+            WHERE game_id = $1 AND round = $2
+            ORDER BY seq",
+            id,
+            round
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let moves = moves_from_db(moves.into_iter().map(|m| (m.team, m.col, m.played_at)))?;
+
+        Ok(Some((
+            ArchivedGame {
+                id,
+                round,
+                width: from_db(row.width)?,
+                height: from_db(row.height)?,
+                connect: from_db(row.connect)?,
+                winner: row.winner.as_deref().map(team_from_db).transpose()?,
+                moves: moves.len(),
+                started_at: row.started_at,
+                finished_at: row.finished_at,
+            },
+            moves,
+        )))
+    }
+}
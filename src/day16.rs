@@ -1,84 +1,85 @@
 use jsonwebtoken::{errors::ErrorKind as JWTError, DecodingKey, EncodingKey, Header, Validation};
-use poem::{
-    get, handler,
-    http::{HeaderMap, StatusCode},
-    post, Response, Route,
-};
+use poem_openapi::{param::Cookie, payload::Json, ApiResponse, OpenApi};
 use std::collections::HashSet;
 
-pub fn route() -> Route {
-    Route::new()
-        .at("/wrap", post(wrap_gift))
-        .at("/unwrap", get(unwrap_gift))
-        .at("/decode", post(decode_old_gift))
+use crate::{config::GiftsConfig, error::AppError, payload::AnyText, ApiTags};
+
+pub struct Api {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    santa_key: DecodingKey,
 }
 
-#[handler]
-fn wrap_gift(body: String) -> Response {
-    let jwt = jsonwebtoken::encode(
-        &Header::default(),
-        &serde_json::from_str::<serde_json::Value>(&body).expect("Input is valid JSON"),
-        &EncodingKey::from_secret(b"a"),
-    )
-    .expect("Failed to encode JWT");
+impl Api {
+    pub fn new(config: &GiftsConfig) -> Self {
+        let santa_pem = config
+            .santa_public_key
+            .as_deref()
+            .unwrap_or(include_str!("../day16_santa_public_key.pem"));
 
-    Response::builder()
-        .header("Set-Cookie", format!("gift={jwt}",))
-        .body(())
+        Self {
+            encoding_key: EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+            santa_key: DecodingKey::from_rsa_pem(santa_pem.as_bytes())
+                .expect("Santa's key is valid"),
+        }
+    }
 }
 
-#[handler]
-async fn unwrap_gift(headers: &HeaderMap) -> Response {
-    let Some(cookie) = headers
-        .get("Cookie")
-        .and_then(|c| c.to_str().ok())
-        .and_then(|c| c.strip_prefix("gift="))
-    else {
-        return StatusCode::BAD_REQUEST.into();
-    };
+#[derive(ApiResponse)]
+enum WrapResponse {
+    /// The gift, wrapped into a JWT and handed back as the `gift` cookie.
+    #[oai(status = 200)]
+    Wrapped(#[oai(header = "Set-Cookie")] String),
+}
 
-    let mut validation = Validation::default();
-    validation.required_spec_claims = HashSet::new();
-    validation.validate_exp = false;
+#[OpenApi(prefix_path = "/16", tag = "ApiTags::Day16")]
+impl Api {
+    /// Wrap an arbitrary JSON gift into a signed JWT cookie.
+    #[oai(path = "/wrap", method = "post")]
+    async fn wrap_gift(&self, claims: Json<serde_json::Value>) -> Result<WrapResponse, AppError> {
+        let jwt = jsonwebtoken::encode(&Header::default(), &*claims, &self.encoding_key)
+            .map_err(|err| AppError::Internal(err.to_string()))?;
 
-    let Ok(decoded) = jsonwebtoken::decode::<serde_json::Value>(
-        cookie,
-        &DecodingKey::from_secret(b"a"),
-        &validation,
-    )
-    .map(|d| d.claims) else {
-        return StatusCode::BAD_REQUEST.into();
-    };
+        Ok(WrapResponse::Wrapped(format!("gift={jwt}",)))
+    }
 
-    decoded.to_string().into()
-}
+    /// Unwrap the gift stored in the `gift` cookie.
+    #[oai(path = "/unwrap", method = "get")]
+    async fn unwrap_gift(&self, gift: Cookie<String>) -> Result<Json<serde_json::Value>, AppError> {
+        let mut validation = Validation::default();
+        validation.required_spec_claims = HashSet::new();
+        validation.validate_exp = false;
 
-#[handler]
-async fn decode_old_gift(body: String) -> Response {
-    let mut validation = Validation::default();
-    validation.required_spec_claims = HashSet::new();
-    validation.validate_exp = false;
-    validation.algorithms = vec![
-        jsonwebtoken::Algorithm::RS256,
-        jsonwebtoken::Algorithm::RS512,
-    ];
+        let decoded =
+            jsonwebtoken::decode::<serde_json::Value>(&gift, &self.decoding_key, &validation)
+                .map(|d| d.claims)
+                .map_err(|err| AppError::BadRequest(format!("Invalid gift: {err}")))?;
 
-    let decoded = match jsonwebtoken::decode::<serde_json::Value>(
-        &body,
-        &DecodingKey::from_rsa_pem(include_bytes!("../day16_santa_public_key.pem"))
-            .expect("Key from filesystem is valid"),
-        &validation,
-    )
-    .map(|c| c.claims)
-    {
-        Ok(decoded) => decoded,
-        Err(err) => {
-            return (match err.into_kind() {
-                JWTError::InvalidSignature => StatusCode::UNAUTHORIZED,
-                _ => StatusCode::BAD_REQUEST,
-            })
-            .into()
-        }
-    };
-    decoded.to_string().into()
+        Ok(Json(decoded))
+    }
+
+    /// Decode one of Santa's old RS256/RS512 signed gifts.
+    #[oai(path = "/decode", method = "post")]
+    async fn decode_old_gift(&self, body: AnyText) -> Result<Json<serde_json::Value>, AppError> {
+        let mut validation = Validation::default();
+        validation.required_spec_claims = HashSet::new();
+        validation.validate_exp = false;
+        validation.algorithms = vec![
+            jsonwebtoken::Algorithm::RS256,
+            jsonwebtoken::Algorithm::RS512,
+        ];
+
+        let decoded =
+            jsonwebtoken::decode::<serde_json::Value>(&body.0, &self.santa_key, &validation)
+                .map(|c| c.claims)
+                .map_err(|err| match err.into_kind() {
+                    JWTError::InvalidSignature => {
+                        AppError::Unauthorized("Invalid signature".to_owned())
+                    }
+                    kind => AppError::BadRequest(format!("Invalid gift: {kind:?}")),
+                })?;
+
+        Ok(Json(decoded))
+    }
 }
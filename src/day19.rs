@@ -1,191 +1,1083 @@
-use chrono::{DateTime, Utc};
-use poem::{
-    delete, get, handler,
-    http::StatusCode,
-    post, put,
-    web::{Data, Path, Query},
-    Body, EndpointExt as _, IntoEndpoint, Response, Route,
+mod audit;
+mod bulk;
+mod cursor;
+mod etag;
+mod store;
+mod validate;
+
+use chrono::{DateTime, NaiveDate, SubsecRound as _, Utc};
+use chrono_tz::Tz;
+use futures_util::TryStreamExt as _;
+use poem::{web::sse::Event, Body};
+use poem_openapi::{
+    param::{Header, Path, Query},
+    payload::{Binary, EventStream, Json, PlainText},
+    types::ToJSON as _,
+    ApiRequest, ApiResponse, Enum, Object, OpenApi, ResponseContent,
 };
-use rand::distributions::{Alphanumeric, DistString as _};
+use rand::{distributions::Alphanumeric, Rng as _};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use serde_json::Value;
+use std::time::Duration;
 use uuid::Uuid;
 
-pub fn route() -> impl IntoEndpoint {
-    Route::new()
-        .at("/reset", post(quotes_reset))
-        .at("/draft", post(quotes_draft))
-        .at("/cite/:id", get(quotes_cite))
-        .at("/undo/:id", put(quotes_update))
-        .at("/remove/:id", delete(quotes_delete))
-        .at("/list", get(quotes_paginate))
-        .data(PaginationStatuses(Arc::new(Mutex::new(HashMap::new()))))
+use crate::{config::QuotesConfig, error::AppError, ApiTags};
+use audit::{Auditor, Caller};
+use bulk::ExportFormat;
+use cursor::{AuditCursor, Cursor, CursorCodec, Listing, SearchCursor};
+use store::{Audit, AuditFilter, QuoteEvents, QuoteFilter, SearchKey};
+
+pub use store::{MemoryQuoteStore, PgQuoteStore, QuoteStore, SharedQuoteStore};
+
+pub struct Api {
+    store: SharedQuoteStore,
+    page_size: usize,
+    max_page_size: usize,
+    cursors: CursorCodec,
+    today_zone: Tz,
+    today_seed: u64,
+    auditor: Auditor,
+}
+
+impl Api {
+    pub fn new(config: &QuotesConfig, store: SharedQuoteStore) -> Self {
+        Self {
+            store,
+            page_size: config.page_size,
+            max_page_size: config.max_page_size,
+            cursors: CursorCodec::new(&secret(&config.cursor_secret, "cursor_secret")),
+            today_zone: config
+                .today_time_zone
+                .parse()
+                .expect("The time zone is valid"),
+            today_seed: config.today_seed,
+            auditor: Auditor::new(
+                &secret(&config.actor_token_secret, "actor_token_secret"),
+                &config.trusted_proxies,
+            ),
+        }
+    }
+}
+
+/// The secret configured as `quotes.{key}`, or else a random one, which
+/// nobody else knows: no other replica, and not this one after a restart.
+fn secret(configured: &Option<String>, key: &str) -> String {
+    configured.clone().unwrap_or_else(|| {
+        tracing::warn!("quotes.{key} isn't set, using a random secret instead");
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    })
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Quote {
+#[derive(Debug, Clone, Object)]
+pub(crate) struct Quote {
+    #[oai(read_only)]
     id: Option<Uuid>,
     author: String,
+    /// The author the quote links to, which `author` may spell differently.
+    /// Left out to keep quotes as clients know them.
+    #[oai(skip)]
+    author_id: Option<Uuid>,
     quote: String,
+    #[oai(read_only)]
     version: Option<i32>,
+    #[oai(read_only)]
     created_at: Option<DateTime<Utc>>,
+    /// When the quote was moved to the trash; absent unless it's there.
+    #[oai(read_only, skip_serializing_if_is_none)]
+    deleted_at: Option<DateTime<Utc>>,
+    /// Lowercase, sorted and without duplicates.
+    #[oai(skip_serializing_if_is_none)]
+    tags: Option<Vec<String>>,
 }
 
-#[handler]
-async fn quotes_reset(pool: Data<&PgPool>) {
-    sqlx::query!("DELETE FROM quotes")
-        .execute(*pool)
-        .await
-        .expect("Clearing table shouldn't fail");
-}
-
-#[handler]
-async fn quotes_draft(pool: Data<&PgPool>, body: Body) -> Response {
-    let quote: Quote = body.into_json().await.unwrap();
-
-    let inserted = sqlx::query_as!(
-        Quote,
-        "INSERT INTO quotes (id, author, quote) VALUES ($1, $2, $3) RETURNING *",
-        Uuid::new_v4(),
-        quote.author,
-        quote.quote
-    )
-    .fetch_one(*pool)
-    .await
-    .unwrap();
-
-    Response::builder()
-        .status(StatusCode::CREATED)
-        .body(serde_json::to_string(&inserted).unwrap())
-}
-
-#[handler]
-async fn quotes_cite(pool: Data<&PgPool>, id: Path<Uuid>) -> Response {
-    let result = sqlx::query_as!(Quote, "SELECT * FROM quotes WHERE id = $1", *id)
-        .fetch_optional(*pool)
-        .await
-        .unwrap();
-    let Some(quote) = result else {
-        return StatusCode::NOT_FOUND.into();
-    };
-
-    serde_json::to_string(&quote).unwrap().into()
-}
-
-#[handler]
-async fn quotes_update(pool: Data<&PgPool>, id: Path<Uuid>, body: Body) -> Response {
-    let new_quote: Quote = body.into_json().await.unwrap();
-
-    let result = sqlx::query_as!(
-        Quote,
-        "UPDATE quotes SET author = $2, quote = $3, version = version + 1 WHERE id = $1 RETURNING *",
-        *id,
-        new_quote.author,
-        new_quote.quote
-    )
-    .fetch_optional(*pool)
-    .await
-    .unwrap();
-
-    let Some(new_quote) = result else {
-        return StatusCode::NOT_FOUND.into();
-    };
-
-    serde_json::to_string(&new_quote).unwrap().into()
-}
-
-#[handler]
-async fn quotes_delete(pool: Data<&PgPool>, id: Path<Uuid>) -> Response {
-    let result = sqlx::query_as!(Quote, "DELETE FROM quotes WHERE id = $1 RETURNING *", *id)
-        .fetch_optional(*pool)
-        .await
-        .unwrap();
-    let Some(quote) = result else {
-        return StatusCode::NOT_FOUND.into();
-    };
-
-    serde_json::to_string(&quote).unwrap().into()
-}
-
-#[derive(Debug, Clone)]
-struct PaginationStatus {
-    page: usize,
-    remaining: Vec<Quote>,
+/// A quote as sent to `/19/draft` and `/19/undo`. Text is trimmed and
+/// normalized before it's stored.
+#[derive(Debug, Object)]
+struct QuoteInput {
+    /// Required, at most 200 characters.
+    author: Option<String>,
+    /// Required, at most 2000 characters.
+    quote: Option<String>,
+    /// Up to 20 tags of at most 50 characters, matched ignoring case. An
+    /// update that leaves them out keeps the ones the quote had.
+    tags: Option<Vec<String>>,
+    /// The server assigns the remaining fields, so sending them is an error.
+    id: Option<Value>,
+    version: Option<Value>,
+    created_at: Option<Value>,
+    deleted_at: Option<Value>,
+}
+
+#[derive(Object)]
+struct QuoteOfTheDay {
+    date: NaiveDate,
+    quote: Quote,
+}
+
+/// An author, with figures over their quotes outside the trash.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct Author {
+    id: Uuid,
+    /// The first spelling seen; quotes by the same name in another case or
+    /// with extra whitespace are theirs too.
+    name: String,
+    quote_count: i64,
+    /// When their oldest quote was created; absent without quotes.
+    first_quote_at: Option<DateTime<Utc>>,
+    /// When their newest quote was created; absent without quotes.
+    last_quote_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Object)]
+struct AuthorProfile {
+    #[oai(flatten)]
+    author: Author,
+    /// Their quotes, a page at a time.
+    #[oai(flatten)]
+    quotes: QuotePaginationResponse,
+}
+
+/// A tag and how many quotes carry it.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct TagCount {
+    name: String,
+    count: i64,
+}
+
+/// A quote as it was at one version.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct QuoteVersion {
+    id: Uuid,
+    version: i32,
+    author: String,
+    quote: String,
+    created_at: DateTime<Utc>,
+    /// When this version was replaced or deleted; absent for the current one.
+    archived_at: Option<DateTime<Utc>>,
+}
+
+impl QuoteVersion {
+    /// The version a stored quote is currently at, archived if the quote is
+    /// in the trash.
+    fn live(quote: &Quote) -> Self {
+        Self {
+            id: quote.id.expect("Stored quotes have an id"),
+            version: quote.version.expect("Stored quotes have a version"),
+            author: quote.author.clone(),
+            quote: quote.quote.clone(),
+            created_at: quote
+                .created_at
+                .expect("Stored quotes have a creation time"),
+            archived_at: quote.deleted_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub(crate) enum QuoteEventKind {
+    Created,
+    Updated,
+    Deleted,
+    /// Every quote was deleted at once.
+    Reset,
+}
+
+/// A change to the quotes.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct QuoteEvent {
+    /// Increases with every change. Also the event's SSE id.
+    id: i64,
+    kind: QuoteEventKind,
+    /// The quote after the change, or as it was when deleted, without its
+    /// tags. Absent for resets.
+    quote: Option<Quote>,
+}
+
+/// The kinds of change the audit log records, named after their endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize)]
+#[oai(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AuditOperation {
+    Draft,
+    Undo,
+    Remove,
+    Restore,
+    Purge,
+    Revert,
+    Reset,
+    Import,
 }
 
-#[derive(Debug, Clone)]
-struct PaginationStatuses(Arc<Mutex<HashMap<String, PaginationStatus>>>);
+/// A change made through the API, as the audit log records it.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct AuditEntry {
+    /// Increases with every entry.
+    id: i64,
+    at: DateTime<Utc>,
+    operation: AuditOperation,
+    /// Who made the change, from their bearer token or, through a trusted
+    /// proxy, the `X-Actor` header; absent if neither says.
+    actor: Option<String>,
+    /// The address the request came from, or that a trusted proxy got it
+    /// from.
+    client_ip: Option<String>,
+    /// Absent for resets.
+    quote_id: Option<Uuid>,
+    /// The quote as it was before the change; absent for new quotes and
+    /// resets.
+    before: Option<Value>,
+    /// The quote as it was after the change; absent for purges and resets.
+    after: Option<Value>,
+}
+
+/// A quote matching a search.
+#[derive(Debug, Clone, Object)]
+pub(crate) struct SearchHit {
+    quote: Quote,
+    /// How well the quote matches the text query; higher is better. Absent
+    /// without a text query.
+    rank: Option<f32>,
+    /// The part of the quote around the matches, which are wrapped in
+    /// `<mark>` tags. The quote itself isn't HTML-escaped. Absent without a
+    /// text query.
+    snippet: Option<String>,
+}
 
-#[derive(Serialize)]
+#[derive(ApiResponse)]
+enum DraftResponse {
+    /// The stored quote.
+    #[oai(status = 201)]
+    Created(Json<Quote>, #[oai(header = "ETag")] String),
+}
+
+#[derive(ApiResponse)]
+enum CiteResponse {
+    /// The quote.
+    #[oai(status = 200)]
+    Found(Json<Quote>, #[oai(header = "ETag")] String),
+    /// The quote matches `If-None-Match`, so the client's copy is current.
+    #[oai(status = 304)]
+    NotModified(#[oai(header = "ETag")] String),
+}
+
+#[derive(ApiResponse)]
+enum UpdateResponse {
+    /// The quote after the update.
+    #[oai(status = 200)]
+    Updated(Json<Quote>, #[oai(header = "ETag")] String),
+}
+
+#[derive(Object)]
 struct QuotePaginationResponse {
     page: usize,
     quotes: Vec<Quote>,
     next_token: Option<String>,
 }
 
-#[handler]
-async fn quotes_paginate(
-    pool: Data<&PgPool>,
-    token: Query<HashMap<String, String>>,
-    pagination_statuses: Data<&PaginationStatuses>,
-) -> Response {
-    if let Some(token) = token.get("token") {
-        let mut lock = pagination_statuses.0 .0.lock().await;
-        let Some(pagination_status) = lock.get(token).map(|s| s.clone()) else {
-            return StatusCode::BAD_REQUEST.into();
+#[derive(Object)]
+struct SearchPaginationResponse {
+    page: usize,
+    results: Vec<SearchHit>,
+    next_token: Option<String>,
+}
+
+#[derive(Object)]
+struct AuditPaginationResponse {
+    page: usize,
+    entries: Vec<AuditEntry>,
+    next_token: Option<String>,
+}
+
+/// Quotes to import, with `author` and `quote` columns or fields, and
+/// optionally `tags`.
+#[derive(ApiRequest)]
+enum ImportPayload {
+    /// With a header row. Tags are joined by `;`.
+    #[oai(content_type = "text/csv")]
+    Csv(PlainText<String>),
+    /// One JSON object per line.
+    #[oai(content_type = "application/x-ndjson")]
+    Ndjson(PlainText<String>),
+}
+
+/// A row that couldn't be imported.
+#[derive(Object)]
+struct RowError {
+    /// Line of the row in the request body, starting at 1.
+    line: u64,
+    error: String,
+}
+
+#[derive(Object)]
+struct ImportReport {
+    /// How many quotes were stored.
+    imported: u64,
+    errors: Vec<RowError>,
+}
+
+#[derive(ApiResponse)]
+enum ImportResponse {
+    /// The valid rows were stored; `errors` lists the ones that were skipped.
+    #[oai(status = 200)]
+    Imported(Json<ImportReport>),
+    /// Nothing was stored, as the import was all-or-nothing and some rows
+    /// were invalid.
+    #[oai(status = 422)]
+    Rejected(Json<ImportReport>),
+}
+
+#[derive(ResponseContent)]
+enum ExportBody {
+    /// A single array.
+    #[oai(content_type = "application/json")]
+    Json(Binary<Body>),
+    #[oai(content_type = "text/csv")]
+    Csv(Binary<Body>),
+    #[oai(content_type = "application/x-ndjson")]
+    Ndjson(Binary<Body>),
+}
+
+#[derive(ApiResponse)]
+enum ExportResponse {
+    /// Every quote, oldest first.
+    #[oai(status = 200)]
+    Exported(ExportBody),
+}
+
+#[OpenApi(prefix_path = "/19", tag = "ApiTags::Day19")]
+impl Api {
+    /// Delete every quote for good, including the trash and all history.
+    #[oai(path = "/reset", method = "post")]
+    async fn quotes_reset(
+        &self,
+        /// Must be `true`, as a reset can't be undone.
+        confirm: Query<Option<bool>>,
+        caller: Caller,
+    ) -> Result<(), AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Reset)?;
+        if confirm.0 != Some(true) {
+            return Err(AppError::BadRequest(
+                "Resetting deletes every quote for good; pass confirm=true to go ahead".to_owned(),
+            ));
+        }
+
+        self.store.reset(&audit).await
+    }
+
+    /// Store a new quote.
+    #[oai(path = "/draft", method = "post")]
+    async fn quotes_draft(
+        &self,
+        quote: Json<QuoteInput>,
+        caller: Caller,
+    ) -> Result<DraftResponse, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Draft)?;
+        let (quote, tags) = quote.validate()?;
+        let inserted = self
+            .store
+            .insert(
+                &quote.author,
+                &quote.quote,
+                &tags.unwrap_or_default(),
+                &audit,
+            )
+            .await?;
+        let etag = inserted.etag();
+
+        Ok(DraftResponse::Created(Json(inserted), etag))
+    }
+
+    /// Fetch a single quote.
+    #[oai(path = "/cite/:id", method = "get")]
+    async fn quotes_cite(
+        &self,
+        id: Path<Uuid>,
+        /// Entity tags of copies the client already has.
+        #[oai(name = "If-None-Match")]
+        if_none_match: Header<Option<String>>,
+    ) -> Result<CiteResponse, AppError> {
+        let quote = self
+            .store
+            .get(*id)
+            .await?
+            .ok_or_else(|| quote_not_found(*id))?;
+        let etag = quote.etag();
+
+        match if_none_match.0 {
+            Some(header) if etag::if_none_match(&header, &quote) => {
+                Ok(CiteResponse::NotModified(etag))
+            }
+            _ => Ok(CiteResponse::Found(Json(quote), etag)),
+        }
+    }
+
+    /// Replace a quote's author, text and tags, bumping its version.
+    #[oai(path = "/undo/:id", method = "put")]
+    async fn quotes_update(
+        &self,
+        id: Path<Uuid>,
+        /// Only update the quote if it still has one of these entity tags.
+        #[oai(name = "If-Match")]
+        if_match: Header<Option<String>>,
+        new_quote: Json<QuoteInput>,
+        caller: Caller,
+    ) -> Result<UpdateResponse, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Undo)?;
+        let (new_quote, tags) = new_quote.validate()?;
+        let expected = if_match.0.as_deref().and_then(etag::if_match_versions);
+        let updated = self
+            .store
+            .update(
+                *id,
+                &new_quote.author,
+                &new_quote.quote,
+                tags.as_deref(),
+                expected.as_deref(),
+                &audit,
+            )
+            .await?;
+
+        match updated {
+            Some(quote) => {
+                let etag = quote.etag();
+                Ok(UpdateResponse::Updated(Json(quote), etag))
+            }
+            None => Err(self.precondition_or_not_found(*id, expected).await),
+        }
+    }
+
+    /// Move a quote to the trash, returning it.
+    #[oai(path = "/remove/:id", method = "delete")]
+    async fn quotes_delete(
+        &self,
+        id: Path<Uuid>,
+        /// Only delete the quote if it still has one of these entity tags.
+        #[oai(name = "If-Match")]
+        if_match: Header<Option<String>>,
+        caller: Caller,
+    ) -> Result<Json<Quote>, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Remove)?;
+        let expected = if_match.0.as_deref().and_then(etag::if_match_versions);
+
+        match self.store.delete(*id, expected.as_deref(), &audit).await? {
+            Some(quote) => Ok(Json(quote)),
+            None => Err(self.precondition_or_not_found(*id, expected).await),
+        }
+    }
+
+    /// List quotes a page at a time, oldest first. Pass the returned
+    /// `next_token` to get the following page.
+    #[oai(path = "/list", method = "get")]
+    async fn quotes_paginate(
+        &self,
+        /// Only quotes with this tag.
+        tag: Query<Option<String>>,
+        /// The `next_token` of the previous page, for the same tag.
+        token: Query<Option<String>>,
+        /// Quotes per page; defaults to the configured page size.
+        limit: Query<Option<usize>>,
+    ) -> Result<Json<QuotePaginationResponse>, AppError> {
+        let limit = self.page_limit(limit.0)?;
+        let listing = Listing {
+            tag: tag.0.as_deref().and_then(validate::tag),
+            ..Listing::default()
         };
-        let mut quotes = pagination_status.remaining;
-        let mut next_token = None;
+        let cursor = self.listing_cursor(token.0, &listing)?;
+        let page = cursor.as_ref().map_or(1, |cursor| cursor.page);
+
+        // Fetching one extra quote tells us whether there's another page.
+        let quotes = self
+            .store
+            .list_after(
+                cursor.map(|cursor| cursor.after),
+                listing.tag.as_deref(),
+                None,
+                limit + 1,
+            )
+            .await?;
+
+        Ok(Json(self.quote_page(quotes, page, limit, listing)))
+    }
+
+    /// List the quotes in the trash, paginated like `/19/list`.
+    #[oai(path = "/trash", method = "get")]
+    async fn quotes_trash(
+        &self,
+        /// The `next_token` of the previous page.
+        token: Query<Option<String>>,
+        /// Quotes per page; defaults to the configured page size.
+        limit: Query<Option<usize>>,
+    ) -> Result<Json<QuotePaginationResponse>, AppError> {
+        let limit = self.page_limit(limit.0)?;
+        let listing = Listing {
+            trash: true,
+            ..Listing::default()
+        };
+        let cursor = self.listing_cursor(token.0, &listing)?;
+        let page = cursor.as_ref().map_or(1, |cursor| cursor.page);
+
+        // Fetching one extra quote tells us whether there's another page.
+        let quotes = self
+            .store
+            .trash_after(cursor.map(|cursor| cursor.after), limit + 1)
+            .await?;
+
+        Ok(Json(self.quote_page(quotes, page, limit, listing)))
+    }
+
+    /// Take a quote out of the trash.
+    #[oai(path = "/restore/:id", method = "post")]
+    async fn quotes_untrash(
+        &self,
+        id: Path<Uuid>,
+        caller: Caller,
+    ) -> Result<UpdateResponse, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Restore)?;
+        let restored = self.untrash(*id, &audit).await?;
+        let etag = restored.etag();
+
+        Ok(UpdateResponse::Updated(Json(restored), etag))
+    }
 
-        if quotes.len() > 3 {
-            next_token = Some(Alphanumeric.sample_string(&mut rand::thread_rng(), 16));
+    /// Delete a quote in the trash for good, along with its history,
+    /// returning it.
+    #[oai(path = "/purge/:id", method = "delete")]
+    async fn quotes_purge(&self, id: Path<Uuid>, caller: Caller) -> Result<Json<Quote>, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Purge)?;
+        match self.store.purge(*id, &audit).await? {
+            Some(quote) => Ok(Json(quote)),
+            None => Err(self.not_in_trash(*id).await),
+        }
+    }
+
+    /// Fetch a quote picked at random, every quote being as likely.
+    #[oai(path = "/random", method = "get")]
+    async fn quotes_random(
+        &self,
+        /// Only pick among this author's quotes, ignoring case.
+        author: Query<Option<String>>,
+    ) -> Result<Json<Quote>, AppError> {
+        self.store
+            .pick(rand::random(), author.0.as_deref())
+            .await?
+            .map(Json)
+            .ok_or_else(|| match author.0 {
+                Some(author) => AppError::NotFound(format!("No quotes by {author}")),
+                None => AppError::NotFound("No quotes yet".to_owned()),
+            })
+    }
+
+    /// Fetch the quote of the day. It's picked the first time its date is
+    /// asked for and kept, so every replica agrees on it and quotes added or
+    /// removed later don't change it, unless it goes to the trash itself.
+    /// Days start at midnight in the configured time zone.
+    #[oai(path = "/today", method = "get")]
+    async fn quotes_today(
+        &self,
+        /// Another day to get the quote of; defaults to today.
+        date: Query<Option<NaiveDate>>,
+    ) -> Result<Json<QuoteOfTheDay>, AppError> {
+        let date = date
+            .0
+            .unwrap_or_else(|| Utc::now().with_timezone(&self.today_zone).date_naive());
+        let quote = self
+            .store
+            .quote_of_the_day(date, day_hash(self.today_seed, date))
+            .await?
+            .ok_or_else(|| AppError::NotFound("No quotes yet".to_owned()))?;
+
+        Ok(Json(QuoteOfTheDay { date, quote }))
+    }
+
+    /// List every tag in use, with how many quotes carry it, most used first.
+    #[oai(path = "/tags", method = "get")]
+    async fn quotes_tags(&self) -> Result<Json<Vec<TagCount>>, AppError> {
+        self.store.tag_counts().await.map(Json)
+    }
 
-            let rest = quotes.split_off(3);
-            lock.insert(
-                next_token.as_ref().unwrap().clone(),
-                PaginationStatus {
-                    page: pagination_status.page + 1,
-                    remaining: rest,
-                },
-            );
+    /// List every author with quotes outside the trash, with figures over
+    /// those quotes, most quoted first.
+    #[oai(path = "/authors", method = "get")]
+    async fn quotes_authors(&self) -> Result<Json<Vec<Author>>, AppError> {
+        self.store.authors().await.map(Json)
+    }
+
+    /// Fetch an author, along with their quotes paginated like `/19/list`.
+    #[oai(path = "/authors/:id", method = "get")]
+    async fn quotes_author(
+        &self,
+        id: Path<Uuid>,
+        /// The `next_token` of the previous page, for the same author.
+        token: Query<Option<String>>,
+        /// Quotes per page; defaults to the configured page size.
+        limit: Query<Option<usize>>,
+    ) -> Result<Json<AuthorProfile>, AppError> {
+        let limit = self.page_limit(limit.0)?;
+        let listing = Listing {
+            author: Some(*id),
+            ..Listing::default()
+        };
+        let cursor = self.listing_cursor(token.0, &listing)?;
+        let page = cursor.as_ref().map_or(1, |cursor| cursor.page);
+
+        let author = self
+            .store
+            .author(*id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No author with id {}", *id)))?;
+        // Fetching one extra quote tells us whether there's another page.
+        let quotes = self
+            .store
+            .list_after(
+                cursor.map(|cursor| cursor.after),
+                None,
+                Some(*id),
+                limit + 1,
+            )
+            .await?;
+
+        Ok(Json(AuthorProfile {
+            author,
+            quotes: self.quote_page(quotes, page, limit, listing),
+        }))
+    }
+
+    /// Search quotes, paginated like `/19/list`. With a text query the best
+    /// matches come first, otherwise the oldest.
+    #[oai(path = "/search", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn quotes_search(
+        &self,
+        /// Words to look for in the author and text. Supports "quoted
+        /// phrases", `or` and `-excluded` words.
+        q: Query<Option<String>>,
+        /// Only quotes by this author, ignoring case.
+        author: Query<Option<String>>,
+        /// Only quotes created at or after this time.
+        from: Query<Option<DateTime<Utc>>>,
+        /// Only quotes created before this time.
+        to: Query<Option<DateTime<Utc>>>,
+        /// Only quotes at this version or later.
+        min_version: Query<Option<i32>>,
+        /// The `next_token` of the previous page, for the same search.
+        token: Query<Option<String>>,
+        /// Results per page; defaults to the configured page size.
+        limit: Query<Option<usize>>,
+    ) -> Result<Json<SearchPaginationResponse>, AppError> {
+        let limit = self.page_limit(limit.0)?;
+        // Postgres only keeps microseconds, and so do tokens.
+        let filter = QuoteFilter {
+            text: q.0.filter(|q| !q.trim().is_empty()),
+            author: author.0,
+            created_from: from.0.map(|from| from.trunc_subsecs(6)),
+            created_before: to.0.map(|to| to.trunc_subsecs(6)),
+            min_version: min_version.0,
+        };
+        if let (Some(from), Some(to)) = (filter.created_from, filter.created_before) {
+            if from > to {
+                return Err(AppError::BadRequest("from must not be after to".to_owned()));
+            }
         }
 
-        serde_json::to_string(&QuotePaginationResponse {
-            page: pagination_status.page,
-            quotes,
+        let cursor = token
+            .0
+            .map(|token| self.cursors.decode(&token))
+            .transpose()?;
+        let (page, after) = match cursor {
+            None => (1, None),
+            Some(Cursor {
+                page,
+                after,
+                search: Some(search),
+                ..
+            }) if search.filter == filter => (
+                page,
+                Some(SearchKey {
+                    rank: search.rank,
+                    key: after,
+                }),
+            ),
+            Some(_) => {
+                return Err(AppError::BadRequest(
+                    "That token belongs to a different search".to_owned(),
+                ))
+            }
+        };
+
+        // Fetching one extra hit tells us whether there's another page.
+        let mut results = self.store.search(&filter, after, limit + 1).await?;
+        let mut next_token = None;
+
+        if results.len() > limit {
+            results.truncate(limit);
+            let last = results.last().expect("limit is at least 1").key();
+            next_token = Some(self.cursors.encode(&Cursor {
+                page: page + 1,
+                after: last.key,
+                listing: Listing::default(),
+                search: Some(SearchCursor {
+                    filter,
+                    rank: last.rank,
+                }),
+            }));
+        }
+
+        Ok(Json(SearchPaginationResponse {
+            page,
+            results,
             next_token,
-        })
-        .unwrap()
-        .into()
-    } else {
-        let mut quotes = sqlx::query_as!(Quote, "SELECT * FROM quotes ORDER BY created_at ASC")
-            .fetch_all(*pool)
-            .await
-            .unwrap();
+        }))
+    }
+
+    /// Store many quotes at once, validating each row. Invalid rows are
+    /// skipped, unless `atomic` is set, in which case any invalid row stops
+    /// the whole import.
+    #[oai(path = "/import", method = "post")]
+    async fn quotes_import(
+        &self,
+        /// Store nothing unless every row is valid.
+        atomic: Query<Option<bool>>,
+        payload: ImportPayload,
+        caller: Caller,
+    ) -> Result<ImportResponse, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Import)?;
+        let (quotes, errors) = match payload {
+            ImportPayload::Csv(PlainText(body)) => bulk::parse_csv(&body)?,
+            ImportPayload::Ndjson(PlainText(body)) => bulk::parse_ndjson(&body),
+        };
+
+        if atomic.0.unwrap_or(false) && !errors.is_empty() {
+            return Ok(ImportResponse::Rejected(Json(ImportReport {
+                imported: 0,
+                errors,
+            })));
+        }
+
+        let imported = if quotes.is_empty() {
+            0
+        } else {
+            self.store.insert_many(&quotes, &audit).await?
+        };
+
+        Ok(ImportResponse::Imported(Json(ImportReport {
+            imported,
+            errors,
+        })))
+    }
+
+    /// Stream every quote, oldest first, as JSON, CSV or NDJSON depending on
+    /// `Accept`.
+    #[oai(path = "/export", method = "get")]
+    async fn quotes_export(
+        &self,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+    ) -> Result<ExportResponse, AppError> {
+        let format = ExportFormat::negotiate(accept.0.as_deref())?;
+        let body = Binary(Body::from_bytes_stream(
+            format
+                .export(self.store.clone())
+                .map_err(std::io::Error::other),
+        ));
+
+        Ok(ExportResponse::Exported(match format {
+            ExportFormat::Json => ExportBody::Json(body),
+            ExportFormat::Csv => ExportBody::Csv(body),
+            ExportFormat::Ndjson => ExportBody::Ndjson(body),
+        }))
+    }
+
+    /// Stream changes to quotes as server-sent events, typed `created`,
+    /// `updated`, `deleted` or `reset` and carrying the quote. Reconnecting
+    /// with `Last-Event-ID` first replays the events missed since, as long as
+    /// they are still kept.
+    #[oai(path = "/events", method = "get")]
+    async fn quotes_events(
+        &self,
+        #[oai(name = "Last-Event-ID")] last_event_id: Header<Option<i64>>,
+    ) -> Result<EventStream<QuoteEvents>, AppError> {
+        let events = self.store.subscribe(last_event_id.0).await?;
+
+        Ok(EventStream::new(events)
+            .keep_alive(Duration::from_secs(15))
+            .to_event(|event| {
+                let data = match &event.quote {
+                    Some(quote) => quote.to_json_string(),
+                    // Clients ignore events without data.
+                    None => "{}".to_owned(),
+                };
+                let kind = match event.kind {
+                    QuoteEventKind::Created => "created",
+                    QuoteEventKind::Updated => "updated",
+                    QuoteEventKind::Deleted => "deleted",
+                    QuoteEventKind::Reset => "reset",
+                };
+                Event::message(data)
+                    .event_type(kind)
+                    .id(event.id.to_string())
+            }))
+    }
+
+    /// List every version of a quote, oldest first, including deleted ones.
+    #[oai(path = "/versions/:id", method = "get")]
+    async fn quotes_versions(&self, id: Path<Uuid>) -> Result<Json<Vec<QuoteVersion>>, AppError> {
+        let versions = self.store.versions(*id).await?;
+        if versions.is_empty() {
+            return Err(quote_not_found(*id));
+        }
+
+        Ok(Json(versions))
+    }
+
+    /// Fetch a quote as it was at one version.
+    #[oai(path = "/versions/:id/:version", method = "get")]
+    async fn quotes_version(
+        &self,
+        id: Path<Uuid>,
+        version: Path<i32>,
+    ) -> Result<Json<QuoteVersion>, AppError> {
+        self.find_version(*id, *version).await.map(Json)
+    }
+
+    /// Bring a quote back to the author and text of an earlier version. The
+    /// result is a new version, so the revert can itself be reverted.
+    #[oai(path = "/versions/:id/:version/revert", method = "post")]
+    async fn quotes_revert(
+        &self,
+        id: Path<Uuid>,
+        version: Path<i32>,
+        caller: Caller,
+    ) -> Result<Json<Quote>, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Revert)?;
+        let target = self.find_version(*id, *version).await?;
+
+        self.store
+            .update(*id, &target.author, &target.quote, None, None, &audit)
+            .await?
+            .map(Json)
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Quote {} was deleted; restore it before reverting",
+                    *id
+                ))
+            })
+    }
+
+    /// Bring a deleted quote back as it was when deleted, like
+    /// `/19/restore/:id`.
+    #[oai(path = "/versions/:id/restore", method = "post")]
+    async fn quotes_restore(
+        &self,
+        id: Path<Uuid>,
+        caller: Caller,
+    ) -> Result<DraftResponse, AppError> {
+        let audit = self.auditor.audit(caller, AuditOperation::Restore)?;
+        let restored = self.untrash(*id, &audit).await?;
+        let etag = restored.etag();
+
+        Ok(DraftResponse::Created(Json(restored), etag))
+    }
+
+    /// List the changes made to quotes through the API, newest first,
+    /// paginated like `/19/list`. Imports are logged an entry per quote.
+    #[oai(path = "/audit", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn quotes_audit(
+        &self,
+        /// Only changes by this actor.
+        actor: Query<Option<String>>,
+        /// Only changes of this kind.
+        operation: Query<Option<AuditOperation>>,
+        /// Only changes to this quote.
+        quote_id: Query<Option<Uuid>>,
+        /// Only changes made at or after this time.
+        from: Query<Option<DateTime<Utc>>>,
+        /// Only changes made before this time.
+        to: Query<Option<DateTime<Utc>>>,
+        /// The `next_token` of the previous page, for the same filters.
+        token: Query<Option<String>>,
+        /// Entries per page; defaults to the configured page size.
+        limit: Query<Option<usize>>,
+    ) -> Result<Json<AuditPaginationResponse>, AppError> {
+        let limit = self.page_limit(limit.0)?;
+        // Postgres only keeps microseconds, and so do tokens.
+        let filter = AuditFilter {
+            actor: actor.0,
+            operation: operation.0,
+            quote_id: quote_id.0,
+            from: from.0.map(|from| from.trunc_subsecs(6)),
+            to: to.0.map(|to| to.trunc_subsecs(6)),
+        };
+        if let (Some(from), Some(to)) = (filter.from, filter.to) {
+            if from > to {
+                return Err(AppError::BadRequest("from must not be after to".to_owned()));
+            }
+        }
+
+        let cursor = token
+            .0
+            .map(|token| self.cursors.decode_audit(&token))
+            .transpose()?;
+        let (page, before) = match cursor {
+            None => (1, None),
+            Some(cursor) if cursor.filter == filter => (cursor.page, Some(cursor.before)),
+            Some(_) => {
+                return Err(AppError::BadRequest(
+                    "That token belongs to a listing with different filters".to_owned(),
+                ))
+            }
+        };
+
+        // Fetching one extra entry tells us whether there's another page.
+        let mut entries = self.store.audit_log(&filter, before, limit + 1).await?;
         let mut next_token = None;
+        if entries.len() > limit {
+            entries.truncate(limit);
+            let last = entries.last().expect("limit is at least 1");
+            next_token = Some(self.cursors.encode_audit(&AuditCursor {
+                page: page + 1,
+                before: last.id,
+                filter,
+            }));
+        }
+
+        Ok(Json(AuditPaginationResponse {
+            page,
+            entries,
+            next_token,
+        }))
+    }
+}
+
+impl Api {
+    fn page_limit(&self, limit: Option<usize>) -> Result<usize, AppError> {
+        let limit = limit.unwrap_or(self.page_size);
+        if !(1..=self.max_page_size).contains(&limit) {
+            return Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                self.max_page_size
+            )));
+        }
+
+        Ok(limit)
+    }
+
+    /// Decodes the token of a `/19/list`, `/19/trash` or `/19/authors/:id`
+    /// page, which must continue the same listing.
+    fn listing_cursor(
+        &self,
+        token: Option<String>,
+        listing: &Listing,
+    ) -> Result<Option<Cursor>, AppError> {
+        let Some(token) = token else {
+            return Ok(None);
+        };
+        let cursor = self.cursors.decode(&token)?;
+        if cursor.search.is_some() {
+            return Err(AppError::BadRequest(
+                "That token continues a search; pass it to /19/search".to_owned(),
+            ));
+        }
+        if cursor.listing != *listing {
+            return Err(AppError::BadRequest(
+                "That token belongs to a different listing".to_owned(),
+            ));
+        }
 
-        if quotes.len() > 3 {
-            next_token = Some(Alphanumeric.sample_string(&mut rand::thread_rng(), 16));
+        Ok(Some(cursor))
+    }
 
-            let rest = quotes.split_off(3);
-            pagination_statuses.0 .0.lock().await.insert(
-                next_token.as_ref().unwrap().clone(),
-                PaginationStatus {
-                    page: 2,
-                    remaining: rest,
-                },
-            );
+    /// A page of a listing, out of `quotes` fetched with one to spare.
+    fn quote_page(
+        &self,
+        mut quotes: Vec<Quote>,
+        page: usize,
+        limit: usize,
+        listing: Listing,
+    ) -> QuotePaginationResponse {
+        let mut next_token = None;
+        if quotes.len() > limit {
+            quotes.truncate(limit);
+            let last = quotes.last().expect("limit is at least 1");
+            next_token = Some(self.cursors.encode(&Cursor {
+                page: page + 1,
+                after: last.key(),
+                listing,
+                search: None,
+            }));
         }
 
-        serde_json::to_string(&QuotePaginationResponse {
-            page: 1,
+        QuotePaginationResponse {
+            page,
             quotes,
             next_token,
-        })
-        .unwrap()
-        .into()
+        }
+    }
+
+    async fn untrash(&self, id: Uuid, audit: &Audit) -> Result<Quote, AppError> {
+        match self.store.restore(id, audit).await? {
+            Some(quote) => Ok(quote),
+            None => Err(self.not_in_trash(id).await),
+        }
+    }
+
+    /// Why a quote couldn't be found in the trash: either it's live, or it
+    /// doesn't exist at all.
+    async fn not_in_trash(&self, id: Uuid) -> AppError {
+        match self.store.get(id).await {
+            Ok(Some(_)) => AppError::Conflict(format!("Quote {id} isn't in the trash")),
+            Ok(None) => quote_not_found(id),
+            Err(err) => err,
+        }
+    }
+
+    async fn find_version(&self, id: Uuid, version: i32) -> Result<QuoteVersion, AppError> {
+        self.store
+            .versions(id)
+            .await?
+            .into_iter()
+            .find(|v| v.version == version)
+            .ok_or_else(|| AppError::NotFound(format!("Quote {id} has no version {version}")))
     }
+
+    /// Why a conditional write touched nothing: either the quote is gone, or
+    /// it has moved on from the versions the client expected.
+    async fn precondition_or_not_found(&self, id: Uuid, expected: Option<Vec<i32>>) -> AppError {
+        if expected.is_none() {
+            return quote_not_found(id);
+        }
+
+        match self.store.get(id).await {
+            Ok(Some(current)) => AppError::PreconditionFailed(format!(
+                "Quote {id} is at version {}, which If-Match doesn't list",
+                current.version.expect("Stored quotes have a version")
+            )),
+            Ok(None) => quote_not_found(id),
+            Err(err) => err,
+        }
+    }
+}
+
+/// FNV-1a of the seed and the date, which unlike the standard library's
+/// hashers is sure to come out the same in every build.
+fn day_hash(seed: u64, date: NaiveDate) -> u32 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in seed
+        .to_le_bytes()
+        .into_iter()
+        .chain(date.to_string().into_bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    (hash ^ (hash >> 32)) as u32
+}
+
+fn quote_not_found(id: Uuid) -> AppError {
+    AppError::NotFound(format!("No quote with id {id}"))
 }
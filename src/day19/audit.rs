@@ -0,0 +1,145 @@
+//! Who is behind a change, for the audit log. A bearer token signed with the
+//! configured secret names the actor in its `sub` claim. Requests through a
+//! trusted proxy may name one in the `X-Actor` header instead, as set by a
+//! proxy that authenticates users, and without either the change is logged
+//! anonymously. Likewise, only trusted proxies get to say which address a
+//! request came from.
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use poem::{Addr, FromRequest, Request, RequestBody};
+use serde::Deserialize;
+use std::{collections::HashSet, net::IpAddr};
+
+use super::{store::Audit, AuditOperation};
+use crate::error::AppError;
+
+/// What a request says about who sent it. It's checked separately, by
+/// [`Auditor::audit`], as that takes the secret and the trusted proxies.
+pub struct Caller {
+    bearer: Option<String>,
+    actor: Option<String>,
+    /// The address of the other end of the connection, if it's a TCP one.
+    peer: Option<IpAddr>,
+    /// `X-Real-IP`, as set by a proxy.
+    real_ip: Option<IpAddr>,
+    /// `X-Forwarded-For`, each proxy appending who it got the request from.
+    forwarded_for: Vec<IpAddr>,
+}
+
+impl<'a> FromRequest<'a> for Caller {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let bearer = header("authorization")
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim().to_owned());
+        let actor = header("x-actor")
+            .map(str::trim)
+            .filter(|actor| !actor.is_empty())
+            .map(str::to_owned);
+        let peer = match req.remote_addr().0 {
+            Addr::SocketAddr(addr) => Some(addr.ip()),
+            _ => None,
+        };
+        let real_ip = header("x-real-ip").and_then(|ip| ip.trim().parse().ok());
+        let forwarded_for = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+
+        Ok(Self {
+            bearer,
+            actor,
+            peer,
+            real_ip,
+            forwarded_for,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ActorClaims {
+    sub: String,
+}
+
+pub struct Auditor {
+    key: DecodingKey,
+    validation: Validation,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Auditor {
+    pub fn new(secret: &str, trusted_proxies: &[IpAddr]) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        // Tokens may be issued without an expiry, but are refused once past
+        // one.
+        validation.required_spec_claims = HashSet::new();
+
+        Self {
+            key: DecodingKey::from_secret(secret.as_bytes()),
+            validation,
+            trusted_proxies: trusted_proxies.to_vec(),
+        }
+    }
+
+    /// How `caller` doing `operation` goes into the audit log. Fails if they
+    /// sent a bearer token that doesn't hold up.
+    pub fn audit(&self, caller: Caller, operation: AuditOperation) -> Result<Audit, AppError> {
+        let proxied = caller.peer.is_some_and(|peer| self.trusted(peer));
+        let client_ip = self.client_ip(&caller);
+        let actor = match caller.bearer {
+            Some(token) => {
+                let claims =
+                    jsonwebtoken::decode::<ActorClaims>(&token, &self.key, &self.validation)
+                        .map_err(|err| {
+                            AppError::Unauthorized(format!("Invalid bearer token: {err}"))
+                        })?
+                        .claims;
+                Some(claims.sub)
+            }
+            None => caller.actor.filter(|_| proxied),
+        };
+
+        Ok(Audit {
+            operation,
+            actor,
+            client_ip,
+        })
+    }
+
+    /// The client `caller`'s request came from: the peer, unless that's a
+    /// trusted proxy, which says who it got the request from. That may be
+    /// another trusted proxy in turn.
+    fn client_ip(&self, caller: &Caller) -> Option<IpAddr> {
+        let peer = caller.peer?;
+        if !self.trusted(peer) {
+            return Some(peer);
+        }
+        if let Some(real_ip) = caller.real_ip {
+            return Some(real_ip);
+        }
+
+        // Only the addresses appended by trusted proxies can be believed, so
+        // the chain is followed from the end.
+        let mut client = peer;
+        for &ip in caller.forwarded_for.iter().rev() {
+            client = ip;
+            if !self.trusted(ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    fn trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.contains(&ip)
+    }
+}
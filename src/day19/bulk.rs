@@ -0,0 +1,299 @@
+//! Moving quotes in and out in bulk: parsing imports row by row, and
+//! streaming exports in whichever format the client accepts.
+
+use futures_util::{stream, Stream, StreamExt as _, TryStreamExt as _};
+use mime::Mime;
+use poem_openapi::types::ToJSON as _;
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+use super::{
+    store::{NewQuote, QuoteKey},
+    validate, Quote, RowError, SharedQuoteStore,
+};
+use crate::error::AppError;
+
+/// How many quotes an export holds in memory at once.
+const EXPORT_BATCH: usize = 500;
+
+/// The valid rows of an import, with their tags, and what was wrong with the
+/// others.
+type Parsed = (Vec<(NewQuote, Vec<String>)>, Vec<RowError>);
+
+/// One row of an import. Other columns or fields, such as the ones an export
+/// adds, are ignored.
+#[derive(Deserialize)]
+struct Row {
+    author: Option<String>,
+    quote: Option<String>,
+    tags: Option<Vec<String>>,
+}
+
+impl Row {
+    /// Held to the same rules as quotes drafted one at a time.
+    fn validate(self) -> Result<(NewQuote, Vec<String>), String> {
+        let quote = validate::new_quote(self.author.as_deref(), self.quote.as_deref());
+        let tags = validate::tags(self.tags.as_deref());
+        match (quote, tags) {
+            (Ok(quote), Ok(tags)) => Ok((quote, tags.unwrap_or_default())),
+            (quote, tags) => Err(quote
+                .err()
+                .into_iter()
+                .flatten()
+                .chain(tags.err().into_iter().flatten())
+                .map(|error| format!("{} {}", error.field, error.message))
+                .collect::<Vec<_>>()
+                .join("; ")),
+        }
+    }
+}
+
+/// A row of a CSV import, whose tags are joined by `;`.
+#[derive(Deserialize)]
+struct CsvRow {
+    author: Option<String>,
+    quote: Option<String>,
+    tags: Option<String>,
+}
+
+impl From<CsvRow> for Row {
+    fn from(row: CsvRow) -> Self {
+        Self {
+            author: row.author,
+            quote: row.quote,
+            tags: row
+                .tags
+                .map(|tags| tags.split(';').map(str::to_owned).collect()),
+        }
+    }
+}
+
+/// Parses a CSV import, whose header must name at least the `author` and
+/// `quote` columns; a `tags` column is optional.
+pub(super) fn parse_csv(body: &str) -> Result<Parsed, AppError> {
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|err| AppError::BadRequest(format!("Invalid CSV header: {err}")))?
+        .clone();
+    if !["author", "quote"]
+        .iter()
+        .all(|column| headers.iter().any(|header| header == *column))
+    {
+        return Err(AppError::BadRequest(
+            "The CSV header must name the author and quote columns".to_owned(),
+        ));
+    }
+
+    let mut quotes = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let row = record.map_err(|err| (err.position().map(|p| p.line()), err.to_string()));
+        let row = row.and_then(|record| {
+            let line = record.position().map(|p| p.line());
+            record
+                .deserialize::<CsvRow>(Some(&headers))
+                .map_err(|err| err.to_string())
+                .and_then(|row| Row::from(row).validate())
+                .map_err(|err| (line, err))
+        });
+        match row {
+            Ok(quote) => quotes.push(quote),
+            Err((line, error)) => errors.push(RowError {
+                line: line.unwrap_or_default(),
+                error,
+            }),
+        }
+    }
+
+    Ok((quotes, errors))
+}
+
+/// Parses an NDJSON import, one quote object per line. Blank lines are
+/// skipped.
+pub(super) fn parse_ndjson(body: &str) -> Parsed {
+    let mut quotes = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in body.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = serde_json::from_str::<Row>(line)
+            .map_err(|err| err.to_string())
+            .and_then(Row::validate);
+        match row {
+            Ok(quote) => quotes.push(quote),
+            Err(error) => errors.push(RowError {
+                line: i as u64 + 1,
+                error,
+            }),
+        }
+    }
+
+    (quotes, errors)
+}
+
+/// The media types each format is served for, preferred in this order when
+/// the client likes them as much.
+const MEDIA_TYPES: [(ExportFormat, &str, &str); 4] = [
+    (ExportFormat::Json, "application", "json"),
+    (ExportFormat::Csv, "text", "csv"),
+    (ExportFormat::Ndjson, "application", "x-ndjson"),
+    (ExportFormat::Ndjson, "application", "jsonl"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ExportFormat {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// Picks the format for an `Accept` header. Each format takes the quality
+    /// value of the most specific range matching it, and the best one wins,
+    /// then the one whose range the client listed first. No header means
+    /// JSON.
+    pub(super) fn negotiate(accept: Option<&str>) -> Result<Self, AppError> {
+        let Some(accept) = accept else {
+            return Ok(ExportFormat::Json);
+        };
+
+        // Ranges that don't parse, or whose quality value doesn't, are
+        // ignored.
+        let ranges: Vec<(Mime, f32)> = accept
+            .split(',')
+            .filter_map(|range| range.trim().parse::<Mime>().ok())
+            .filter_map(|range| {
+                let quality = match range.get_param("q") {
+                    Some(q) => q
+                        .as_str()
+                        .parse()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q))?,
+                    None => 1.0,
+                };
+                Some((range, quality))
+            })
+            .collect();
+
+        let mut best: Option<(f32, usize, ExportFormat)> = None;
+        for (format, type_, subtype) in MEDIA_TYPES {
+            let matched = ranges
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (range, quality))| {
+                    let specificity = if range.type_() == mime::STAR {
+                        0
+                    } else if range.type_() != type_ {
+                        return None;
+                    } else if range.subtype() == mime::STAR {
+                        1
+                    } else if range.subtype() == subtype {
+                        2
+                    } else {
+                        return None;
+                    };
+                    Some((specificity, Reverse(i), *quality))
+                })
+                .max_by_key(|(specificity, i, _)| (*specificity, *i));
+            let Some((_, Reverse(i), quality)) = matched else {
+                continue;
+            };
+            if quality > 0.0
+                && best.is_none_or(|(best_quality, best_i, _)| {
+                    quality > best_quality || (quality == best_quality && i < best_i)
+                })
+            {
+                best = Some((quality, i, format));
+            }
+        }
+
+        best.map(|(_, _, format)| format).ok_or_else(|| {
+            AppError::NotAcceptable(
+                "Quotes export as application/json, text/csv or application/x-ndjson".to_owned(),
+            )
+        })
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "[",
+            ExportFormat::Csv => "id,author,quote,version,created_at,tags\n",
+            ExportFormat::Ndjson => "",
+        }
+    }
+
+    fn footer(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "]\n",
+            ExportFormat::Csv | ExportFormat::Ndjson => "",
+        }
+    }
+
+    /// `first` tells whether anything was written before, for JSON's commas.
+    fn render(self, quotes: &[Quote], first: bool) -> String {
+        match self {
+            ExportFormat::Json => {
+                let mut out = String::new();
+                for (i, quote) in quotes.iter().enumerate() {
+                    if !(first && i == 0) {
+                        out.push(',');
+                    }
+                    out.push_str(&quote.to_json_string());
+                }
+                out
+            }
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for quote in quotes {
+                    writer
+                        .write_record([
+                            quote.id.map(|id| id.to_string()).unwrap_or_default(),
+                            quote.author.clone(),
+                            quote.quote.clone(),
+                            quote.version.map(|v| v.to_string()).unwrap_or_default(),
+                            quote.created_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                            quote.tags.as_deref().unwrap_or_default().join(";"),
+                        ])
+                        .expect("Writing to a Vec can't fail");
+                }
+                let bytes = writer.into_inner().expect("Writing to a Vec can't fail");
+                String::from_utf8(bytes).expect("Quotes are UTF-8")
+            }
+            ExportFormat::Ndjson => quotes
+                .iter()
+                .map(|quote| quote.to_json_string() + "\n")
+                .collect(),
+        }
+    }
+
+    /// Every quote in listing order, fetched a batch at a time as the client
+    /// reads. Batches are separate queries, so quotes changing mid-export may
+    /// or may not make it in.
+    pub(super) fn export(
+        self,
+        store: SharedQuoteStore,
+    ) -> impl Stream<Item = Result<String, AppError>> + Send + 'static {
+        // Where the next batch starts: `Some(None)` for the beginning, `None`
+        // once everything was read.
+        let start: Option<Option<QuoteKey>> = Some(None);
+        let batches = stream::try_unfold(
+            (store, start, true),
+            move |(store, next, first)| async move {
+                let Some(after) = next else {
+                    return Ok(None);
+                };
+                let quotes = store.list_after(after, None, None, EXPORT_BATCH).await?;
+                let next = (quotes.len() == EXPORT_BATCH).then(|| quotes.last().map(Quote::key));
+                let chunk = self.render(&quotes, first);
+
+                Ok(Some((chunk, (store, next, first && quotes.is_empty()))))
+            },
+        );
+
+        stream::once(async move { Ok(self.header().to_owned()) })
+            .chain(batches.try_filter(|chunk| futures_util::future::ready(!chunk.is_empty())))
+            .chain(stream::once(async move { Ok(self.footer().to_owned()) }))
+    }
+}
@@ -0,0 +1,217 @@
+//! Continuation tokens for `/19/list`, `/19/trash`, `/19/authors/:id`,
+//! `/19/search` and `/19/audit`. A token records where the previous page
+//! ended, so nothing is kept on the server between requests, and it is signed
+//! so that clients can't forge one.
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use super::{
+    store::{AuditFilter, QuoteFilter, QuoteKey},
+    AuditOperation,
+};
+use crate::error::AppError;
+
+/// Where the next page of quotes starts.
+pub struct Cursor {
+    /// Number of the page the token leads to.
+    pub page: usize,
+    /// The last quote of the previous page.
+    pub after: QuoteKey,
+    /// The listing the token continues; the default one for searches.
+    pub listing: Listing,
+    /// Set for search results only.
+    pub search: Option<SearchCursor>,
+}
+
+/// Which quotes a listing pages through, so that a token only continues the
+/// listing it came from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing {
+    /// Only quotes with this tag.
+    pub tag: Option<String>,
+    /// Only quotes by this author.
+    pub author: Option<Uuid>,
+    /// The quotes in the trash rather than the live ones.
+    pub trash: bool,
+}
+
+/// The search a token continues, so it can't be redeemed for another one.
+pub struct SearchCursor {
+    pub filter: QuoteFilter,
+    /// Rank of the last result of the previous page.
+    pub rank: Option<f32>,
+}
+
+/// Where the next page of the audit log starts.
+pub struct AuditCursor {
+    pub page: usize,
+    /// The last entry of the previous page.
+    pub before: i64,
+    /// The filters the token continues, so it can't be redeemed for others.
+    pub filter: AuditFilter,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    page: usize,
+    /// Microseconds since the epoch, which is all the precision Postgres
+    /// keeps.
+    created_at: i64,
+    id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    trash: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    search: Option<SearchClaims>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SearchClaims {
+    text: Option<String>,
+    author: Option<String>,
+    /// Microseconds since the epoch, like `created_at`.
+    created_from: Option<i64>,
+    created_before: Option<i64>,
+    min_version: Option<i32>,
+    rank: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuditClaims {
+    page: usize,
+    before: i64,
+    actor: Option<String>,
+    operation: Option<AuditOperation>,
+    quote_id: Option<Uuid>,
+    /// Microseconds since the epoch, like `created_at` in [`Claims`].
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+pub struct CursorCodec {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+impl CursorCodec {
+    pub fn new(secret: &str) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims = HashSet::new();
+        validation.validate_exp = false;
+
+        Self {
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            validation,
+        }
+    }
+
+    pub fn encode(&self, cursor: &Cursor) -> String {
+        let claims = Claims {
+            page: cursor.page,
+            created_at: cursor.after.created_at.timestamp_micros(),
+            id: cursor.after.id,
+            tag: cursor.listing.tag.clone(),
+            author: cursor.listing.author,
+            trash: cursor.listing.trash,
+            search: cursor.search.as_ref().map(|search| SearchClaims {
+                text: search.filter.text.clone(),
+                author: search.filter.author.clone(),
+                created_from: search.filter.created_from.map(|t| t.timestamp_micros()),
+                created_before: search.filter.created_before.map(|t| t.timestamp_micros()),
+                min_version: search.filter.min_version,
+                rank: search.rank,
+            }),
+        };
+
+        self.sign(&claims)
+    }
+
+    pub fn decode(&self, token: &str) -> Result<Cursor, AppError> {
+        let invalid = || AppError::BadRequest(format!("Invalid token {token}"));
+        let timestamp =
+            |micros: i64| DateTime::<Utc>::from_timestamp_micros(micros).ok_or_else(invalid);
+
+        let claims: Claims = self.verify(token)?;
+        let search = match claims.search {
+            Some(search) => Some(SearchCursor {
+                filter: QuoteFilter {
+                    text: search.text,
+                    author: search.author,
+                    created_from: search.created_from.map(timestamp).transpose()?,
+                    created_before: search.created_before.map(timestamp).transpose()?,
+                    min_version: search.min_version,
+                },
+                rank: search.rank,
+            }),
+            None => None,
+        };
+
+        Ok(Cursor {
+            page: claims.page,
+            after: QuoteKey {
+                created_at: timestamp(claims.created_at)?,
+                id: claims.id,
+            },
+            listing: Listing {
+                tag: claims.tag,
+                author: claims.author,
+                trash: claims.trash,
+            },
+            search,
+        })
+    }
+
+    pub fn encode_audit(&self, cursor: &AuditCursor) -> String {
+        self.sign(&AuditClaims {
+            page: cursor.page,
+            before: cursor.before,
+            actor: cursor.filter.actor.clone(),
+            operation: cursor.filter.operation,
+            quote_id: cursor.filter.quote_id,
+            from: cursor.filter.from.map(|t| t.timestamp_micros()),
+            to: cursor.filter.to.map(|t| t.timestamp_micros()),
+        })
+    }
+
+    pub fn decode_audit(&self, token: &str) -> Result<AuditCursor, AppError> {
+        let invalid = || AppError::BadRequest(format!("Invalid token {token}"));
+        let timestamp =
+            |micros: i64| DateTime::<Utc>::from_timestamp_micros(micros).ok_or_else(invalid);
+
+        let claims: AuditClaims = self.verify(token)?;
+
+        Ok(AuditCursor {
+            page: claims.page,
+            before: claims.before,
+            filter: AuditFilter {
+                actor: claims.actor,
+                operation: claims.operation,
+                quote_id: claims.quote_id,
+                from: claims.from.map(timestamp).transpose()?,
+                to: claims.to.map(timestamp).transpose()?,
+            },
+        })
+    }
+
+    fn sign(&self, claims: &impl Serialize) -> String {
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key)
+            .expect("Cursor claims always serialize")
+    }
+
+    /// The claims of a token signed by [`CursorCodec::sign`]. Tokens of one
+    /// kind lack the claims of the others, so they can't be mixed up.
+    fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, AppError> {
+        jsonwebtoken::decode::<T>(token, &self.decoding_key, &self.validation)
+            .map(|data| data.claims)
+            .map_err(|_| AppError::BadRequest(format!("Invalid token {token}")))
+    }
+}
@@ -0,0 +1,46 @@
+//! Conditional requests on quotes. A quote's entity tag is its version, which
+//! changes on every update, so it doubles as the token for optimistic
+//! concurrency control.
+
+use super::Quote;
+
+impl Quote {
+    pub(super) fn etag(&self) -> String {
+        format!(
+            "\"{}\"",
+            self.version.expect("Stored quotes have a version")
+        )
+    }
+}
+
+/// The versions an `If-Match` header accepts, or `None` for `*`, which any
+/// existing quote matches. `If-Match` compares strongly, so weak tags never
+/// match.
+pub(super) fn if_match_versions(header: &str) -> Option<Vec<i32>> {
+    if header.trim() == "*" {
+        return None;
+    }
+
+    Some(entity_tags(header).filter_map(parse_version).collect())
+}
+
+/// Whether an `If-None-Match` header matches `quote`, meaning the client's
+/// copy is current. This comparison is weak, so `W/` prefixes are ignored.
+pub(super) fn if_none_match(header: &str, quote: &Quote) -> bool {
+    if header.trim() == "*" {
+        return true;
+    }
+
+    entity_tags(header)
+        .map(|tag| tag.strip_prefix("W/").unwrap_or(tag))
+        .filter_map(parse_version)
+        .any(|version| Some(version) == quote.version)
+}
+
+fn entity_tags(header: &str) -> impl Iterator<Item = &str> {
+    header.split(',').map(str::trim)
+}
+
+fn parse_version(tag: &str) -> Option<i32> {
+    tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
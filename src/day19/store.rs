@@ -0,0 +1,267 @@
+//! Where quotes are kept. Handlers only talk to [`QuoteStore`], so the
+//! backend can be picked at startup.
+
+mod memory;
+mod postgres;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::{stream, stream::BoxStream, StreamExt as _};
+use std::{net::IpAddr, sync::Arc};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use super::{
+    AuditEntry, AuditOperation, Author, Quote, QuoteEvent, QuoteVersion, SearchHit, TagCount,
+};
+use crate::error::AppError;
+
+pub use memory::MemoryQuoteStore;
+pub use postgres::PgQuoteStore;
+
+/// Where a quote sits in listing order: oldest first, with the id breaking
+/// ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QuoteKey {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Quote {
+    pub(super) fn key(&self) -> QuoteKey {
+        QuoteKey {
+            created_at: self.created_at.expect("Stored quotes have a creation time"),
+            id: self.id.expect("Stored quotes have an id"),
+        }
+    }
+}
+
+/// A quote to store, as read from an import.
+#[derive(Debug, Clone)]
+pub struct NewQuote {
+    pub author: String,
+    pub quote: String,
+}
+
+/// Which quotes a search matches. Every criterion that is set must hold.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuoteFilter {
+    /// Words to look for in the author and text.
+    pub text: Option<String>,
+    /// Matched ignoring case.
+    pub author: Option<String>,
+    /// Inclusive.
+    pub created_from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub created_before: Option<DateTime<Utc>>,
+    pub min_version: Option<i32>,
+}
+
+/// A change made through the API, as the audit log records it.
+#[derive(Debug, Clone)]
+pub struct Audit {
+    pub operation: AuditOperation,
+    /// Who asked for the change, if they said.
+    pub actor: Option<String>,
+    pub client_ip: Option<IpAddr>,
+}
+
+/// Which audit log entries to list. Every criterion that is set must hold.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub operation: Option<AuditOperation>,
+    pub quote_id: Option<Uuid>,
+    /// Inclusive.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Where a hit sits in search order: best rank first, then [`QuoteKey`]
+/// order. Only searches for text are ranked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchKey {
+    pub rank: Option<f32>,
+    pub key: QuoteKey,
+}
+
+impl SearchHit {
+    pub(super) fn key(&self) -> SearchKey {
+        SearchKey {
+            rank: self.rank,
+            key: self.quote.key(),
+        }
+    }
+}
+
+/// Backends must agree on semantics: new quotes start at version 1, every
+/// update bumps the version and keeps `created_at`, the content replaced by an
+/// update is archived, and listing follows [`QuoteKey`] order. Deleted quotes
+/// go to the trash at the version they had, and only the trash methods and
+/// [`QuoteStore::versions`] see them. Tags are passed normalized, and come
+/// back sorted by name. Every quote links to an author, matched by name
+/// ignoring case and surrounding whitespace and created on first sight; authors
+/// only go away on reset. Methods taking an [`Audit`] log the change along
+/// with what it changed, atomically, and only if it went through. The log is
+/// never trimmed.
+#[async_trait]
+pub trait QuoteStore: Send + Sync {
+    /// Deletes every quote, along with its history, tags and authors.
+    async fn reset(&self, audit: &Audit) -> Result<(), AppError>;
+
+    async fn insert(
+        &self,
+        author: &str,
+        quote: &str,
+        tags: &[String],
+        audit: &Audit,
+    ) -> Result<Quote, AppError>;
+
+    /// Stores every quote, with its tags, or none of them, returning how many
+    /// were stored. They are created in order, so listings keep it, and each
+    /// is logged.
+    async fn insert_many(
+        &self,
+        quotes: &[(NewQuote, Vec<String>)],
+        audit: &Audit,
+    ) -> Result<u64, AppError>;
+
+    async fn get(&self, id: Uuid) -> Result<Option<Quote>, AppError>;
+
+    /// Replaces a quote's author and text, and its tags unless `tags` is
+    /// `None`. Returns `None` if the quote doesn't exist or, when `expected` is
+    /// given, isn't at one of those versions.
+    async fn update(
+        &self,
+        id: Uuid,
+        author: &str,
+        quote: &str,
+        tags: Option<&[String]>,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError>;
+
+    /// Moves a quote to the trash, returning it if it existed and, when
+    /// `expected` is given, was at one of those versions.
+    async fn delete(
+        &self,
+        id: Uuid,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError>;
+
+    /// Up to `limit` quotes in [`QuoteKey`] order, starting right after
+    /// `after`, or from the beginning. With a `tag`, only the quotes that
+    /// carry it, and with an `author`, only theirs.
+    async fn list_after(
+        &self,
+        after: Option<QuoteKey>,
+        tag: Option<&str>,
+        author: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError>;
+
+    /// Like [`QuoteStore::list_after`], but for the quotes in the trash.
+    async fn trash_after(
+        &self,
+        after: Option<QuoteKey>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError>;
+
+    /// The quote at position `n`, modulo their number, in [`QuoteKey`] order.
+    /// With an `author`, only their quotes count, matched ignoring case.
+    /// `None` if there are no such quotes.
+    async fn pick(&self, n: u32, author: Option<&str>) -> Result<Option<Quote>, AppError>;
+
+    /// The quote of the day for `date`. The first time a date is asked for,
+    /// or once its quote is in the trash, the one at position `n` is picked as
+    /// [`pick`](Self::pick) does and kept, so quotes coming and going later
+    /// leave it be.
+    async fn quote_of_the_day(&self, date: NaiveDate, n: u32) -> Result<Option<Quote>, AppError>;
+
+    /// How many quotes there are, leaving out the trash.
+    async fn count(&self) -> Result<i64, AppError>;
+
+    /// Every tag carried by at least one quote, with how many carry it, most
+    /// used first and then by name.
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, AppError>;
+
+    /// Every author with quotes outside the trash, most quoted first and then
+    /// by name.
+    async fn authors(&self) -> Result<Vec<Author>, AppError>;
+
+    /// `None` if there's no such author. Their figures may well be zero, as
+    /// authors outlive their quotes.
+    async fn author(&self, id: Uuid) -> Result<Option<Author>, AppError>;
+
+    /// Up to `limit` quotes matching `filter` in [`SearchKey`] order, starting
+    /// right after `after`, or from the beginning. Hits for a text search come
+    /// with a snippet of the quote, matches wrapped in `<mark>` tags.
+    async fn search(
+        &self,
+        filter: &QuoteFilter,
+        after: Option<SearchKey>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, AppError>;
+
+    /// Every version of a quote, archived or current, oldest first. The
+    /// current version of a trashed quote counts as archived when it was
+    /// trashed. Empty if the quote never existed or was purged.
+    async fn versions(&self, id: Uuid) -> Result<Vec<QuoteVersion>, AppError>;
+
+    /// Takes a quote out of the trash as it was, returning `None` if it isn't
+    /// in the trash.
+    async fn restore(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError>;
+
+    /// Removes a quote in the trash for good, along with its history,
+    /// returning `None` if it isn't in the trash.
+    async fn purge(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError>;
+
+    /// Up to `limit` audit log entries matching `filter`, newest first,
+    /// starting right before entry `before`, or from the latest.
+    async fn audit_log(
+        &self,
+        filter: &AuditFilter,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, AppError>;
+
+    /// Every change from now on, preceded by the ones after event `after` that
+    /// are still kept. Ends if the subscriber falls too far behind, after
+    /// which it can resubscribe from the last event it saw.
+    async fn subscribe(&self, after: Option<i64>) -> Result<QuoteEvents, AppError>;
+}
+
+pub type SharedQuoteStore = Arc<dyn QuoteStore>;
+
+pub type QuoteEvents = BoxStream<'static, QuoteEvent>;
+
+/// How many events a subscriber may fall behind by before it's dropped.
+const EVENT_BUFFER: usize = 1024;
+
+fn event_channel() -> broadcast::Sender<QuoteEvent> {
+    broadcast::channel(EVENT_BUFFER).0
+}
+
+/// Replays `backlog`, then follows `live` from where it left off. `live` must
+/// have been subscribed to before `backlog` was read, so that nothing falls
+/// in between.
+fn follow(
+    backlog: Vec<QuoteEvent>,
+    live: broadcast::Receiver<QuoteEvent>,
+    after: Option<i64>,
+) -> QuoteEvents {
+    let last = backlog.last().map(|event| event.id).or(after);
+    let live = stream::unfold(live, |mut live| async move {
+        match live.recv().await {
+            Ok(event) => Some((event, live)),
+            // Lagging subscribers are cut off rather than silently skipping
+            // events.
+            Err(_) => None,
+        }
+    })
+    .filter(move |event| std::future::ready(last.is_none_or(|last| event.id > last)));
+
+    stream::iter(backlog).chain(live).boxed()
+}
@@ -0,0 +1,669 @@
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeDelta};
+use poem_openapi::types::ToJSON as _;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashMap, VecDeque},
+    ops::Bound,
+};
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+use super::{
+    event_channel, follow, Audit, AuditFilter, NewQuote, QuoteEvents, QuoteFilter, QuoteKey,
+    QuoteStore, SearchKey,
+};
+use crate::{
+    clock::now,
+    day19::{
+        AuditEntry, Author, Quote, QuoteEvent, QuoteEventKind, QuoteVersion, SearchHit, TagCount,
+    },
+    error::AppError,
+};
+
+/// How many past events are kept for subscribers to catch up on.
+const EVENTS_KEPT: usize = 1000;
+
+/// Keeps quotes in process memory, for running without Postgres. Nothing
+/// survives a restart.
+#[derive(Default)]
+pub struct MemoryQuoteStore {
+    state: RwLock<State>,
+}
+
+struct State {
+    /// Including the ones in the trash.
+    quotes: BTreeMap<QuoteKey, Quote>,
+    /// Archived versions of each quote, oldest first.
+    history: HashMap<Uuid, Vec<QuoteVersion>>,
+    /// Each author's id and name, by [`author_key`].
+    authors: HashMap<String, (Uuid, String)>,
+    /// The latest events, oldest first.
+    events: VecDeque<QuoteEvent>,
+    last_event_id: i64,
+    subscribers: broadcast::Sender<QuoteEvent>,
+    /// The audit log, oldest first. Its ids are its positions, from 1.
+    audit_log: Vec<AuditEntry>,
+    /// The id of the quote picked for each day.
+    quotes_of_the_day: HashMap<NaiveDate, Uuid>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            quotes: BTreeMap::new(),
+            history: HashMap::new(),
+            authors: HashMap::new(),
+            events: VecDeque::new(),
+            last_event_id: 0,
+            subscribers: event_channel(),
+            audit_log: Vec::new(),
+            quotes_of_the_day: HashMap::new(),
+        }
+    }
+}
+
+impl State {
+    /// What the `quotes_record_event` trigger does in Postgres. Called with
+    /// the state locked, so events go out in order.
+    fn record(&mut self, kind: QuoteEventKind, quote: Option<&Quote>) {
+        self.last_event_id += 1;
+        let event = QuoteEvent {
+            id: self.last_event_id,
+            kind,
+            // Postgres keeps tags apart from the row the event records.
+            quote: quote.map(|quote| Quote {
+                tags: None,
+                ..quote.clone()
+            }),
+        };
+        if self.events.len() == EVENTS_KEPT {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+        // Nobody may be listening, which is fine.
+        let _ = self.subscribers.send(event);
+    }
+
+    fn find(&self, id: Uuid) -> Option<QuoteKey> {
+        self.quotes
+            .values()
+            .find(|q| q.id == Some(id))
+            .map(Quote::key)
+    }
+
+    fn find_live(&self, id: Uuid) -> Option<QuoteKey> {
+        self.find(id)
+            .filter(|key| self.quotes[key].deleted_at.is_none())
+    }
+
+    fn find_trashed(&self, id: Uuid) -> Option<QuoteKey> {
+        self.find(id)
+            .filter(|key| self.quotes[key].deleted_at.is_some())
+    }
+
+    /// Like [`State::find_live`], but only if the quote is at one of the
+    /// `expected` versions.
+    fn find_expected(&self, id: Uuid, expected: Option<&[i32]>) -> Option<QuoteKey> {
+        self.find_live(id).filter(|key| {
+            expected.is_none_or(|versions| {
+                let version = self.quotes[key]
+                    .version
+                    .expect("Stored quotes have a version");
+                versions.contains(&version)
+            })
+        })
+    }
+
+    /// Logs a change in the audit log, as Postgres does alongside it.
+    fn log(
+        &mut self,
+        audit: &Audit,
+        quote_id: Option<Uuid>,
+        before: Option<&Quote>,
+        after: Option<&Quote>,
+    ) {
+        let entry = AuditEntry {
+            id: self.audit_log.len() as i64 + 1,
+            at: now(),
+            operation: audit.operation,
+            actor: audit.actor.clone(),
+            client_ip: audit.client_ip.map(|ip| ip.to_string()),
+            quote_id,
+            before: before.and_then(|quote| quote.to_json()),
+            after: after.and_then(|quote| quote.to_json()),
+        };
+        self.audit_log.push(entry);
+    }
+
+    /// What the `quotes_link_author` trigger does in Postgres.
+    fn link_author(&mut self, author: &str) -> Uuid {
+        self.authors
+            .entry(author_key(author))
+            .or_insert_with(|| (Uuid::new_v4(), author.trim().to_owned()))
+            .0
+    }
+
+    /// An author's figures, over the quotes outside the trash.
+    fn author(&self, id: Uuid, name: &str) -> Author {
+        let created: Vec<_> = self
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none() && quote.author_id == Some(id))
+            .filter_map(|quote| quote.created_at)
+            .collect();
+
+        Author {
+            id,
+            name: name.to_owned(),
+            quote_count: created.len() as i64,
+            first_quote_at: created.iter().min().copied(),
+            last_quote_at: created.iter().max().copied(),
+        }
+    }
+
+    /// What the `quotes_archive_version` trigger does in Postgres.
+    fn archive(&mut self, quote: &Quote) {
+        let version = QuoteVersion::live(quote);
+        self.history
+            .entry(version.id)
+            .or_default()
+            .push(QuoteVersion {
+                archived_at: Some(now()),
+                ..version
+            });
+    }
+}
+
+#[async_trait]
+impl QuoteStore for MemoryQuoteStore {
+    async fn reset(&self, audit: &Audit) -> Result<(), AppError> {
+        let mut state = self.state.write().await;
+        state.quotes.clear();
+        state.history.clear();
+        state.authors.clear();
+        state.quotes_of_the_day.clear();
+        state.record(QuoteEventKind::Reset, None);
+        state.log(audit, None, None, None);
+
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        author: &str,
+        quote: &str,
+        tags: &[String],
+        audit: &Audit,
+    ) -> Result<Quote, AppError> {
+        let mut state = self.state.write().await;
+        let inserted = Quote {
+            id: Some(Uuid::new_v4()),
+            author: author.to_owned(),
+            author_id: Some(state.link_author(author)),
+            quote: quote.to_owned(),
+            version: Some(1),
+            created_at: Some(now()),
+            deleted_at: None,
+            tags: Some(tags.to_vec()),
+        };
+        state.quotes.insert(inserted.key(), inserted.clone());
+        state.record(QuoteEventKind::Created, Some(&inserted));
+        state.log(audit, inserted.id, None, Some(&inserted));
+
+        Ok(inserted)
+    }
+
+    async fn insert_many(
+        &self,
+        quotes: &[(NewQuote, Vec<String>)],
+        audit: &Audit,
+    ) -> Result<u64, AppError> {
+        // A microsecond apart each, like in Postgres, which keeps the order.
+        let now = now();
+        let mut state = self.state.write().await;
+        for (i, (new, tags)) in quotes.iter().enumerate() {
+            let inserted = Quote {
+                id: Some(Uuid::new_v4()),
+                author: new.author.clone(),
+                author_id: Some(state.link_author(&new.author)),
+                quote: new.quote.clone(),
+                version: Some(1),
+                created_at: Some(now + TimeDelta::microseconds(i as i64)),
+                deleted_at: None,
+                tags: Some(tags.clone()),
+            };
+            state.record(QuoteEventKind::Created, Some(&inserted));
+            state.log(audit, inserted.id, None, Some(&inserted));
+            state.quotes.insert(inserted.key(), inserted);
+        }
+
+        Ok(quotes.len() as u64)
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<Quote>, AppError> {
+        let state = self.state.read().await;
+        Ok(state.find_live(id).map(|key| state.quotes[&key].clone()))
+    }
+
+    async fn update(
+        &self,
+        id: Uuid,
+        author: &str,
+        quote: &str,
+        tags: Option<&[String]>,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError> {
+        let mut state = self.state.write().await;
+        let Some(key) = state.find_expected(id, expected) else {
+            return Ok(None);
+        };
+        let previous = state.quotes[&key].clone();
+        state.archive(&previous);
+        let author_id = state.link_author(author);
+
+        let existing = state.quotes.get_mut(&key).expect("Key was just found");
+        existing.author = author.to_owned();
+        existing.author_id = Some(author_id);
+        existing.quote = quote.to_owned();
+        if let Some(tags) = tags {
+            existing.tags = Some(tags.to_vec());
+        }
+        existing.version = existing.version.map(|v| v + 1);
+        let updated = existing.clone();
+        state.record(QuoteEventKind::Updated, Some(&updated));
+        state.log(audit, Some(id), Some(&previous), Some(&updated));
+
+        Ok(Some(updated))
+    }
+
+    async fn delete(
+        &self,
+        id: Uuid,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError> {
+        let mut state = self.state.write().await;
+        let Some(key) = state.find_expected(id, expected) else {
+            return Ok(None);
+        };
+        let previous = state.quotes[&key].clone();
+        state.record(QuoteEventKind::Deleted, Some(&previous));
+
+        let trashed = state.quotes.get_mut(&key).expect("Key was just found");
+        trashed.deleted_at = Some(now());
+        let trashed = trashed.clone();
+        state.log(audit, Some(id), Some(&previous), Some(&trashed));
+
+        Ok(Some(trashed))
+    }
+
+    async fn list_after(
+        &self,
+        after: Option<QuoteKey>,
+        tag: Option<&str>,
+        author: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let state = self.state.read().await;
+
+        Ok(state
+            .quotes
+            .range((start, Bound::Unbounded))
+            .map(|(_, quote)| quote)
+            .filter(|quote| quote.deleted_at.is_none())
+            .filter(|quote| tag.is_none_or(|tag| has_tag(quote, tag)))
+            .filter(|quote| author.is_none_or(|author| quote.author_id == Some(author)))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn trash_after(
+        &self,
+        after: Option<QuoteKey>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let state = self.state.read().await;
+
+        Ok(state
+            .quotes
+            .range((start, Bound::Unbounded))
+            .map(|(_, quote)| quote)
+            .filter(|quote| quote.deleted_at.is_some())
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn pick(&self, n: u32, author: Option<&str>) -> Result<Option<Quote>, AppError> {
+        let state = self.state.read().await;
+        let candidates: Vec<&Quote> = state
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none())
+            .filter(|quote| {
+                author.is_none_or(|author| author.to_lowercase() == quote.author.to_lowercase())
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(candidates[n as usize % candidates.len()].clone()))
+    }
+
+    async fn quote_of_the_day(&self, date: NaiveDate, n: u32) -> Result<Option<Quote>, AppError> {
+        let mut state = self.state.write().await;
+        let picked = state
+            .quotes_of_the_day
+            .get(&date)
+            .and_then(|&id| state.find_live(id));
+        if let Some(key) = picked {
+            return Ok(Some(state.quotes[&key].clone()));
+        }
+
+        let live: Vec<&Quote> = state
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none())
+            .collect();
+        if live.is_empty() {
+            return Ok(None);
+        }
+        let quote = live[n as usize % live.len()].clone();
+        let id = quote.id.expect("Stored quotes have an id");
+        state.quotes_of_the_day.insert(date, id);
+
+        Ok(Some(quote))
+    }
+
+    async fn count(&self) -> Result<i64, AppError> {
+        let state = self.state.read().await;
+        Ok(state
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none())
+            .count() as i64)
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, AppError> {
+        let state = self.state.read().await;
+        let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+        for tags in state
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none())
+            .filter_map(|quote| quote.tags.as_ref())
+        {
+            for tag in tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+
+        let mut counts: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(name, count)| TagCount {
+                name: name.to_owned(),
+                count,
+            })
+            .collect();
+        // Stable, so ties stay in name order.
+        counts.sort_by_key(|count| Reverse(count.count));
+
+        Ok(counts)
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>, AppError> {
+        let state = self.state.read().await;
+        let mut authors: Vec<Author> = state
+            .authors
+            .values()
+            .map(|(id, name)| state.author(*id, name))
+            .filter(|author| author.quote_count > 0)
+            .collect();
+        authors.sort_by(|a, b| a.name.cmp(&b.name));
+        // Stable, so ties stay in name order.
+        authors.sort_by_key(|author| Reverse(author.quote_count));
+
+        Ok(authors)
+    }
+
+    async fn author(&self, id: Uuid) -> Result<Option<Author>, AppError> {
+        let state = self.state.read().await;
+        Ok(state
+            .authors
+            .values()
+            .find(|(author_id, _)| *author_id == id)
+            .map(|(id, name)| state.author(*id, name)))
+    }
+
+    async fn search(
+        &self,
+        filter: &QuoteFilter,
+        after: Option<SearchKey>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, AppError> {
+        let terms = filter.text.as_deref().map(terms);
+        let state = self.state.read().await;
+
+        let mut hits: Vec<SearchHit> = state
+            .quotes
+            .values()
+            .filter(|quote| quote.deleted_at.is_none() && matches_filter(filter, quote))
+            .filter_map(|quote| {
+                let Some(terms) = &terms else {
+                    return Some(SearchHit {
+                        quote: quote.clone(),
+                        rank: None,
+                        snippet: None,
+                    });
+                };
+                Some(SearchHit {
+                    quote: quote.clone(),
+                    rank: Some(rank(terms, quote)?),
+                    snippet: Some(highlight(terms, &quote.quote)),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| search_order(&a.key(), &b.key()));
+
+        Ok(hits
+            .into_iter()
+            .filter(|hit| after.is_none_or(|after| search_order(&hit.key(), &after).is_gt()))
+            .take(limit)
+            .collect())
+    }
+
+    async fn versions(&self, id: Uuid) -> Result<Vec<QuoteVersion>, AppError> {
+        let state = self.state.read().await;
+        let mut versions = state.history.get(&id).cloned().unwrap_or_default();
+        if let Some(key) = state.find(id) {
+            versions.push(QuoteVersion::live(&state.quotes[&key]));
+        }
+
+        Ok(versions)
+    }
+
+    async fn restore(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError> {
+        let mut state = self.state.write().await;
+        let Some(key) = state.find_trashed(id) else {
+            return Ok(None);
+        };
+        let previous = state.quotes[&key].clone();
+        let restored = state.quotes.get_mut(&key).expect("Key was just found");
+        restored.deleted_at = None;
+        let restored = restored.clone();
+        state.record(QuoteEventKind::Created, Some(&restored));
+        state.log(audit, Some(id), Some(&previous), Some(&restored));
+
+        Ok(Some(restored))
+    }
+
+    async fn purge(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError> {
+        let mut state = self.state.write().await;
+        let Some(key) = state.find_trashed(id) else {
+            return Ok(None);
+        };
+        state.history.remove(&id);
+        let purged = state.quotes.remove(&key);
+        state.log(audit, Some(id), purged.as_ref(), None);
+
+        Ok(purged)
+    }
+
+    async fn audit_log(
+        &self,
+        filter: &AuditFilter,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, AppError> {
+        let state = self.state.read().await;
+
+        Ok(state
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|entry| before.is_none_or(|before| entry.id < before))
+            .filter(|entry| matches_audit_filter(filter, entry))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn subscribe(&self, after: Option<i64>) -> Result<QuoteEvents, AppError> {
+        // Reading the backlog and subscribing under one lock leaves no gap.
+        let state = self.state.read().await;
+        let backlog = match after {
+            Some(after) => state
+                .events
+                .iter()
+                .filter(|event| event.id > after)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(follow(backlog, state.subscribers.subscribe(), after))
+    }
+}
+
+/// What tells authors apart, like the `authors_name_key` index in Postgres.
+fn author_key(author: &str) -> String {
+    author.trim().to_lowercase()
+}
+
+fn has_tag(quote: &Quote, tag: &str) -> bool {
+    quote
+        .tags
+        .as_ref()
+        .is_some_and(|tags| tags.iter().any(|t| t == tag))
+}
+
+fn matches_filter(filter: &QuoteFilter, quote: &Quote) -> bool {
+    let created_at = quote
+        .created_at
+        .expect("Stored quotes have a creation time");
+    let version = quote.version.expect("Stored quotes have a version");
+
+    filter
+        .author
+        .as_ref()
+        .is_none_or(|author| author.to_lowercase() == quote.author.to_lowercase())
+        && filter.created_from.is_none_or(|from| created_at >= from)
+        && filter
+            .created_before
+            .is_none_or(|before| created_at < before)
+        && filter.min_version.is_none_or(|min| version >= min)
+}
+
+fn matches_audit_filter(filter: &AuditFilter, entry: &AuditEntry) -> bool {
+    filter
+        .actor
+        .as_ref()
+        .is_none_or(|actor| entry.actor.as_ref() == Some(actor))
+        && filter
+            .operation
+            .is_none_or(|operation| entry.operation == operation)
+        && filter.quote_id.is_none_or(|id| entry.quote_id == Some(id))
+        && filter.from.is_none_or(|from| entry.at >= from)
+        && filter.to.is_none_or(|to| entry.at < to)
+}
+
+fn search_order(a: &SearchKey, b: &SearchKey) -> Ordering {
+    let by_rank = match (a.rank, b.rank) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        _ => Ordering::Equal,
+    };
+    by_rank.then(a.key.cmp(&b.key))
+}
+
+// A rough stand-in for Postgres full-text search: there is no stemming and no
+// query syntax, just words that must each start some word of the author or
+// quote.
+
+fn terms(text: &str) -> Vec<String> {
+    words(text).map(str::to_lowercase).collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+fn matches_term(terms: &[String], word: &str) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.starts_with(term.as_str()))
+}
+
+/// `None` unless every term matches. Author matches count for more, as the
+/// author carries the higher weight in Postgres.
+fn rank(terms: &[String], quote: &Quote) -> Option<f32> {
+    if terms.is_empty() {
+        return None;
+    }
+
+    let mut rank = 0.0;
+    for term in terms {
+        let term = std::slice::from_ref(term);
+        let in_author = words(&quote.author)
+            .filter(|word| matches_term(term, word))
+            .count();
+        let in_quote = words(&quote.quote)
+            .filter(|word| matches_term(term, word))
+            .count();
+        if in_author + in_quote == 0 {
+            return None;
+        }
+        rank += in_author as f32 + 0.4 * in_quote as f32;
+    }
+
+    Some(rank)
+}
+
+fn highlight(terms: &[String], text: &str) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(char::is_alphanumeric) {
+        highlighted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        if matches_term(terms, word) {
+            highlighted.push_str("<mark>");
+            highlighted.push_str(word);
+            highlighted.push_str("</mark>");
+        } else {
+            highlighted.push_str(word);
+        }
+        rest = &rest[end..];
+    }
+    highlighted.push_str(rest);
+
+    highlighted
+}
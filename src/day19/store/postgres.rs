@@ -0,0 +1,844 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use poem_openapi::types::ToJSON as _;
+use serde_json::Value;
+use sqlx::{postgres::PgListener, PgConnection, PgPool};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, OnceCell};
+use uuid::Uuid;
+
+use super::{
+    event_channel, follow, Audit, AuditFilter, NewQuote, QuoteEvents, QuoteFilter, QuoteKey,
+    QuoteStore, SearchKey,
+};
+use crate::{
+    day19::{
+        AuditEntry, AuditOperation, Author, Quote, QuoteEvent, QuoteEventKind, QuoteVersion,
+        SearchHit, TagCount,
+    },
+    error::AppError,
+};
+
+pub struct PgQuoteStore {
+    pool: PgPool,
+    /// Fed by a task listening for `quote_events` notifications, which the
+    /// first subscriber starts.
+    events: OnceCell<broadcast::Sender<QuoteEvent>>,
+}
+
+impl PgQuoteStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            events: OnceCell::new(),
+        }
+    }
+
+    async fn events(&self) -> Result<&broadcast::Sender<QuoteEvent>, AppError> {
+        self.events
+            .get_or_try_init(|| async {
+                let mut listener = PgListener::connect_with(&self.pool).await?;
+                listener.listen("quote_events").await?;
+                let sender = event_channel();
+                tokio::spawn(forward_events(self.pool.clone(), listener, sender.clone()));
+
+                Ok(sender)
+            })
+            .await
+    }
+}
+
+/// How long an event missing between committed ones is waited for, its
+/// transaction still running, before it's taken to have rolled back.
+const EVENT_GAP_GRACE: Duration = Duration::from_secs(1);
+
+/// How often events are fetched again while one is missing.
+const EVENT_GAP_POLL: Duration = Duration::from_millis(50);
+
+/// Broadcasts the events announced on the `quote_events` channel, in order.
+/// Each notification fetches everything after the last event broadcast, which
+/// also catches up on notifications lost while the listener reconnected.
+///
+/// Ids are handed out when events are recorded, but transactions can commit
+/// in another order, so an event may show up after a later one. When one is
+/// missing, the ones after it are held back and fetched again for
+/// [`EVENT_GAP_GRACE`]; only then is it skipped.
+async fn forward_events(pool: PgPool, listener: PgListener, sender: broadcast::Sender<QuoteEvent>) {
+    let (announce, mut announcements) = mpsc::unbounded_channel();
+    tokio::spawn(receive_announcements(listener, announce));
+
+    let mut last: Option<i64> = None;
+    // The first id missing after `last`, and since when.
+    let mut missing: Option<(i64, Instant)> = None;
+    loop {
+        let announced = if missing.is_some() {
+            match tokio::time::timeout(EVENT_GAP_POLL, announcements.recv()).await {
+                Ok(Some(announced)) => Some(announced),
+                Ok(None) => return,
+                // Time to look for the missing event again.
+                Err(_) => None,
+            }
+        } else {
+            match announcements.recv().await {
+                Some(announced) => Some(announced),
+                None => return,
+            }
+        };
+        let after = match (last, announced) {
+            (Some(last), _) => last,
+            (None, Some(announced)) => announced - 1,
+            (None, None) => continue,
+        };
+        if missing.is_none() && announced.is_some_and(|announced| announced <= after) {
+            continue;
+        }
+
+        // A failed fetch is retried by the next notification.
+        let Ok(events) = events_after(&pool, after).await else {
+            continue;
+        };
+        let waiting = missing.take();
+        for event in events {
+            let expected = last.map_or(event.id, |last| last + 1);
+            if event.id > expected {
+                let since = match waiting {
+                    Some((id, since)) if id == expected => since,
+                    _ => Instant::now(),
+                };
+                if since.elapsed() < EVENT_GAP_GRACE {
+                    missing = Some((expected, since));
+                    break;
+                }
+            }
+            last = Some(event.id);
+            // Nobody may be listening, which is fine.
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Passes on the ids notified on the `quote_events` channel until the pool
+/// closes, apart from [`forward_events`] so that it can wait for them with a
+/// timeout.
+async fn receive_announcements(mut listener: PgListener, announce: mpsc::UnboundedSender<i64>) {
+    loop {
+        match listener.try_recv().await {
+            Ok(Some(notification)) => {
+                if let Ok(announced) = notification.payload().parse() {
+                    if announce.send(announced).is_err() {
+                        return;
+                    }
+                }
+            }
+            // The connection was lost; the next call reconnects.
+            Ok(None) => {}
+            // Shutting down, and waiting for this connection to be returned.
+            Err(sqlx::Error::PoolClosed) => return,
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+async fn events_after(pool: &PgPool, after: i64) -> Result<Vec<QuoteEvent>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT e.id, e.kind, q.id AS "quote_id?", q.author AS "author?", q.author_id,
+            q.quote AS "quote?", q.version AS "version?", q.created_at AS "created_at?",
+            q.deleted_at
+        FROM quote_events e
+        LEFT JOIN LATERAL jsonb_populate_record(NULL::quotes, e.quote) q ON true
+        WHERE e.id > $1
+        ORDER BY e.id"#,
+        after
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let kind = match row.kind.as_str() {
+                "created" => QuoteEventKind::Created,
+                "updated" => QuoteEventKind::Updated,
+                "deleted" => QuoteEventKind::Deleted,
+                "reset" => QuoteEventKind::Reset,
+                _ => return None,
+            };
+            let quote = row.quote_id.map(|id| Quote {
+                id: Some(id),
+                author: row.author.unwrap_or_default(),
+                author_id: row.author_id,
+                quote: row.quote.unwrap_or_default(),
+                version: row.version,
+                created_at: row.created_at,
+                deleted_at: row.deleted_at,
+                // Tags live in their own table, so the row doesn't have them.
+                tags: None,
+            });
+
+            Some(QuoteEvent {
+                id: row.id,
+                kind,
+                quote,
+            })
+        })
+        .collect())
+}
+
+/// Replaces the tags of quote `id`, creating the ones that don't exist yet.
+async fn set_tags(conn: &mut PgConnection, id: Uuid, tags: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO tags (name) SELECT unnest($1::text[]) ON CONFLICT (name) DO NOTHING",
+        tags
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!("DELETE FROM quote_tags WHERE quote_id = $1", id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO quote_tags (quote_id, tag_id) SELECT $1, id FROM tags WHERE name = ANY($2)",
+        id,
+        tags
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Locks quote `id` for a change, returning it as it was if it's in the trash,
+/// or out of it, as wanted.
+async fn lock(
+    conn: &mut PgConnection,
+    id: Uuid,
+    trashed: bool,
+) -> Result<Option<Quote>, sqlx::Error> {
+    sqlx::query_as!(
+        Quote,
+        "SELECT *, quote_tag_names(id) AS tags FROM quotes
+        WHERE id = $1 AND (deleted_at IS NOT NULL) = $2
+        FOR UPDATE",
+        id,
+        trashed
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Logs a change in the audit log, in the transaction that made it.
+async fn log(
+    conn: &mut PgConnection,
+    audit: &Audit,
+    quote_id: Option<Uuid>,
+    before: Option<&Quote>,
+    after: Option<&Quote>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO quote_audit (operation, actor, client_ip, quote_id, before, after)
+        VALUES ($1, $2, $3::text::inet, $4, $5, $6)",
+        operation_name(audit.operation),
+        audit.actor,
+        audit.client_ip.map(|ip| ip.to_string()),
+        quote_id,
+        before.and_then(|quote| quote.to_json()),
+        after.and_then(|quote| quote.to_json())
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+fn operation_name(operation: AuditOperation) -> &'static str {
+    match operation {
+        AuditOperation::Draft => "draft",
+        AuditOperation::Undo => "undo",
+        AuditOperation::Remove => "remove",
+        AuditOperation::Restore => "restore",
+        AuditOperation::Purge => "purge",
+        AuditOperation::Revert => "revert",
+        AuditOperation::Reset => "reset",
+        AuditOperation::Import => "import",
+    }
+}
+
+#[async_trait]
+impl QuoteStore for PgQuoteStore {
+    async fn reset(&self, audit: &Audit) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "TRUNCATE quotes, quote_versions, quote_tags, tags, authors, quotes_of_the_day"
+        )
+        .execute(&mut *tx)
+        .await?;
+        log(&mut tx, audit, None, None, None).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        author: &str,
+        quote: &str,
+        tags: &[String],
+        audit: &Audit,
+    ) -> Result<Quote, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = sqlx::query_as!(
+            Quote,
+            "INSERT INTO quotes (id, author, quote) VALUES ($1, $2, $3)
+            RETURNING *, quote_tag_names(id) AS tags",
+            Uuid::new_v4(),
+            author,
+            quote
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        set_tags(&mut tx, inserted.key().id, tags).await?;
+        inserted.tags = Some(tags.to_vec());
+        log(&mut tx, audit, inserted.id, None, Some(&inserted)).await?;
+        tx.commit().await?;
+
+        Ok(inserted)
+    }
+
+    async fn insert_many(
+        &self,
+        quotes: &[(NewQuote, Vec<String>)],
+        audit: &Audit,
+    ) -> Result<u64, AppError> {
+        let ids: Vec<Uuid> = quotes.iter().map(|_| Uuid::new_v4()).collect();
+        let authors: Vec<&str> = quotes.iter().map(|(q, _)| q.author.as_str()).collect();
+        let texts: Vec<&str> = quotes.iter().map(|(q, _)| q.quote.as_str()).collect();
+
+        // Every row would otherwise share the transaction's timestamp,
+        // leaving listings to order them by id.
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = sqlx::query_as!(
+            Quote,
+            "INSERT INTO quotes (id, author, quote, created_at)
+            SELECT id, author, quote, CURRENT_TIMESTAMP + (n - 1) * INTERVAL '1 microsecond'
+            FROM UNNEST($1::uuid[], $2::text[], $3::text[])
+                WITH ORDINALITY AS rows (id, author, quote, n)
+            RETURNING *, quote_tag_names(id) AS tags",
+            &ids,
+            &authors as &[&str],
+            &texts as &[&str]
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        inserted.sort_by_key(Quote::key);
+
+        // Likewise a statement for all the tags, a row per quote and tag.
+        let (tagged, names): (Vec<Uuid>, Vec<&str>) = ids
+            .iter()
+            .zip(quotes)
+            .flat_map(|(id, (_, tags))| tags.iter().map(move |tag| (*id, tag.as_str())))
+            .unzip();
+        if !names.is_empty() {
+            sqlx::query!(
+                "INSERT INTO tags (name) SELECT unnest($1::text[]) ON CONFLICT (name) DO NOTHING",
+                &names as &[&str]
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO quote_tags (quote_id, tag_id)
+                SELECT rows.quote_id, tags.id
+                FROM UNNEST($1::uuid[], $2::text[]) AS rows (quote_id, name)
+                JOIN tags ON tags.name = rows.name",
+                &tagged,
+                &names as &[&str]
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        // Sorted by creation time, they're in the order they came in.
+        for (quote, (_, tags)) in inserted.iter_mut().zip(quotes) {
+            quote.tags = Some(tags.clone());
+        }
+
+        // A statement for the lot, as imports may be large.
+        let quote_ids: Vec<Uuid> = inserted.iter().map(|quote| quote.key().id).collect();
+        let afters: Vec<Value> = inserted
+            .iter()
+            .map(|quote| quote.to_json().unwrap_or_default())
+            .collect();
+        sqlx::query!(
+            "INSERT INTO quote_audit (operation, actor, client_ip, quote_id, after)
+            SELECT $1, $2, $3::text::inet, quote_id, after
+            FROM UNNEST($4::uuid[], $5::jsonb[]) WITH ORDINALITY AS rows (quote_id, after, n)
+            ORDER BY n",
+            operation_name(audit.operation),
+            audit.actor,
+            audit.client_ip.map(|ip| ip.to_string()),
+            &quote_ids,
+            &afters
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(inserted.len() as u64)
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<Quote>, AppError> {
+        let quote = sqlx::query_as!(
+            Quote,
+            "SELECT *, quote_tag_names(id) AS tags FROM quotes
+            WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(quote)
+    }
+
+    async fn update(
+        &self,
+        id: Uuid,
+        author: &str,
+        quote: &str,
+        tags: Option<&[String]>,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = lock(&mut tx, id, false).await? else {
+            return Ok(None);
+        };
+        let updated = sqlx::query_as!(
+            Quote,
+            "UPDATE quotes SET author = $2, quote = $3, version = version + 1
+            WHERE id = $1 AND deleted_at IS NULL
+                AND ($4::int4[] IS NULL OR version = ANY($4))
+            RETURNING *, quote_tag_names(id) AS tags",
+            id,
+            author,
+            quote,
+            expected
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(mut updated) = updated else {
+            return Ok(None);
+        };
+        if let Some(tags) = tags {
+            set_tags(&mut tx, id, tags).await?;
+            updated.tags = Some(tags.to_vec());
+        }
+        log(&mut tx, audit, Some(id), Some(&before), Some(&updated)).await?;
+        tx.commit().await?;
+
+        Ok(Some(updated))
+    }
+
+    async fn delete(
+        &self,
+        id: Uuid,
+        expected: Option<&[i32]>,
+        audit: &Audit,
+    ) -> Result<Option<Quote>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = lock(&mut tx, id, false).await? else {
+            return Ok(None);
+        };
+        let deleted = sqlx::query_as!(
+            Quote,
+            "UPDATE quotes SET deleted_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted_at IS NULL
+                AND ($2::int4[] IS NULL OR version = ANY($2))
+            RETURNING *, quote_tag_names(id) AS tags",
+            id,
+            expected
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(deleted) = deleted else {
+            return Ok(None);
+        };
+        log(&mut tx, audit, Some(id), Some(&before), Some(&deleted)).await?;
+        tx.commit().await?;
+
+        Ok(Some(deleted))
+    }
+
+    async fn list_after(
+        &self,
+        after: Option<QuoteKey>,
+        tag: Option<&str>,
+        author: Option<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError> {
+        let quotes = sqlx::query_as!(
+            Quote,
+            "SELECT *, quote_tag_names(id) AS tags FROM quotes
+            WHERE deleted_at IS NULL
+                AND ($1::timestamptz IS NULL OR (created_at, id) > ($1, $2))
+                AND ($3::text IS NULL OR id IN (
+                    SELECT qt.quote_id FROM quote_tags qt JOIN tags t ON t.id = qt.tag_id
+                    WHERE t.name = $3
+                ))
+                AND ($4::uuid IS NULL OR author_id = $4)
+            ORDER BY created_at ASC, id ASC
+            LIMIT $5",
+            after.map(|key| key.created_at),
+            after.map(|key| key.id),
+            tag,
+            author,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(quotes)
+    }
+
+    async fn trash_after(
+        &self,
+        after: Option<QuoteKey>,
+        limit: usize,
+    ) -> Result<Vec<Quote>, AppError> {
+        let quotes = sqlx::query_as!(
+            Quote,
+            "SELECT *, quote_tag_names(id) AS tags FROM quotes
+            WHERE deleted_at IS NOT NULL
+                AND ($1::timestamptz IS NULL OR (created_at, id) > ($1, $2))
+            ORDER BY created_at ASC, id ASC
+            LIMIT $3",
+            after.map(|key| key.created_at),
+            after.map(|key| key.id),
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(quotes)
+    }
+
+    async fn pick(&self, n: u32, author: Option<&str>) -> Result<Option<Quote>, AppError> {
+        // Counting in the same statement keeps the offset in range. Without
+        // any quotes it's NULL, which Postgres takes as no offset.
+        let quote = sqlx::query_as!(
+            Quote,
+            "SELECT *, quote_tag_names(id) AS tags FROM quotes
+            WHERE deleted_at IS NULL AND ($2::text IS NULL OR lower(author) = lower($2))
+            ORDER BY created_at ASC, id ASC
+            OFFSET $1 % NULLIF((
+                SELECT count(*) FROM quotes
+                WHERE deleted_at IS NULL AND ($2::text IS NULL OR lower(author) = lower($2))
+            ), 0)
+            LIMIT 1",
+            i64::from(n),
+            author
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(quote)
+    }
+
+    async fn quote_of_the_day(&self, date: NaiveDate, n: u32) -> Result<Option<Quote>, AppError> {
+        let picked = sqlx::query_as!(
+            Quote,
+            "SELECT q.*, quote_tag_names(q.id) AS tags
+            FROM quotes_of_the_day d JOIN quotes q ON q.id = d.quote_id
+            WHERE d.date = $1 AND q.deleted_at IS NULL",
+            date
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if picked.is_some() {
+            return Ok(picked);
+        }
+
+        let Some(quote) = self.pick(n, None).await? else {
+            return Ok(None);
+        };
+        // Another replica may have picked meanwhile, from other quotes. Its
+        // pick wins unless it's in the trash too.
+        let id = sqlx::query_scalar!(
+            "INSERT INTO quotes_of_the_day (date, quote_id) VALUES ($1, $2)
+            ON CONFLICT (date) DO UPDATE SET quote_id = EXCLUDED.quote_id
+            WHERE NOT EXISTS (
+                SELECT FROM quotes
+                WHERE id = quotes_of_the_day.quote_id AND deleted_at IS NULL
+            )
+            RETURNING quote_id",
+            date,
+            quote.id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if id.is_some() {
+            return Ok(Some(quote));
+        }
+
+        let picked = sqlx::query_as!(
+            Quote,
+            "SELECT q.*, quote_tag_names(q.id) AS tags
+            FROM quotes_of_the_day d JOIN quotes q ON q.id = d.quote_id
+            WHERE d.date = $1",
+            date
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(picked.or(Some(quote)))
+    }
+
+    async fn count(&self) -> Result<i64, AppError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT count(*) AS "count!" FROM quotes WHERE deleted_at IS NULL"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn tag_counts(&self) -> Result<Vec<TagCount>, AppError> {
+        let counts = sqlx::query_as!(
+            TagCount,
+            r#"SELECT t.name, count(*) AS "count!"
+            FROM tags t
+                JOIN quote_tags qt ON qt.tag_id = t.id
+                JOIN quotes q ON q.id = qt.quote_id AND q.deleted_at IS NULL
+            GROUP BY t.name
+            ORDER BY 2 DESC, t.name COLLATE "C""#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>, AppError> {
+        let authors = sqlx::query_as!(
+            Author,
+            r#"SELECT a.id, a.name, count(*) AS "quote_count!",
+                min(q.created_at) AS first_quote_at, max(q.created_at) AS last_quote_at
+            FROM authors a JOIN quotes q ON q.author_id = a.id AND q.deleted_at IS NULL
+            GROUP BY a.id
+            ORDER BY 3 DESC, a.name COLLATE "C""#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(authors)
+    }
+
+    async fn author(&self, id: Uuid) -> Result<Option<Author>, AppError> {
+        let author = sqlx::query_as!(
+            Author,
+            r#"SELECT a.id, a.name, count(q.id) AS "quote_count!",
+                min(q.created_at) AS first_quote_at, max(q.created_at) AS last_quote_at
+            FROM authors a LEFT JOIN quotes q ON q.author_id = a.id AND q.deleted_at IS NULL
+            WHERE a.id = $1
+            GROUP BY a.id"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(author)
+    }
+
+    async fn search(
+        &self,
+        filter: &QuoteFilter,
+        after: Option<SearchKey>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, AppError> {
+        // Without a text query the rank is NULL throughout, which leaves plain
+        // listing order.
+        let rows = sqlx::query!(
+            r#"WITH matches AS (
+                SELECT *,
+                    ts_rank(quote_document(author, quote), websearch_to_tsquery('english', $1))
+                        AS rank
+                FROM quotes
+                WHERE deleted_at IS NULL
+                    AND ($1::text IS NULL
+                        OR quote_document(author, quote) @@ websearch_to_tsquery('english', $1))
+                    AND ($2::text IS NULL OR lower(author) = lower($2))
+                    AND ($3::timestamptz IS NULL OR created_at >= $3)
+                    AND ($4::timestamptz IS NULL OR created_at < $4)
+                    AND ($5::int4 IS NULL OR version >= $5)
+            )
+            SELECT id AS "id!", author AS "author!", author_id AS "author_id!", quote AS "quote!",
+                version AS "version!", created_at AS "created_at!",
+                quote_tag_names(id) AS tags, rank,
+                CASE WHEN $1 IS NOT NULL THEN ts_headline(
+                    'english', quote, websearch_to_tsquery('english', $1),
+                    'StartSel=<mark>, StopSel=</mark>'
+                ) END AS snippet
+            FROM matches
+            WHERE $6::timestamptz IS NULL
+                OR ($1 IS NULL AND (created_at, id) > ($6, $7))
+                OR rank < $8
+                OR (rank = $8 AND (created_at, id) > ($6, $7))
+            ORDER BY rank DESC, created_at ASC, id ASC
+            LIMIT $9"#,
+            filter.text,
+            filter.author,
+            filter.created_from,
+            filter.created_before,
+            filter.min_version,
+            after.map(|after| after.key.created_at),
+            after.map(|after| after.key.id),
+            after.and_then(|after| after.rank),
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| SearchHit {
+                quote: Quote {
+                    id: Some(row.id),
+                    author: row.author,
+                    author_id: Some(row.author_id),
+                    quote: row.quote,
+                    version: Some(row.version),
+                    created_at: Some(row.created_at),
+                    deleted_at: None,
+                    tags: row.tags,
+                },
+                rank: row.rank,
+                snippet: row.snippet,
+            })
+            .collect())
+    }
+
+    async fn versions(&self, id: Uuid) -> Result<Vec<QuoteVersion>, AppError> {
+        let versions = sqlx::query_as!(
+            QuoteVersion,
+            r#"SELECT quote_id AS "id!", version AS "version!", author AS "author!",
+                quote AS "quote!", created_at AS "created_at!", archived_at
+            FROM quote_versions WHERE quote_id = $1
+            UNION ALL
+            SELECT id, version, author, quote, created_at, deleted_at
+            FROM quotes WHERE id = $1
+            ORDER BY 2"#,
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(versions)
+    }
+
+    async fn subscribe(&self, after: Option<i64>) -> Result<QuoteEvents, AppError> {
+        let live = self.events().await?.subscribe();
+        let backlog = match after {
+            Some(after) => events_after(&self.pool, after).await?,
+            None => Vec::new(),
+        };
+
+        Ok(follow(backlog, live, after))
+    }
+
+    async fn restore(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(before) = lock(&mut tx, id, true).await? else {
+            return Ok(None);
+        };
+        let restored = sqlx::query_as!(
+            Quote,
+            "UPDATE quotes SET deleted_at = NULL WHERE id = $1
+            RETURNING *, quote_tag_names(id) AS tags",
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        log(&mut tx, audit, Some(id), Some(&before), Some(&restored)).await?;
+        tx.commit().await?;
+
+        Ok(Some(restored))
+    }
+
+    async fn purge(&self, id: Uuid, audit: &Audit) -> Result<Option<Quote>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let purged = sqlx::query_as!(
+            Quote,
+            "DELETE FROM quotes WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING *, quote_tag_names(id) AS tags",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(purged) = purged else {
+            return Ok(None);
+        };
+        // Includes the version the delete just archived.
+        sqlx::query!("DELETE FROM quote_versions WHERE quote_id = $1", id)
+            .execute(&mut *tx)
+            .await?;
+        log(&mut tx, audit, Some(id), Some(&purged), None).await?;
+        tx.commit().await?;
+
+        Ok(Some(purged))
+    }
+
+    async fn audit_log(
+        &self,
+        filter: &AuditFilter,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT id, at, operation, actor, host(client_ip) AS client_ip, quote_id,
+                before, after
+            FROM quote_audit
+            WHERE ($1::text IS NULL OR actor = $1)
+                AND ($2::text IS NULL OR operation = $2)
+                AND ($3::uuid IS NULL OR quote_id = $3)
+                AND ($4::timestamptz IS NULL OR at >= $4)
+                AND ($5::timestamptz IS NULL OR at < $5)
+                AND ($6::int8 IS NULL OR id < $6)
+            ORDER BY id DESC
+            LIMIT $7"#,
+            filter.actor,
+            filter.operation.map(operation_name),
+            filter.quote_id,
+            filter.from,
+            filter.to,
+            before,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let operation = match row.operation.as_str() {
+                    "draft" => AuditOperation::Draft,
+                    "undo" => AuditOperation::Undo,
+                    "remove" => AuditOperation::Remove,
+                    "restore" => AuditOperation::Restore,
+                    "purge" => AuditOperation::Purge,
+                    "revert" => AuditOperation::Revert,
+                    "reset" => AuditOperation::Reset,
+                    "import" => AuditOperation::Import,
+                    _ => return None,
+                };
+
+                Some(AuditEntry {
+                    id: row.id,
+                    at: row.at,
+                    operation,
+                    actor: row.actor,
+                    client_ip: row.client_ip,
+                    quote_id: row.quote_id,
+                    before: row.before,
+                    after: row.after,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,118 @@
+//! Checks on quotes sent by clients. Text is stored trimmed and in Unicode
+//! normalization form C, so that quotes that look the same compare the same.
+
+use unicode_normalization::UnicodeNormalization as _;
+
+use super::{store::NewQuote, QuoteInput};
+use crate::error::{AppError, FieldError};
+
+/// Longest author name, in characters.
+const MAX_AUTHOR_CHARS: usize = 200;
+/// Longest quote, in characters.
+const MAX_QUOTE_CHARS: usize = 2000;
+const MAX_TAGS: usize = 20;
+/// Longest tag, in characters.
+const MAX_TAG_CHARS: usize = 50;
+
+impl QuoteInput {
+    /// The quote to store, and its tags if any were sent, or everything wrong
+    /// with the input.
+    pub(super) fn validate(&self) -> Result<(NewQuote, Option<Vec<String>>), AppError> {
+        let mut errors = Vec::new();
+        for (field, value) in [
+            ("id", self.id.is_some()),
+            ("version", self.version.is_some()),
+            ("created_at", self.created_at.is_some()),
+            ("deleted_at", self.deleted_at.is_some()),
+        ] {
+            if value {
+                errors.push(FieldError::new(field, "is assigned by the server"));
+            }
+        }
+
+        let quote = new_quote(self.author.as_deref(), self.quote.as_deref());
+        let tags = tags(self.tags.as_deref());
+        match (quote, tags) {
+            (Ok(quote), Ok(tags)) if errors.is_empty() => Ok((quote, tags)),
+            (quote, tags) => {
+                errors.extend(quote.err().into_iter().flatten());
+                errors.extend(tags.err().into_iter().flatten());
+                Err(AppError::Invalid(errors))
+            }
+        }
+    }
+}
+
+/// The author and text of a quote, required, trimmed and normalized.
+pub(super) fn new_quote(
+    author: Option<&str>,
+    quote: Option<&str>,
+) -> Result<NewQuote, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let author = text(&mut errors, "author", author, MAX_AUTHOR_CHARS);
+    let quote = text(&mut errors, "quote", quote, MAX_QUOTE_CHARS);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(NewQuote { author, quote })
+}
+
+fn text(errors: &mut Vec<FieldError>, field: &str, value: Option<&str>, max: usize) -> String {
+    let Some(value) = value else {
+        errors.push(FieldError::new(field, "is required"));
+        return String::new();
+    };
+
+    let value: String = value.trim().nfc().collect();
+    if value.is_empty() {
+        errors.push(FieldError::new(field, "must not be blank"));
+    } else if value.chars().count() > max {
+        errors.push(FieldError::new(
+            field,
+            format!("must be at most {max} characters long"),
+        ));
+    }
+
+    value
+}
+
+/// Tags are matched ignoring case and surrounding whitespace, so they are
+/// stored without either, sorted and without duplicates.
+pub(super) fn tags(tags: Option<&[String]>) -> Result<Option<Vec<String>>, Vec<FieldError>> {
+    let Some(tags) = tags else {
+        return Ok(None);
+    };
+
+    let mut errors = Vec::new();
+    if tags.len() > MAX_TAGS {
+        errors.push(FieldError::new(
+            "tags",
+            format!("must hold at most {MAX_TAGS} tags"),
+        ));
+    }
+    let mut normalized = Vec::with_capacity(tags.len());
+    for (i, tag) in tags.iter().enumerate() {
+        match self::tag(tag) {
+            None => errors.push(FieldError::new(format!("tags[{i}]"), "must not be blank")),
+            Some(tag) if tag.chars().count() > MAX_TAG_CHARS => errors.push(FieldError::new(
+                format!("tags[{i}]"),
+                format!("must be at most {MAX_TAG_CHARS} characters long"),
+            )),
+            Some(tag) => normalized.push(tag),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    normalized.sort();
+    normalized.dedup();
+
+    Ok(Some(normalized))
+}
+
+/// A tag as stored, or `None` if it's blank.
+pub(super) fn tag(tag: &str) -> Option<String> {
+    let tag: String = tag.trim().nfc().collect::<String>().to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
@@ -1,101 +1,87 @@
 use std::net::{Ipv4Addr, Ipv6Addr};
 
-use poem::{get, handler, web::Query, Route};
-use serde::Deserialize;
-
-pub fn route() -> Route {
-    Route::new()
-        .at("/dest", get(encrypt_address))
-        .at("/key", get(get_address_key))
-        .at("/v6/dest", get(encrypt_address_ipv6))
-        .at("/v6/key", get(get_address_key_ipv6))
-}
-
-#[derive(Deserialize)]
-struct EncryptParams {
-    from: Ipv4Addr,
-    key: Ipv4Addr,
-}
-
-#[derive(Deserialize)]
-struct KeyParams {
-    from: Ipv4Addr,
-    to: Ipv4Addr,
-}
-
-#[derive(Deserialize)]
-struct EncryptParamsV6 {
-    from: Ipv6Addr,
-    key: Ipv6Addr,
-}
-
-#[derive(Deserialize)]
-struct KeyParamsV6 {
-    from: Ipv6Addr,
-    to: Ipv6Addr,
-}
-
-#[handler]
-fn encrypt_address(params: Query<EncryptParams>) -> String {
-    let Query(EncryptParams { from, key }) = params;
-
-    let added: Vec<u8> = from
-        .octets()
-        .into_iter()
-        .zip(key.octets().into_iter())
-        .map(|(a, b)| a.wrapping_add(b))
-        .collect();
-    let dest = Ipv4Addr::new(added[0], added[1], added[2], added[3]);
-
-    dest.to_string()
-}
-
-#[handler]
-fn get_address_key(params: Query<KeyParams>) -> String {
-    let Query(KeyParams { from, to }) = params;
-
-    let diffed: Vec<u8> = from
-        .octets()
-        .into_iter()
-        .zip(to.octets().into_iter())
-        .map(|(a, b)| b.wrapping_sub(a))
-        .collect();
-    let key = Ipv4Addr::new(diffed[0], diffed[1], diffed[2], diffed[3]);
-
-    key.to_string()
-}
-
-#[handler]
-fn encrypt_address_ipv6(params: Query<EncryptParamsV6>) -> String {
-    let Query(EncryptParamsV6 { from, key }) = params;
-
-    let xored: Vec<u8> = from
-        .octets()
-        .into_iter()
-        .zip(key.octets().into_iter())
-        .map(|(a, b)| a ^ b)
-        .collect();
-
-    let mut octets: [u8; 16] = xored.try_into().unwrap();
-
-    let to = Ipv6Addr::from(octets);
-
-    to.to_string()
-}
-
-#[handler]
-fn get_address_key_ipv6(params: Query<KeyParamsV6>) -> String {
-    let Query(KeyParamsV6 { from, to }) = params;
-
-    let xored: Vec<u8> = from
-        .octets()
-        .into_iter()
-        .zip(to.octets().into_iter())
-        .map(|(a, b)| a ^ b)
-        .collect();
-
-    let mut octets: [u8; 16] = xored.try_into().unwrap();
-    let key = Ipv6Addr::from(octets);
-
-    key.to_string()
+use poem_openapi::{param::Query, payload::PlainText, OpenApi};
+
+use crate::ApiTags;
+
+pub struct Api;
+
+#[OpenApi(prefix_path = "/2", tag = "ApiTags::Day2")]
+impl Api {
+    /// Encrypt an IPv4 address by adding the key to it octet by octet.
+    #[oai(path = "/dest", method = "get")]
+    async fn encrypt_address(
+        &self,
+        from: Query<Ipv4Addr>,
+        key: Query<Ipv4Addr>,
+    ) -> PlainText<String> {
+        let added: Vec<u8> = from
+            .octets()
+            .into_iter()
+            .zip(key.octets())
+            .map(|(a, b)| a.wrapping_add(b))
+            .collect();
+        let dest = Ipv4Addr::new(added[0], added[1], added[2], added[3]);
+
+        PlainText(dest.to_string())
+    }
+
+    /// Recover the key that encrypts `from` into `to`.
+    #[oai(path = "/key", method = "get")]
+    async fn get_address_key(
+        &self,
+        from: Query<Ipv4Addr>,
+        to: Query<Ipv4Addr>,
+    ) -> PlainText<String> {
+        let diffed: Vec<u8> = from
+            .octets()
+            .into_iter()
+            .zip(to.octets())
+            .map(|(a, b)| b.wrapping_sub(a))
+            .collect();
+        let key = Ipv4Addr::new(diffed[0], diffed[1], diffed[2], diffed[3]);
+
+        PlainText(key.to_string())
+    }
+
+    /// Encrypt an IPv6 address by XORing the key into it.
+    #[oai(path = "/v6/dest", method = "get")]
+    async fn encrypt_address_ipv6(
+        &self,
+        from: Query<Ipv6Addr>,
+        key: Query<Ipv6Addr>,
+    ) -> PlainText<String> {
+        let xored: Vec<u8> = from
+            .octets()
+            .into_iter()
+            .zip(key.octets())
+            .map(|(a, b)| a ^ b)
+            .collect();
+
+        let octets: [u8; 16] = xored.try_into().expect("IPv6 addresses have 16 octets");
+
+        let to = Ipv6Addr::from(octets);
+
+        PlainText(to.to_string())
+    }
+
+    /// Recover the key that encrypts `from` into `to`.
+    #[oai(path = "/v6/key", method = "get")]
+    async fn get_address_key_ipv6(
+        &self,
+        from: Query<Ipv6Addr>,
+        to: Query<Ipv6Addr>,
+    ) -> PlainText<String> {
+        let xored: Vec<u8> = from
+            .octets()
+            .into_iter()
+            .zip(to.octets())
+            .map(|(a, b)| a ^ b)
+            .collect();
+
+        let octets: [u8; 16] = xored.try_into().expect("IPv6 addresses have 16 octets");
+        let key = Ipv6Addr::from(octets);
+
+        PlainText(key.to_string())
+    }
 }
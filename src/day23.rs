@@ -1,109 +1,133 @@
-use http::StatusCode;
-use poem::{
-    web::{Html, Multipart, Path},
-    *,
-};
+use poem_openapi::{param::Path, payload::Html, types::multipart::Upload, Multipart, OpenApi};
 use toml::Table;
 
-pub fn route() -> Route {
-    Route::new()
-        .at("/star", get(light_star))
-        .at("/present/:color", get(cycle_present_color))
-        .at("/ornament/:state/:n", get(ornament_iteration))
-        .at("/lockfile", post(bake_a_cake))
-}
+use crate::{error::AppError, ApiTags};
 
-#[handler]
-pub async fn html() -> impl IntoResponse {
-    Html(include_str!("../23.html"))
-}
+pub struct Api;
 
-#[handler]
-async fn light_star() -> impl IntoResponse {
-    r#"<div id="star" class="lit"></div>"#
-}
+/// The page driving the day 23 endpoints.
+pub struct Assets;
 
-#[handler]
-async fn cycle_present_color(color: Path<String>) -> Result<impl IntoResponse> {
-    let next = match (*color).as_str() {
-        "red" => "blue",
-        "blue" => "purple",
-        "purple" => "red",
-        _ => return Err(StatusCode::IM_A_TEAPOT.into()),
-    };
-    Ok(format!(
-        r#"
-          <div class="present {}" hx-get="/23/present/{}" hx-swap="outerHTML">
-              <div class="ribbon"></div>
-              <div class="ribbon"></div>
-              <div class="ribbon"></div>
-              <div class="ribbon"></div>
-          </div>
-        "#,
-        *color, next
-    ))
+#[derive(Multipart)]
+struct LockfileForm {
+    /// A `Cargo.lock` file.
+    lockfile: Upload,
 }
 
-#[handler]
-async fn ornament_iteration(Path((state, n)): Path<(String, String)>) -> Result<impl IntoResponse> {
-    let next_state = match state.as_str() {
-        "on" => "off",
-        "off" => "on",
-        _ => return Err(StatusCode::IM_A_TEAPOT.into()),
-    };
-    let mut buf = String::new();
-    let escaped = html_escape::encode_double_quoted_attribute_to_string(n, &mut buf);
-
-    Ok(format!(
-        r#"<div class="ornament{}" id="ornament{}" hx-trigger="load delay:2s once" hx-get="/23/ornament/{}/{}" hx-swap="outerHTML"></div>"#,
-        if state == "on" { " on" } else { "" },
-        escaped,
-        next_state,
-        escaped,
-    ))
+#[OpenApi(tag = "ApiTags::Day23")]
+impl Assets {
+    /// The htmx Christmas tree page.
+    #[oai(path = "/assets/23.html", method = "get")]
+    async fn html(&self) -> Html<&'static str> {
+        Html(include_str!("../23.html"))
+    }
 }
 
-#[handler]
-async fn bake_a_cake(mut form: Multipart) -> Result<impl IntoResponse> {
-    let field = form.next_field().await?.ok_or(StatusCode::BAD_REQUEST)?;
+#[OpenApi(prefix_path = "/23", tag = "ApiTags::Day23")]
+impl Api {
+    /// Light up the star.
+    #[oai(path = "/star", method = "get")]
+    async fn light_star(&self) -> Html<&'static str> {
+        Html(r#"<div id="star" class="lit"></div>"#)
+    }
+
+    /// Cycle a present to the next color.
+    #[oai(path = "/present/:color", method = "get")]
+    async fn cycle_present_color(&self, color: Path<String>) -> Result<Html<String>, AppError> {
+        let next = match (*color).as_str() {
+            "red" => "blue",
+            "blue" => "purple",
+            "purple" => "red",
+            _ => {
+                return Err(AppError::Teapot(format!(
+                    "{} is not a present color",
+                    *color
+                )))
+            }
+        };
+        Ok(Html(format!(
+            r#"
+              <div class="present {}" hx-get="/23/present/{}" hx-swap="outerHTML">
+                  <div class="ribbon"></div>
+                  <div class="ribbon"></div>
+                  <div class="ribbon"></div>
+                  <div class="ribbon"></div>
+              </div>
+            "#,
+            *color, next
+        )))
+    }
 
-    let lockfile: Table =
-        toml::from_str(&field.text().await?).map_err(|_| StatusCode::BAD_REQUEST)?;
-    let checksums: Vec<&str> = lockfile
-        .get("package")
-        .unwrap()
-        .as_array()
-        .unwrap()
-        .into_iter()
-        .filter_map(|p| p.get("checksum").and_then(|c| c.as_str()))
-        .collect();
+    /// Toggle an ornament, scheduling the next toggle.
+    #[oai(path = "/ornament/:state/:n", method = "get")]
+    async fn ornament_iteration(
+        &self,
+        state: Path<String>,
+        n: Path<String>,
+    ) -> Result<Html<String>, AppError> {
+        let next_state = match state.as_str() {
+            "on" => "off",
+            "off" => "on",
+            _ => {
+                return Err(AppError::Teapot(format!(
+                    "{} is not an ornament state",
+                    *state
+                )))
+            }
+        };
+        let mut buf = String::new();
+        let escaped = html_escape::encode_double_quoted_attribute_to_string(&*n, &mut buf);
 
-    if checksums.is_empty() {
-        return Err(StatusCode::BAD_REQUEST.into());
+        Ok(Html(format!(
+            r#"<div class="ornament{}" id="ornament{}" hx-trigger="load delay:2s once" hx-get="/23/ornament/{}/{}" hx-swap="outerHTML"></div>"#,
+            if *state == "on" { " on" } else { "" },
+            escaped,
+            next_state,
+            escaped,
+        )))
     }
 
-    checksums
-        .into_iter()
-        .map(|checksum| {
-            if checksum.len() < 10
-                || checksum.chars().any(|c| {
-                    let c = c.to_ascii_lowercase();
+    /// Render a sprinkle for every package checksum in a lockfile.
+    #[oai(path = "/lockfile", method = "post")]
+    async fn bake_a_cake(&self, form: LockfileForm) -> Result<Html<String>, AppError> {
+        let text = form
+            .lockfile
+            .into_string()
+            .await
+            .map_err(|err| AppError::BadRequest(err.to_string()))?;
 
-                    // ASCII wizardry
-                    c < '0' || c > 'f' || (c > '9' && c < 'a')
-                })
-            {
-                return Err(StatusCode::UNPROCESSABLE_ENTITY.into());
-            }
+        let lockfile: Table =
+            toml::from_str(&text).map_err(|err| AppError::BadRequest(err.to_string()))?;
+        let checksums: Vec<&str> = lockfile
+            .get("package")
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| AppError::BadRequest("Lockfile has no packages".to_owned()))?
+            .iter()
+            .filter_map(|p| p.get("checksum").and_then(|c| c.as_str()))
+            .collect();
 
-            let color = &checksum[0..6];
-            let top = i32::from_str_radix(&checksum[6..8], 16).unwrap();
-            let left = i32::from_str_radix(&checksum[8..10], 16).unwrap();
+        if checksums.is_empty() {
+            return Err(AppError::BadRequest("Lockfile has no checksums".to_owned()));
+        }
 
-            Ok(format!(
-                r#"<div style="background-color:#{color};top:{top}px;left:{left}px;"></div>"#
-            ))
-        })
-        .collect::<Result<Vec<String>>>()
-        .map(|v| v.join("\n"))
+        checksums
+            .into_iter()
+            .map(|checksum| {
+                if checksum.len() < 10 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(AppError::Unprocessable(format!(
+                        "{checksum} is not a valid checksum"
+                    )));
+                }
+
+                let color = &checksum[0..6];
+                let top = i32::from_str_radix(&checksum[6..8], 16).unwrap();
+                let left = i32::from_str_radix(&checksum[8..10], 16).unwrap();
+
+                Ok(format!(
+                    r#"<div style="background-color:#{color};top:{top}px;left:{left}px;"></div>"#
+                ))
+            })
+            .collect::<Result<Vec<String>, AppError>>()
+            .map(|v| Html(v.join("\n")))
+    }
 }
@@ -1,78 +1,93 @@
 use cargo_manifest::Manifest;
 use itertools::Itertools;
-use poem::{
-    handler,
-    http::{HeaderMap, StatusCode},
-    post, Response, Route,
-};
+use poem_openapi::{payload::PlainText, ApiRequest, ApiResponse, OpenApi};
 use std::str::FromStr as _;
 
-pub fn route() -> Route {
-    Route::new().at("/manifest", post(order_manifests))
+use crate::{error::AppError, ApiTags};
+
+pub struct Api;
+
+/// A Cargo manifest, in any of the formats Santa's elves use.
+#[derive(ApiRequest)]
+enum ManifestPayload {
+    #[oai(content_type = "application/toml")]
+    Toml(PlainText<String>),
+    #[oai(content_type = "application/json")]
+    Json(PlainText<String>),
+    #[oai(content_type = "application/yaml")]
+    Yaml(PlainText<String>),
+}
+
+#[derive(ApiResponse)]
+enum ManifestResponse {
+    /// The orders in the manifest, one `item: quantity` per line.
+    #[oai(status = 200)]
+    Orders(PlainText<String>),
+    /// The manifest contains no valid orders.
+    #[oai(status = 204)]
+    NoOrders,
 }
 
-#[handler]
-fn order_manifests(headers: &HeaderMap, body: String) -> Response {
-    // This is a horrendous shortcut, but hey, it works, and if it ain't broke,
-    // don't fix it ¯\_(ツ)_/¯.
-    let Some(data) = (match headers.get("Content-Type").and_then(|v| v.to_str().ok()) {
-        Some("application/toml") => Some(body),
-        Some("application/json") => serde_json::from_str(&body)
-            .ok()
-            .and_then(|v: serde_json::Value| toml::ser::to_string(&v).ok()),
-        Some("application/yaml") => serde_yaml::from_str(&body)
-            .ok()
-            .and_then(|v: serde_json::Value| toml::ser::to_string(&v).ok()),
-        _ => None,
-    }) else {
-        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into();
-    };
+#[OpenApi(prefix_path = "/5", tag = "ApiTags::Day5")]
+impl Api {
+    /// List the orders found in a manifest's `package.metadata`.
+    #[oai(path = "/manifest", method = "post")]
+    async fn order_manifests(
+        &self,
+        manifest: ManifestPayload,
+    ) -> Result<ManifestResponse, AppError> {
+        // This is a horrendous shortcut, but hey, it works, and if it ain't broke,
+        // don't fix it ¯\_(ツ)_/¯.
+        let data = match manifest {
+            ManifestPayload::Toml(PlainText(body)) => Some(body),
+            ManifestPayload::Json(PlainText(body)) => {
+                let value: serde_json::Value = serde_json::from_str(&body)
+                    .map_err(|err| AppError::BadJson(err.to_string()))?;
+                toml::ser::to_string(&value).ok()
+            }
+            ManifestPayload::Yaml(PlainText(body)) => serde_yaml::from_str(&body)
+                .ok()
+                .and_then(|v: serde_json::Value| toml::ser::to_string(&v).ok()),
+        };
 
-    let Ok(manifest) = Manifest::from_str(&data) else {
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid manifest");
-    };
+        let Some(manifest) = data.and_then(|data| Manifest::from_str(&data).ok()) else {
+            return Err(AppError::BadRequest("Invalid manifest".to_owned()));
+        };
 
-    let Some(package) = manifest.package else {
-        return StatusCode::NO_CONTENT.into();
-    };
-    if package
-        .keywords
-        .filter(|k| {
+        let Some(package) = manifest.package else {
+            return Ok(ManifestResponse::NoOrders);
+        };
+        if !package.keywords.is_some_and(|k| {
             k.as_ref()
                 .as_local()
-                .unwrap()
-                .contains(&"Christmas 2024".to_owned())
-        })
-        .is_none()
-    {
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Magic keyword not provided");
-    };
+                .is_some_and(|k| k.contains(&"Christmas 2024".to_owned()))
+        }) {
+            return Err(AppError::BadRequest(
+                "Magic keyword not provided".to_owned(),
+            ));
+        };
 
-    let Some(orders) = package
-        .metadata
-        .and_then(|m| Some(m.get("orders")?.as_array()?.to_owned()))
-    else {
-        return StatusCode::NO_CONTENT.into();
-    };
+        let Some(orders) = package
+            .metadata
+            .and_then(|m| Some(m.get("orders")?.as_array()?.to_owned()))
+        else {
+            return Ok(ManifestResponse::NoOrders);
+        };
 
-    let items: String = orders
-        .into_iter()
-        .filter_map(|map| {
-            let item = map.get("item")?.as_str()?;
-            let quantity = map.get("quantity")?.as_integer()?;
+        let items: String = orders
+            .into_iter()
+            .filter_map(|map| {
+                let item = map.get("item")?.as_str()?;
+                let quantity = map.get("quantity")?.as_integer()?;
 
-            Some(format!("{}: {}", item, quantity))
-        })
-        .intersperse("\n".to_owned())
-        .collect();
+                Some(format!("{}: {}", item, quantity))
+            })
+            .join("\n");
 
-    if items.is_empty() {
-        return StatusCode::NO_CONTENT.into();
-    }
+        if items.is_empty() {
+            return Ok(ManifestResponse::NoOrders);
+        }
 
-    items.into()
+        Ok(ManifestResponse::Orders(PlainText(items)))
+    }
 }
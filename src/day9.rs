@@ -1,84 +1,232 @@
-use poem::{
-    handler,
-    http::{HeaderMap, StatusCode},
-    post,
-    web::Data,
-    EndpointExt as _, IntoEndpoint, Response, Route,
+use poem::{Request, RequestBody};
+use poem_openapi::{
+    payload::{Json, PlainText},
+    registry::{MetaMediaType, MetaRequest, Registry},
+    types::Type,
+    ApiExtractor, ApiExtractorType, ApiResponse, ExtractParamOptions, Object, OpenApi,
+    ResponseContent, Union,
+};
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    IntGauge,
 };
 use serde::{Deserialize, Serialize};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::Mutex;
-
-pub fn route() -> impl IntoEndpoint {
-    Route::new()
-        .at("/milk", post(leaky_milk))
-        .at("/refill", post(fill_milk_bucket))
-        .data(MilkBucket(Arc::new(Mutex::new(
-            leaky_bucket::RateLimiter::builder()
-                .initial(5)
-                .max(5)
-                .interval(Duration::from_secs(1))
-                .build(),
-        ))))
+use std::sync::{Arc, RwLock};
+
+use crate::{config::MilkConfig, error::AppError, metrics::Metrics, ApiTags};
+
+pub struct Api {
+    config: MilkConfig,
+    bucket: MilkBucket,
 }
 
+impl Api {
+    pub fn new(config: &MilkConfig, metrics: &Metrics) -> Self {
+        let bucket = MilkBucket(Arc::new(RwLock::new(full_bucket(config))));
+        metrics.register(MilkGauge::new(bucket.clone()));
+
+        Self {
+            config: config.clone(),
+            bucket,
+        }
+    }
+}
+
+fn full_bucket(config: &MilkConfig) -> leaky_bucket::RateLimiter {
+    leaky_bucket::RateLimiter::builder()
+        .initial(config.initial)
+        .max(config.max)
+        .interval(config.refill_interval())
+        .build()
+}
+
+// Nothing is awaited while the limiter is borrowed, so a plain lock does, and
+// lets the gauge below read it synchronously.
 #[derive(Clone)]
-struct MilkBucket(Arc<Mutex<leaky_bucket::RateLimiter>>);
+struct MilkBucket(Arc<RwLock<leaky_bucket::RateLimiter>>);
+
+/// Reports the liters left in the bucket whenever metrics are scraped, since
+/// the bucket refills on its own.
+struct MilkGauge {
+    bucket: MilkBucket,
+    gauge: IntGauge,
+}
+
+impl MilkGauge {
+    fn new(bucket: MilkBucket) -> Self {
+        Self {
+            bucket,
+            gauge: IntGauge::new("milk_tokens", "Liters of milk left in the bucket")
+                .expect("Metric is valid"),
+        }
+    }
+}
+
+impl Collector for MilkGauge {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let balance = self
+            .bucket
+            .0
+            .read()
+            .expect("Milk bucket lock poisoned")
+            .balance();
+        self.gauge.set(balance as i64);
+        self.gauge.collect()
+    }
+}
+
+#[derive(Object, Serialize, Deserialize)]
+#[oai(deny_unknown_fields)]
+#[serde(deny_unknown_fields)]
+struct Liters {
+    liters: f32,
+}
+
+#[derive(Object, Serialize, Deserialize)]
+#[oai(deny_unknown_fields)]
+#[serde(deny_unknown_fields)]
+struct Gallons {
+    gallons: f32,
+}
+
+#[derive(Object, Serialize, Deserialize)]
+#[oai(deny_unknown_fields)]
+#[serde(deny_unknown_fields)]
+struct Litres {
+    litres: f32,
+}
 
-#[derive(Serialize, Deserialize)]
-#[serde(untagged, deny_unknown_fields)]
+#[derive(Object, Serialize, Deserialize)]
+#[oai(deny_unknown_fields)]
+#[serde(deny_unknown_fields)]
+struct Pints {
+    pints: f32,
+}
+
+/// An amount of milk in one unit, to be converted to its counterpart.
+#[derive(Union, Serialize, Deserialize)]
+#[oai(one_of)]
+#[serde(untagged)]
 enum MilkConversion {
-    Liters { liters: f32 },
-    Gallons { gallons: f32 },
-    IHateTheBritish { litres: f32 },
-    IHateTheBritishMore { pints: f32 },
-}
-
-#[handler]
-async fn leaky_milk(headers: &HeaderMap, bucket: Data<&MilkBucket>, body: String) -> Response {
-    if !bucket.0 .0.lock().await.try_acquire(1) {
-        return Response::builder()
-            .status(StatusCode::TOO_MANY_REQUESTS)
-            .body("No milk available\n");
+    Liters(Liters),
+    Gallons(Gallons),
+    IHateTheBritish(Litres),
+    IHateTheBritishMore(Pints),
+}
+
+/// Only JSON bodies ask for a conversion, anything else just withdraws milk.
+/// The body is kept raw so that an empty bucket is reported before a
+/// malformed conversion.
+enum MilkRequest {
+    Conversion(String),
+    Withdrawal,
+}
+
+impl<'a> ApiExtractor<'a> for MilkRequest {
+    const TYPES: &'static [ApiExtractorType] = &[ApiExtractorType::RequestObject];
+
+    type ParamType = ();
+    type ParamRawType = ();
+
+    fn register(registry: &mut Registry) {
+        MilkConversion::register(registry);
+    }
+
+    fn request_meta() -> Option<MetaRequest> {
+        Some(MetaRequest {
+            description: Some("An amount of milk to convert; omit to just withdraw milk."),
+            content: vec![MetaMediaType {
+                content_type: "application/json",
+                schema: MilkConversion::schema_ref(),
+            }],
+            required: false,
+        })
     }
 
-    if headers
-        .get("Content-Type")
-        .map_or(false, |v| v == "application/json")
-    {
-        let Ok(conversion_request): Result<MilkConversion, _> = serde_json::from_str(&body) else {
-            return StatusCode::BAD_REQUEST.into();
+    async fn from_request(
+        request: &'a Request,
+        body: &mut RequestBody,
+        _param_opts: ExtractParamOptions<Self::ParamType>,
+    ) -> poem::Result<Self> {
+        if request.content_type() != Some("application/json") {
+            return Ok(MilkRequest::Withdrawal);
+        }
+
+        let body = body.take()?.into_string().await?;
+        Ok(MilkRequest::Conversion(body))
+    }
+}
+
+#[derive(ResponseContent)]
+enum MilkBody {
+    #[oai(actual_type = "Json<MilkConversion>")]
+    Converted(poem::web::Json<MilkConversion>),
+    Withdrawn(PlainText<&'static str>),
+}
+
+#[derive(ApiResponse)]
+enum MilkResponse {
+    /// Either the converted amount, or a note that milk was withdrawn.
+    #[oai(status = 200)]
+    Milk(MilkBody),
+}
+
+#[OpenApi(prefix_path = "/9", tag = "ApiTags::Day9")]
+impl Api {
+    /// Withdraw milk from the rate-limited bucket, optionally converting units.
+    #[oai(path = "/milk", method = "post")]
+    async fn leaky_milk(&self, request: MilkRequest) -> Result<MilkResponse, AppError> {
+        if !self
+            .bucket
+            .0
+            .read()
+            .expect("Milk bucket lock poisoned")
+            .try_acquire(1)
+        {
+            return Err(AppError::TooManyRequests("No milk available".to_owned()));
+        }
+
+        let MilkRequest::Conversion(body) = request else {
+            return Ok(MilkResponse::Milk(MilkBody::Withdrawn(PlainText(
+                "Milk withdrawn\n",
+            ))));
         };
 
+        let conversion_request: MilkConversion =
+            serde_json::from_str(&body).map_err(|err| AppError::BadJson(err.to_string()))?;
+
         let response = match conversion_request {
-            MilkConversion::Liters { liters } => {
-                let gallons = liters * 0.2641720524;
-                MilkConversion::Gallons { gallons }
+            MilkConversion::Liters(Liters { liters }) => {
+                let gallons = liters * 0.264_172_05;
+                MilkConversion::Gallons(Gallons { gallons })
             }
-            MilkConversion::Gallons { gallons } => {
-                let liters = gallons * 3.785411784;
-                MilkConversion::Liters { liters }
+            MilkConversion::Gallons(Gallons { gallons }) => {
+                let liters = gallons * 3.785_411_8;
+                MilkConversion::Liters(Liters { liters })
             }
-            MilkConversion::IHateTheBritish { litres } => {
+            MilkConversion::IHateTheBritish(Litres { litres }) => {
                 let pints = litres * 1.75975;
-                MilkConversion::IHateTheBritishMore { pints }
+                MilkConversion::IHateTheBritishMore(Pints { pints })
             }
-            MilkConversion::IHateTheBritishMore { pints } => {
+            MilkConversion::IHateTheBritishMore(Pints { pints }) => {
                 let litres = pints * 0.56826;
-                MilkConversion::IHateTheBritish { litres }
+                MilkConversion::IHateTheBritish(Litres { litres })
             }
         };
-        return serde_json::to_string(&response).unwrap().into();
-    }
 
-    "Milk withdrawn\n".into()
-}
+        Ok(MilkResponse::Milk(MilkBody::Converted(poem::web::Json(
+            response,
+        ))))
+    }
 
-#[handler]
-async fn fill_milk_bucket(bucket: Data<&MilkBucket>) {
-    *bucket.0 .0.lock().await = leaky_bucket::RateLimiter::builder()
-        .initial(5)
-        .max(5)
-        .interval(Duration::from_secs(1))
-        .build();
+    /// Refill the milk bucket to its full capacity.
+    #[oai(path = "/refill", method = "post")]
+    async fn fill_milk_bucket(&self) {
+        *self.bucket.0.write().expect("Milk bucket lock poisoned") = full_bucket(&self.config);
+    }
 }
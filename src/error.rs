@@ -0,0 +1,269 @@
+//! Crate-wide error type. Every handler returns [`AppError`], which renders as
+//! an RFC 7807 `application/problem+json` body carrying a stable `code` that
+//! clients can match on.
+
+use poem::{
+    error::ResponseError,
+    http::{header, StatusCode},
+    Response,
+};
+use poem_openapi::{
+    error::ParseRequestPayloadError,
+    registry::{MetaMediaType, MetaResponse, MetaResponses, Registry},
+    types::{ToJSON as _, Type},
+    ApiResponse, Object,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    BadJson(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    NotAcceptable(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    Unprocessable(String),
+    /// The body parsed, but some of its fields don't hold acceptable values.
+    #[error("The request has invalid fields")]
+    Invalid(Vec<FieldError>),
+    #[error("{0}")]
+    TooManyRequests(String),
+    #[error("{0}")]
+    Teapot(String),
+    #[error("The database could not be reached")]
+    DatabaseUnavailable(#[source] sqlx::Error),
+    #[error("An unexpected error occurred")]
+    Internal(String),
+}
+
+impl AppError {
+    /// Stable, machine-readable identifier for this kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadJson(_) => "bad_json",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::NotFound(_) => "not_found",
+            AppError::NotAcceptable(_) => "not_acceptable",
+            AppError::Conflict(_) => "conflict",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::Unprocessable(_) => "unprocessable_entity",
+            AppError::Invalid(_) => "validation_failed",
+            AppError::TooManyRequests(_) => "too_many_requests",
+            AppError::Teapot(_) => "teapot",
+            AppError::DatabaseUnavailable(_) => "database_unavailable",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    /// Server errors don't tell clients what went wrong, so the cause, down
+    /// to its root, goes to the logs instead.
+    fn log(&self) {
+        let mut cause = match self {
+            AppError::Internal(msg) => msg.clone(),
+            err => err.to_string(),
+        };
+        let mut source = std::error::Error::source(self);
+        while let Some(err) = source {
+            cause.push_str(": ");
+            cause.push_str(&err.to_string());
+            source = err.source();
+        }
+
+        tracing::error!(code = self.code(), "{cause}");
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            AppError::BadJson(_) => "Malformed JSON body",
+            AppError::BadRequest(_) => "Bad request",
+            AppError::Unauthorized(_) => "Unauthorized",
+            AppError::NotFound(_) => "Resource not found",
+            AppError::NotAcceptable(_) => "Not acceptable",
+            AppError::Conflict(_) => "Conflict",
+            AppError::PreconditionFailed(_) => "Precondition failed",
+            AppError::Unprocessable(_) => "Unprocessable entity",
+            AppError::Invalid(_) => "Validation failed",
+            AppError::TooManyRequests(_) => "Too many requests",
+            AppError::Teapot(_) => "I'm a teapot",
+            AppError::DatabaseUnavailable(_) => "Database unavailable",
+            AppError::Internal(_) => "Internal server error",
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadJson(_) | AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::Unprocessable(_) | AppError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Teapot(_) => StatusCode::IM_A_TEAPOT,
+            AppError::DatabaseUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        if self.status().is_server_error() {
+            self.log();
+        }
+        let errors = match self {
+            AppError::Invalid(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+
+        problem(
+            self.status(),
+            self.code(),
+            self.title(),
+            &self.to_string(),
+            errors,
+        )
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => AppError::NotFound("Row not found".to_owned()),
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                AppError::Conflict(conflict(db.constraint()).to_owned())
+            }
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => AppError::DatabaseUnavailable(err),
+            err => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+/// What clients are told when `constraint` is violated. Postgres' own message
+/// names tables and indexes, which are nobody else's business.
+fn conflict(constraint: Option<&str>) -> &'static str {
+    match constraint {
+        Some("quotes_pkey") => "A quote with that ID already exists",
+        Some("quote_versions_pkey") => "The quote was changed at the same time",
+        Some("tags_name_key") => "A tag with that name already exists",
+        Some("quote_tags_pkey") => "The quote already has that tag",
+        Some("authors_name_key") => "An author with that name already exists",
+        Some("connect4_games_pkey") => "A game with that ID already exists",
+        Some("connect4_rounds_pkey") => "That round of the game was already started",
+        Some("connect4_moves_pkey") => "That move of the game was already played",
+        _ => "It already exists",
+    }
+}
+
+impl ApiResponse for AppError {
+    fn meta() -> MetaResponses {
+        MetaResponses {
+            responses: vec![MetaResponse {
+                description: "An RFC 7807 problem; `code` tells what went wrong.",
+                status: None,
+                status_range: None,
+                content: vec![MetaMediaType {
+                    content_type: "application/problem+json",
+                    schema: Problem::schema_ref(),
+                }],
+                headers: vec![],
+            }],
+        }
+    }
+
+    fn register(registry: &mut Registry) {
+        Problem::register(registry);
+    }
+}
+
+/// RFC 7807 problem details.
+#[derive(Object)]
+struct Problem {
+    #[oai(rename = "type")]
+    kind: String,
+    title: String,
+    status: u16,
+    detail: String,
+    /// Stable identifier of the error, e.g. `bad_json` or `not_found`.
+    code: String,
+    /// What is wrong with each invalid field, for `validation_failed`.
+    #[oai(skip_serializing_if_is_empty)]
+    errors: Vec<FieldError>,
+}
+
+/// Why a field of a request body was rejected.
+#[derive(Debug, Clone, PartialEq, Object)]
+pub struct FieldError {
+    /// Name of the field, with the index for array items, e.g. `tags[1]`.
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+fn problem(
+    status: StatusCode,
+    code: &str,
+    title: &str,
+    detail: &str,
+    errors: Vec<FieldError>,
+) -> Response {
+    let body = Problem {
+        kind: format!("urn:cch24:problem:{code}"),
+        title: title.to_owned(),
+        status: status.as_u16(),
+        detail: detail.to_owned(),
+        code: code.to_owned(),
+        errors,
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/problem+json")
+        .body(body.to_json_string())
+}
+
+/// Renders any error reaching the top of the route tree as problem+json,
+/// including the ones poem produces itself (unknown routes, bad path or query
+/// parameters, ...).
+pub async fn render(err: poem::Error) -> Response {
+    if let Some(err) = err.downcast_ref::<AppError>() {
+        return err.as_response();
+    }
+    // Every structured request body we accept is JSON.
+    if let Some(err) = err.downcast_ref::<ParseRequestPayloadError>() {
+        return AppError::BadJson(err.reason.clone()).as_response();
+    }
+
+    let status = err.status();
+    if status.is_server_error() {
+        tracing::error!(status = status.as_u16(), "{err}");
+    }
+    let reason = status.canonical_reason().unwrap_or("Error");
+    let code = reason.to_ascii_lowercase().replace([' ', '-'], "_");
+    let detail = err.to_string();
+
+    problem(status, &code, reason, &detail, Vec::new())
+}
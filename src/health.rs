@@ -0,0 +1,174 @@
+//! Liveness and readiness probes for orchestrators.
+
+use std::{future::Future, time::Duration};
+
+use poem_openapi::{payload::Json, ApiResponse, Enum, Object, OpenApi};
+use sqlx::{migrate::Migrate as _, Connection as _, PgPool};
+
+use crate::{ApiTags, MIGRATOR};
+
+/// How long a readiness check may take before the subsystem counts as down,
+/// well below the pool's own acquire timeout.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Api {
+    /// `None` when nothing is stored in Postgres, in which case the database
+    /// checks are skipped.
+    pool: Option<PgPool>,
+}
+
+impl Api {
+    pub fn new(pool: Option<PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[oai(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Failing,
+    /// The subsystem isn't in use.
+    Skipped,
+}
+
+#[derive(Object)]
+struct Liveness {
+    status: Status,
+}
+
+#[derive(Object)]
+struct Check {
+    status: Status,
+    /// Why the check failed.
+    #[oai(skip_serializing_if_is_none)]
+    detail: Option<String>,
+}
+
+impl Check {
+    fn skipped() -> Self {
+        Check {
+            status: Status::Skipped,
+            detail: None,
+        }
+    }
+}
+
+impl From<Result<(), String>> for Check {
+    fn from(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Check {
+                status: Status::Ok,
+                detail: None,
+            },
+            Err(detail) => Check {
+                status: Status::Failing,
+                detail: Some(detail),
+            },
+        }
+    }
+}
+
+#[derive(Object)]
+struct Checks {
+    /// Postgres answers a ping.
+    database: Check,
+    /// Every migration in `migrations/` has been applied, unmodified.
+    migrations: Check,
+}
+
+#[derive(Object)]
+struct Readiness {
+    status: Status,
+    checks: Checks,
+}
+
+#[derive(ApiResponse)]
+enum ReadyResponse {
+    /// Every subsystem is healthy.
+    #[oai(status = 200)]
+    Ready(Json<Readiness>),
+    /// At least one subsystem is failing; see `checks`.
+    #[oai(status = 503)]
+    NotReady(Json<Readiness>),
+}
+
+#[OpenApi(tag = "ApiTags::Ops")]
+impl Api {
+    /// Report that the process is up, without touching any dependency.
+    #[oai(path = "/healthz", method = "get")]
+    async fn healthz(&self) -> Json<Liveness> {
+        Json(Liveness { status: Status::Ok })
+    }
+
+    /// Report whether the instance can serve traffic, per subsystem.
+    #[oai(path = "/readyz", method = "get")]
+    async fn readyz(&self) -> ReadyResponse {
+        let checks = match &self.pool {
+            Some(pool) => {
+                let (database, migrations) = tokio::join!(
+                    with_timeout(check_database(pool)),
+                    with_timeout(check_migrations(pool))
+                );
+                Checks {
+                    database: database.into(),
+                    migrations: migrations.into(),
+                }
+            }
+            None => Checks {
+                database: Check::skipped(),
+                migrations: Check::skipped(),
+            },
+        };
+
+        if checks.database.status != Status::Failing && checks.migrations.status != Status::Failing
+        {
+            ReadyResponse::Ready(Json(Readiness {
+                status: Status::Ok,
+                checks,
+            }))
+        } else {
+            ReadyResponse::NotReady(Json(Readiness {
+                status: Status::Failing,
+                checks,
+            }))
+        }
+    }
+}
+
+async fn with_timeout(check: impl Future<Output = Result<(), String>>) -> Result<(), String> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("Timed out after {CHECK_TIMEOUT:?}")))
+}
+
+async fn check_database(pool: &PgPool) -> Result<(), String> {
+    let mut conn = pool.acquire().await.map_err(|err| err.to_string())?;
+    conn.ping().await.map_err(|err| err.to_string())
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<(), String> {
+    let mut conn = pool.acquire().await.map_err(|err| err.to_string())?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+    {
+        match applied.iter().find(|a| a.version == migration.version) {
+            None => return Err(format!("Migration {} is not applied", migration.version)),
+            Some(a) if a.checksum != migration.checksum => {
+                return Err(format!(
+                    "Migration {} was modified after it was applied",
+                    migration.version
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+mod clock;
+pub mod config;
+mod day0;
+mod day12;
+mod day16;
+mod day19;
+mod day2;
+mod day23;
+mod day5;
+mod day9;
+mod error;
+mod health;
+mod metrics;
+mod payload;
+
+use poem::{
+    middleware::{CookieJarManager, Tracing},
+    Endpoint, EndpointExt as _, Route,
+};
+use poem_openapi::{OpenApiService, Tags};
+use sqlx::{migrate::Migrator, PgPool};
+use std::sync::Arc;
+
+use crate::{
+    config::{Backend, Config},
+    day12::{MemoryGameStore, PgGameStore, SharedGameStore},
+    day19::{MemoryQuoteStore, PgQuoteStore, SharedQuoteStore},
+    metrics::{Metrics, RequestMetrics},
+};
+
+/// The migrations in `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Warming up
+    Day0,
+    /// IP address encryption
+    Day2,
+    /// Cargo manifest orders
+    Day5,
+    /// Rate-limited milk
+    Day9,
+    /// Connect 4 with cookies and milk
+    Day12,
+    /// JWT gift wrapping
+    Day16,
+    /// Quote book
+    Day19,
+    /// htmx Christmas tree
+    Day23,
+    /// Operational endpoints
+    Ops,
+}
+
+/// Builds the whole application, shared by the Shuttle entry point and the
+/// standalone server. `pool` is only needed by the Postgres backends.
+pub fn app(pool: Option<PgPool>, config: &Config) -> impl Endpoint {
+    let metrics = Metrics::new();
+    let quotes: SharedQuoteStore = match config.quotes.backend {
+        Backend::Postgres => Arc::new(PgQuoteStore::new(
+            pool.clone()
+                .expect("The Postgres quote backend needs a database"),
+        )),
+        Backend::Memory => Arc::new(MemoryQuoteStore::default()),
+    };
+    let games: SharedGameStore = match config.connect4.backend {
+        Backend::Postgres => Arc::new(PgGameStore::new(
+            pool.clone()
+                .expect("The Postgres game backend needs a database"),
+        )),
+        Backend::Memory => Arc::new(MemoryGameStore::default()),
+    };
+
+    let api = OpenApiService::new(
+        (
+            day0::Api,
+            day2::Api,
+            day5::Api,
+            day9::Api::new(&config.milk, &metrics),
+            day12::Api::new(&config.connect4, games, &metrics),
+            day16::Api::new(&config.gifts),
+            day19::Api::new(&config.quotes, quotes.clone()),
+            day23::Assets,
+            day23::Api,
+            health::Api::new(pool),
+            metrics::Api::new(&metrics, quotes),
+        ),
+        "Shuttlings CCH24",
+        env!("CARGO_PKG_VERSION"),
+    );
+
+    let request_metrics = RequestMetrics::new(&metrics, &api.spec());
+
+    Route::new()
+        .at("/openapi.json", api.spec_endpoint())
+        .nest("/docs", api.swagger_ui())
+        .nest("/", api)
+        .with(CookieJarManager::new())
+        .with(Tracing)
+        .with(request_metrics)
+        .catch_all_error(error::render)
+}
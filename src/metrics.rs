@@ -0,0 +1,213 @@
+//! Prometheus metrics: request counts and latencies for every route, plus a
+//! few gauges and counters owned by the day modules.
+
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use crate::{day19::SharedQuoteStore, ApiTags};
+use poem::{Endpoint, IntoResponse, Middleware, PathPattern, Request, Response, Result};
+use poem_openapi::{payload::PlainText, OpenApi};
+use prometheus::{
+    core::Collector, Encoder as _, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+
+/// Every metric the service exports. Cheap to clone; clones share the same
+/// underlying metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    pub(crate) connect4_games_finished: IntCounterVec,
+    pub(crate) quotes: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("cch24".to_owned()), None).expect("Namespace is valid");
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("Metric is valid");
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle HTTP requests",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("Metric is valid");
+        let connect4_games_finished = IntCounterVec::new(
+            Opts::new(
+                "connect4_games_finished_total",
+                "Connect 4 games played to the end, by winner",
+            ),
+            &["winner"],
+        )
+        .expect("Metric is valid");
+        let quotes = IntGauge::new("quotes", "Quotes in the quote book").expect("Metric is valid");
+
+        let metrics = Self {
+            registry,
+            requests,
+            request_duration,
+            connect4_games_finished,
+            quotes,
+        };
+        metrics.register(metrics.requests.clone());
+        metrics.register(metrics.request_duration.clone());
+        metrics.register(metrics.connect4_games_finished.clone());
+        metrics.register(metrics.quotes.clone());
+
+        metrics
+    }
+
+    /// Registers a collector that's read at scrape time, for values that are
+    /// cheaper to look up than to keep up to date.
+    pub(crate) fn register(&self, collector: impl Collector + 'static) {
+        self.registry
+            .register(Box::new(collector))
+            .expect("Metrics are only registered once");
+    }
+
+    fn render(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("Writing to a Vec can't fail");
+        String::from_utf8(buf).expect("Text format is UTF-8")
+    }
+}
+
+/// Records the count and latency of every request, labelled by the route
+/// template that matched rather than the raw path.
+#[derive(Clone)]
+pub struct RequestMetrics {
+    metrics: Metrics,
+    /// poem-openapi registers its routes with positional parameter names
+    /// (`/12/place/:param0/:param1`), so those are mapped back to the names
+    /// in the spec.
+    templates: Arc<HashMap<String, String>>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: &Metrics, spec: &str) -> Self {
+        let spec: serde_json::Value = serde_json::from_str(spec).expect("Spec is valid JSON");
+        let templates = spec["paths"]
+            .as_object()
+            .into_iter()
+            .flat_map(|paths| paths.keys())
+            .map(|path| {
+                let mut param = 0;
+                let mut pattern = Vec::new();
+                let mut template = Vec::new();
+                for segment in path.split('/') {
+                    match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                        Some(name) => {
+                            pattern.push(format!(":param{param}"));
+                            template.push(format!(":{name}"));
+                            param += 1;
+                        }
+                        None => {
+                            pattern.push(segment.to_owned());
+                            template.push(segment.to_owned());
+                        }
+                    }
+                }
+                (pattern.join("/"), template.join("/"))
+            })
+            .collect();
+
+        Self {
+            metrics: metrics.clone(),
+            templates: Arc::new(templates),
+        }
+    }
+
+    fn route(&self, pattern: Option<&PathPattern>) -> String {
+        match pattern.map(|p| &*p.0) {
+            Some(pattern) if !pattern.is_empty() => self
+                .templates
+                .get(pattern)
+                .cloned()
+                .unwrap_or_else(|| pattern.to_owned()),
+            // Unmatched paths are lumped together so scanners can't blow up
+            // the label cardinality.
+            _ => "unmatched".to_owned(),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestMetrics {
+    type Output = RequestMetricsEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestMetricsEndpoint {
+            inner: ep,
+            metrics: self.clone(),
+        }
+    }
+}
+
+pub struct RequestMetricsEndpoint<E> {
+    inner: E,
+    metrics: RequestMetrics,
+}
+
+impl<E: Endpoint> Endpoint for RequestMetricsEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let method = req.method().to_string();
+        let start = Instant::now();
+        let result = self.inner.call(req).await.map(IntoResponse::into_response);
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let (status, route) = match &result {
+            Ok(resp) => (resp.status(), self.metrics.route(resp.data())),
+            Err(err) => (err.status(), self.metrics.route(err.data())),
+        };
+        let labels = [method.as_str(), route.as_str(), status.as_str()];
+
+        let metrics = &self.metrics.metrics;
+        metrics.requests.with_label_values(&labels).inc();
+        metrics
+            .request_duration
+            .with_label_values(&labels)
+            .observe(elapsed);
+
+        result
+    }
+}
+
+pub struct Api {
+    metrics: Metrics,
+    quotes: SharedQuoteStore,
+}
+
+impl Api {
+    pub fn new(metrics: &Metrics, quotes: SharedQuoteStore) -> Self {
+        Self {
+            metrics: metrics.clone(),
+            quotes,
+        }
+    }
+}
+
+#[OpenApi(tag = "ApiTags::Ops")]
+impl Api {
+    /// Export metrics in the Prometheus text format.
+    #[oai(path = "/metrics", method = "get")]
+    async fn metrics(&self) -> PlainText<String> {
+        // A database outage shouldn't hide the request metrics, so the last
+        // known count is exported instead.
+        if let Ok(count) = self.quotes.count().await {
+            self.metrics.quotes.set(count);
+        }
+
+        PlainText(self.metrics.render())
+    }
+}
@@ -0,0 +1,40 @@
+//! Request payloads that poem-openapi doesn't provide out of the box.
+
+use poem::{FromRequest, Request, RequestBody};
+use poem_openapi::{
+    registry::{MetaMediaType, MetaRequest},
+    types::Type,
+    ApiExtractor, ApiExtractorType, ExtractParamOptions,
+};
+
+/// A UTF-8 body, documented as `text/plain` but accepted whatever
+/// `Content-Type` the client sends (or doesn't).
+pub struct AnyText(pub String);
+
+impl<'a> ApiExtractor<'a> for AnyText {
+    const TYPES: &'static [ApiExtractorType] = &[ApiExtractorType::RequestObject];
+
+    type ParamType = ();
+    type ParamRawType = ();
+
+    fn request_meta() -> Option<MetaRequest> {
+        Some(MetaRequest {
+            description: None,
+            content: vec![MetaMediaType {
+                content_type: "text/plain",
+                schema: String::schema_ref(),
+            }],
+            required: true,
+        })
+    }
+
+    async fn from_request(
+        request: &'a Request,
+        body: &mut RequestBody,
+        _param_opts: ExtractParamOptions<Self::ParamType>,
+    ) -> poem::Result<Self> {
+        <String as FromRequest>::from_request(request, body)
+            .await
+            .map(AnyText)
+    }
+}
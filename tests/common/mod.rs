@@ -0,0 +1,43 @@
+//! Shared setup for the integration tests, which drive the full application
+//! through poem's test client.
+
+use poem::{test::TestClient, Endpoint};
+use shuttlings_cch24::config::{Backend, Config};
+use sqlx::PgPool;
+
+/// The default configuration, but with quotes and games kept in memory so
+/// that no Postgres is needed.
+#[allow(dead_code)]
+pub fn config() -> Config {
+    let mut config = db_config();
+    config.quotes.backend = Backend::Memory;
+    config.connect4.backend = Backend::Memory;
+    config
+}
+
+/// The default configuration, with the secrets that have no default set, so
+/// that clients share them.
+pub fn db_config() -> Config {
+    let mut config = Config::default();
+    config.quotes.cursor_secret = Some("quotes".to_owned());
+    config.quotes.actor_token_secret = Some("actors".to_owned());
+    config
+}
+
+#[allow(dead_code)]
+pub fn client() -> TestClient<impl Endpoint> {
+    client_with(&config())
+}
+
+/// A client without a database, so `config` must use the memory backend.
+#[allow(dead_code)]
+pub fn client_with(config: &Config) -> TestClient<impl Endpoint> {
+    TestClient::new(shuttlings_cch24::app(None, config))
+}
+
+/// A client backed by a real database, e.g. one handed out by `#[sqlx::test]`
+/// (which needs `DATABASE_URL` pointing at a Postgres server).
+#[allow(dead_code)]
+pub fn db_client(pool: PgPool) -> TestClient<impl Endpoint> {
+    TestClient::new(shuttlings_cch24::app(Some(pool), &db_config()))
+}
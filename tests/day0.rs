@@ -0,0 +1,17 @@
+mod common;
+
+use poem::http::StatusCode;
+
+#[tokio::test]
+async fn hello_bird() {
+    let resp = common::client().get("/").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text("Hello, bird!").await;
+}
+
+#[tokio::test]
+async fn seek_redirects() {
+    let resp = common::client().get("/-1/seek").send().await;
+    resp.assert_status(StatusCode::FOUND);
+    resp.assert_header("location", "https://www.youtube.com/watch?v=9Gc4QTqslN4");
+}
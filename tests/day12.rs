@@ -0,0 +1,1196 @@
+mod common;
+
+use poem::{http::StatusCode, test::TestClient, Endpoint};
+use sqlx::PgPool;
+use std::time::Duration;
+
+const EMPTY_BOARD: &str = "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+";
+
+async fn place(cli: &TestClient<impl Endpoint>, team: &str, column: usize) -> (StatusCode, String) {
+    let resp = cli.post(format!("/12/place/{team}/{column}")).send().await;
+    let status = resp.0.status();
+    (status, resp.0.into_body().into_string().await.unwrap())
+}
+
+/// Start a game of its own, returning its ID.
+async fn create_game(cli: &TestClient<impl Endpoint>) -> String {
+    let resp = cli.post("/12/games").send().await;
+    resp.assert_status(StatusCode::CREATED);
+    let game = resp.json().await;
+    let game = game.value().object();
+    game.get("board").assert_string(EMPTY_BOARD);
+    game.get("id").string().to_owned()
+}
+
+#[tokio::test]
+async fn starts_empty() {
+    let resp = common::client().get("/12/board").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text(EMPTY_BOARD).await;
+}
+
+#[tokio::test]
+async fn vertical_win() {
+    let cli = common::client();
+
+    for _ in 0..3 {
+        let (status, _) = place(&cli, "cookie", 1).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, board) = place(&cli, "cookie", 1).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        board,
+        "\
+⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+🍪 wins!
+"
+    );
+
+    // Nobody gets to play after the game is won.
+    let (status, after) = place(&cli, "milk", 2).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(after, board);
+}
+
+#[tokio::test]
+async fn horizontal_win() {
+    let cli = common::client();
+
+    for column in 1..=4 {
+        place(&cli, "milk", column).await;
+    }
+    let resp = cli.get("/12/board").send().await;
+    resp.assert_text(
+        "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🥛🥛🥛🥛⬜
+⬜⬜⬜⬜⬜⬜
+🥛 wins!
+",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn draw() {
+    let cli = common::client();
+
+    for (column, teams) in [
+        (1, ["cookie", "milk", "cookie", "milk"]),
+        (2, ["cookie", "milk", "cookie", "milk"]),
+        (3, ["milk", "cookie", "milk", "cookie"]),
+        (4, ["milk", "cookie", "milk", "cookie"]),
+    ] {
+        for team in teams {
+            let (status, _) = place(&cli, team, column).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    let resp = cli.get("/12/board").send().await;
+    resp.assert_text(
+        "\
+⬜🥛🥛🍪🍪⬜
+⬜🍪🍪🥛🥛⬜
+⬜🥛🥛🍪🍪⬜
+⬜🍪🍪🥛🥛⬜
+⬜⬜⬜⬜⬜⬜
+No winner.
+",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn column_full() {
+    let cli = common::client();
+
+    for team in ["cookie", "milk", "cookie", "milk"] {
+        place(&cli, team, 3).await;
+    }
+    let (status, board) = place(&cli, "cookie", 3).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!board.contains("wins"));
+}
+
+#[tokio::test]
+async fn invalid_moves() {
+    let cli = common::client();
+
+    let (status, _) = place(&cli, "cookie", 0).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = place(&cli, "cookie", 5).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = place(&cli, "tea", 1).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reset() {
+    let cli = common::client();
+
+    place(&cli, "cookie", 2).await;
+    let resp = cli.post("/12/reset").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text(EMPTY_BOARD).await;
+}
+
+#[tokio::test]
+async fn random_boards_are_reproducible() {
+    let cli = common::client();
+
+    let first = cli.get("/12/random-board").send().await;
+    let first = first.0.into_body().into_string().await.unwrap();
+    let second = cli.get("/12/random-board").send().await;
+    let second = second.0.into_body().into_string().await.unwrap();
+    assert_ne!(first, second);
+
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+    cli.get("/12/random-board")
+        .send()
+        .await
+        .assert_text(first)
+        .await;
+}
+
+#[tokio::test]
+async fn reset_to_a_classic_board() {
+    let cli = common::client();
+
+    let resp = cli
+        .post("/12/reset")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text(
+        "\
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜⬜⬜⬜
+",
+    )
+    .await;
+
+    // All seven columns can be played, but no more.
+    let (status, _) = place(&cli, "cookie", 7).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = place(&cli, "cookie", 8).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Another reset goes back to the configured board.
+    cli.post("/12/reset")
+        .send()
+        .await
+        .assert_text(EMPTY_BOARD)
+        .await;
+}
+
+#[tokio::test]
+async fn diagonal_win_on_a_wide_board() {
+    let cli = common::client();
+
+    cli.post("/12/reset")
+        .query("width", &6)
+        .query("height", &3)
+        .query("connect", &3)
+        .send()
+        .await
+        .assert_status_is_ok();
+    for (team, column) in [
+        ("milk", 4),
+        ("milk", 5),
+        ("milk", 5),
+        ("cookie", 3),
+        ("cookie", 4),
+    ] {
+        let (status, _) = place(&cli, team, column).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, board) = place(&cli, "cookie", 5).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        board,
+        "\
+⬜⬛⬛⬛⬛🍪⬛⬜
+⬜⬛⬛⬛🍪🥛⬛⬜
+⬜⬛⬛🍪🥛🥛⬛⬜
+⬜⬜⬜⬜⬜⬜⬜⬜
+🍪 wins!
+"
+    );
+}
+
+#[tokio::test]
+async fn unplayable_boards_are_refused() {
+    let cli = common::client();
+
+    for (width, height, connect) in [(0, 4, 4), (4, 17, 4), (4, 4, 1), (5, 3, 6)] {
+        let resp = cli
+            .post("/12/reset")
+            .query("width", &width)
+            .query("height", &height)
+            .query("connect", &connect)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+    }
+    // The game in progress is left alone.
+    cli.get("/12/board")
+        .send()
+        .await
+        .assert_text(EMPTY_BOARD)
+        .await;
+}
+
+#[tokio::test]
+async fn random_boards_of_any_size() {
+    let cli = common::client();
+
+    let resp = cli
+        .get("/12/random-board")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let board = resp.0.into_body().into_string().await.unwrap();
+    let rows: Vec<_> = board.lines().collect();
+    assert_eq!(rows[0].chars().count(), 9);
+    assert_eq!(rows[6], "⬜".repeat(9));
+}
+
+#[tokio::test]
+async fn configured_board_size() {
+    let mut config = common::config();
+    config.connect4.width = 5;
+    config.connect4.height = 2;
+    config.connect4.connect = 2;
+    let cli = common::client_with(&config);
+
+    cli.get("/12/board")
+        .send()
+        .await
+        .assert_text(
+            "\
+⬜⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜⬜
+",
+        )
+        .await;
+    place(&cli, "milk", 5).await;
+    let (_, board) = place(&cli, "milk", 4).await;
+    assert!(board.ends_with("🥛 wins!\n"));
+}
+
+#[tokio::test]
+async fn games_are_played_apart() {
+    let cli = common::client();
+    let first = create_game(&cli).await;
+    let second = create_game(&cli).await;
+    assert_ne!(first, second);
+
+    for column in 1..=4 {
+        let resp = cli
+            .post(format!("/12/games/{first}/place/milk/{column}"))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+    }
+    let resp = cli
+        .post(format!("/12/games/{second}/place/cookie/1"))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+
+    cli.get(format!("/12/games/{first}/board"))
+        .send()
+        .await
+        .assert_text(
+            "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🥛🥛🥛🥛⬜
+⬜⬜⬜⬜⬜⬜
+🥛 wins!
+",
+        )
+        .await;
+    cli.get(format!("/12/games/{second}/board"))
+        .send()
+        .await
+        .assert_text(
+            "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+",
+        )
+        .await;
+    // Nor do they touch the default game.
+    cli.get("/12/board")
+        .send()
+        .await
+        .assert_text(EMPTY_BOARD)
+        .await;
+
+    let resp = cli
+        .post(format!("/12/games/{first}/place/cookie/1"))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    let resp = cli
+        .post(format!("/12/games/{first}/place/cookie/5"))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn games_of_any_size() {
+    let cli = common::client();
+
+    let resp = cli
+        .post("/12/games")
+        .query("width", &5)
+        .query("height", &1)
+        .query("connect", &2)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let game = resp.json().await;
+    let game = game.value().object();
+    game.get("board")
+        .assert_string("⬜⬛⬛⬛⬛⬛⬜\n⬜⬜⬜⬜⬜⬜⬜\n");
+    let id = game.get("id").string().to_owned();
+
+    let resp = cli
+        .post(format!("/12/games/{id}/place/milk/5"))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text("⬜⬛⬛⬛⬛🥛⬜\n⬜⬜⬜⬜⬜⬜⬜\n").await;
+
+    let resp = cli.post("/12/games").query("connect", &5).send().await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reset_a_game() {
+    let cli = common::client();
+    let id = create_game(&cli).await;
+
+    cli.post(format!("/12/games/{id}/place/cookie/2"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    // The size carries over unless asked otherwise.
+    let resp = cli.post(format!("/12/games/{id}/reset")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text(EMPTY_BOARD).await;
+
+    let resp = cli
+        .post(format!("/12/games/{id}/reset"))
+        .query("height", &1)
+        .send()
+        .await;
+    resp.assert_text("⬜⬛⬛⬛⬛⬜\n⬜⬜⬜⬜⬜⬜\n").await;
+    let resp = cli.post(format!("/12/games/{id}/reset")).send().await;
+    resp.assert_text("⬜⬛⬛⬛⬛⬜\n⬜⬜⬜⬜⬜⬜\n").await;
+}
+
+#[tokio::test]
+async fn unknown_games() {
+    let cli = common::client();
+    let id = "0f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f0";
+
+    cli.get(format!("/12/games/{id}/board"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.post(format!("/12/games/{id}/place/milk/1"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.post(format!("/12/games/{id}/reset"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.get("/12/games/nope/board")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn idle_games_expire() {
+    let mut config = common::config();
+    config.connect4.idle_game_timeout_secs = 1;
+    let cli = common::client_with(&config);
+    let idle = create_game(&cli).await;
+    let active = create_game(&cli).await;
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    cli.get(format!("/12/games/{active}/board"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    cli.get(format!("/12/games/{idle}/board"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.get(format!("/12/games/{active}/board"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    // The default game is there to stay.
+    cli.get("/12/board").send().await.assert_status_is_ok();
+}
+
+/// Milk wins game `id` down the first column.
+async fn milk_wins(cli: &TestClient<impl Endpoint>, id: &str) {
+    for team in ["milk", "cookie", "milk", "cookie", "milk", "cookie", "milk"] {
+        let column = if team == "milk" { 1 } else { 2 };
+        cli.post(format!("/12/games/{id}/place/{team}/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+}
+
+async fn archive(cli: TestClient<impl Endpoint>) {
+    let id = create_game(&cli).await;
+    milk_wins(&cli, &id).await;
+    // The next round is archived on its own once finished, and unfinished
+    // ones not at all.
+    cli.post(format!("/12/games/{id}/reset"))
+        .query("width", &2)
+        .query("height", &1)
+        .query("connect", &2)
+        .send()
+        .await
+        .assert_status_is_ok();
+    for (team, column) in [("cookie", 1), ("milk", 2)] {
+        cli.post(format!("/12/games/{id}/place/{team}/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    cli.post(format!("/12/games/{id}/reset"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post(format!("/12/games/{id}/place/milk/1"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    let other = create_game(&cli).await;
+    cli.post(format!("/12/games/{other}/place/milk/1"))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let resp = cli.get("/12/archive").send().await;
+    resp.assert_status_is_ok();
+    let archive = resp.json().await;
+    let archive = archive.value().array();
+    archive.assert_len(2);
+    let drawn = archive.get(0).object();
+    drawn.get("id").assert_string(&id);
+    drawn.get("round").assert_i64(2);
+    drawn.get("width").assert_i64(2);
+    drawn.get("height").assert_i64(1);
+    drawn.get("connect").assert_i64(2);
+    drawn.get("moves").assert_i64(2);
+    drawn.get("winner").assert_null();
+    let won = archive.get(1).object();
+    won.get("round").assert_i64(1);
+    won.get("winner").assert_string("milk");
+    won.get("moves").assert_i64(7);
+
+    let resp = cli
+        .get("/12/archive")
+        .query("limit", &1)
+        .query("offset", &1)
+        .send()
+        .await;
+    let page = resp.json().await;
+    page.value().array().assert_len(1);
+    page.value()
+        .array()
+        .get(0)
+        .object()
+        .get("round")
+        .assert_i64(1);
+
+    let resp = cli.get(format!("/12/archive/{id}/1")).send().await;
+    resp.assert_status_is_ok();
+    let replay = resp.json().await;
+    let replay = replay.value().object();
+    replay.get("winner").assert_string("milk");
+    let moves = replay.get("moves").array();
+    moves.assert_len(7);
+    moves.get(0).object().get("team").assert_string("milk");
+    moves.get(0).object().get("column").assert_i64(1);
+    moves.get(1).object().get("team").assert_string("cookie");
+    moves.get(1).object().get("column").assert_i64(2);
+    replay.get("board").assert_string(
+        "\
+⬜🥛⬛⬛⬛⬜
+⬜🥛🍪⬛⬛⬜
+⬜🥛🍪⬛⬛⬜
+⬜🥛🍪⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+🥛 wins!
+",
+    );
+
+    for path in [
+        format!("/12/archive/{id}/3"),
+        format!("/12/archive/{id}/4"),
+        format!("/12/archive/{other}/1"),
+    ] {
+        cli.get(path)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+    for limit in [0, 101] {
+        cli.get("/12/archive")
+            .query("limit", &limit)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn archive_in_memory() {
+    archive(common::client()).await;
+}
+
+#[sqlx::test]
+async fn archive_in_postgres(pool: PgPool) {
+    archive(common::db_client(pool)).await;
+}
+
+#[sqlx::test]
+async fn games_survive_restarts(pool: PgPool) {
+    let before = common::db_client(pool.clone());
+    let id = create_game(&before).await;
+    before
+        .post(format!("/12/games/{id}/reset"))
+        .query("width", &3)
+        .send()
+        .await
+        .assert_status_is_ok();
+    for (team, column) in [("cookie", 1), ("milk", 3), ("cookie", 1)] {
+        before
+            .post(format!("/12/games/{id}/place/{team}/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    before
+        .post("/12/place/milk/4")
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let after = common::db_client(pool);
+    let resp = after.get(format!("/12/games/{id}/board")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text(
+        "\
+⬜⬛⬛⬛⬜
+⬜⬛⬛⬛⬜
+⬜🍪⬛⬛⬜
+⬜🍪⬛🥛⬜
+⬜⬜⬜⬜⬜
+",
+    )
+    .await;
+    // Play goes on where it stopped.
+    let resp = after
+        .post(format!("/12/games/{id}/place/cookie/1"))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = after
+        .post(format!("/12/games/{id}/place/cookie/1"))
+        .send()
+        .await;
+    resp.assert_text(
+        "\
+⬜🍪⬛⬛⬜
+⬜🍪⬛⬛⬜
+⬜🍪⬛⬛⬜
+⬜🍪⬛🥛⬜
+⬜⬜⬜⬜⬜
+🍪 wins!
+",
+    )
+    .await;
+
+    // So does the default game.
+    after
+        .get("/12/board")
+        .send()
+        .await
+        .assert_text(
+            "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛🥛⬜
+⬜⬜⬜⬜⬜⬜
+",
+        )
+        .await;
+}
+
+#[sqlx::test]
+async fn idle_games_expire_across_restarts(pool: PgPool) {
+    let id = create_game(&common::db_client(pool.clone())).await;
+    sqlx::query("UPDATE connect4_games SET active_at = now() - INTERVAL '2 hours'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    common::db_client(pool)
+        .get(format!("/12/games/{id}/board"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn replicas_take_turns(pool: PgPool) {
+    let first = common::db_client(pool.clone());
+    let second = common::db_client(pool);
+    let id = create_game(&first).await;
+    second
+        .get(format!("/12/games/{id}/board"))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    first
+        .post(format!("/12/games/{id}/place/milk/1"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    // The second replica hasn't seen that move, and finds out.
+    let resp = second
+        .post(format!("/12/games/{id}/place/cookie/2"))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CONFLICT);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("detail")
+        .assert_string("That move of the game was already played");
+    let resp = second
+        .post(format!("/12/games/{id}/place/cookie/2"))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text(
+        "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🥛🍪⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+",
+    )
+    .await;
+}
+
+/// A replica that missed a reset can't play on in the round it knew.
+#[sqlx::test]
+async fn replicas_follow_resets(pool: PgPool) {
+    let first = common::db_client(pool.clone());
+    let second = common::db_client(pool);
+    let id = create_game(&first).await;
+    first
+        .post(format!("/12/games/{id}/place/milk/1"))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    second
+        .post(format!("/12/games/{id}/reset"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    first
+        .post(format!("/12/games/{id}/place/cookie/2"))
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    let resp = first
+        .post(format!("/12/games/{id}/place/cookie/2"))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text(
+        "\
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛🍪⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+",
+    )
+    .await;
+
+    // Round 1 was never finished, so it isn't archived.
+    first
+        .get(format!("/12/archive/{id}/1"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+/// A game waiting on the store holds up no other game.
+#[sqlx::test]
+async fn games_wait_on_the_store_alone(pool: PgPool) {
+    let cli = common::db_client(pool.clone());
+    let stuck = create_game(&cli).await;
+    let other = create_game(&cli).await;
+
+    // Storing a move marks its game active, which waits for this lock.
+    let mut lock = pool.begin().await.unwrap();
+    sqlx::query("SELECT FROM connect4_games WHERE id = $1::uuid FOR UPDATE")
+        .bind(&stuck)
+        .execute(&mut *lock)
+        .await
+        .unwrap();
+
+    let blocked = cli.post(format!("/12/games/{stuck}/place/cookie/1")).send();
+    let unblocked = async {
+        // Long enough for the first move to be waiting on the store.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let resp = tokio::time::timeout(
+            Duration::from_secs(5),
+            cli.post(format!("/12/games/{other}/place/cookie/1")).send(),
+        )
+        .await
+        .expect("The move waited on another game");
+        resp.assert_status_is_ok();
+        lock.commit().await.unwrap();
+    };
+    let (resp, ()) = tokio::join!(blocked, unblocked);
+    resp.assert_status_is_ok();
+}
+
+/// Has the computer play `team` in game `id`, returning the column it played.
+async fn computer(
+    cli: &TestClient<impl Endpoint>,
+    id: &str,
+    team: &str,
+    difficulty: &str,
+) -> (usize, String) {
+    let resp = cli
+        .post(format!("/12/games/{id}/ai/{team}"))
+        .query("difficulty", &difficulty)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let column = resp.0.headers()["x-column"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    (column, resp.0.into_body().into_string().await.unwrap())
+}
+
+#[tokio::test]
+async fn computer_takes_wins_and_blocks() {
+    let cli = common::client();
+
+    for difficulty in ["greedy", "depth-limited", "perfect"] {
+        let id = create_game(&cli).await;
+        for (team, column) in [("milk", 2), ("cookie", 3), ("milk", 2), ("cookie", 3)] {
+            cli.post(format!("/12/games/{id}/place/{team}/{column}"))
+                .send()
+                .await
+                .assert_status_is_ok();
+        }
+        cli.post(format!("/12/games/{id}/place/milk/2"))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        // Cookie can't win yet, so it stops milk from winning.
+        let (column, _) = computer(&cli, &id, "cookie", difficulty).await;
+        assert_eq!(column, 2, "{difficulty}");
+        for (team, column) in [("cookie", 3), ("milk", 1), ("milk", 1), ("milk", 1)] {
+            cli.post(format!("/12/games/{id}/place/{team}/{column}"))
+                .send()
+                .await
+                .assert_status_is_ok();
+        }
+        // Now both could win, so it does rather than block.
+        let (column, board) = computer(&cli, &id, "cookie", difficulty).await;
+        assert_eq!(column, 3, "{difficulty}");
+        assert!(board.ends_with("🍪 wins!\n"), "{difficulty}");
+
+        let resp = cli
+            .post(format!("/12/games/{id}/ai/milk"))
+            .query("difficulty", &difficulty)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        resp.assert_text(board).await;
+    }
+}
+
+#[tokio::test]
+async fn computer_plays_randomly() {
+    let cli = common::client();
+
+    let resp = cli
+        .post("/12/games")
+        .query("width", &3)
+        .query("height", &1)
+        .query("connect", &2)
+        .send()
+        .await;
+    let id = resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get("id")
+        .string()
+        .to_owned();
+    for column in [1, 3] {
+        cli.post(format!("/12/games/{id}/place/cookie/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    let (column, _) = computer(&cli, &id, "milk", "random").await;
+    assert_eq!(column, 2);
+}
+
+#[tokio::test]
+async fn perfect_play_draws() {
+    let cli = common::client();
+
+    let mut teams = ["cookie", "milk"].into_iter().cycle();
+    let board = loop {
+        let resp = cli
+            .post(format!("/12/ai/{}", teams.next().unwrap()))
+            .query("difficulty", &"perfect")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let board = resp.0.into_body().into_string().await.unwrap();
+        if board.lines().count() > 5 {
+            break board;
+        }
+    };
+    assert!(board.ends_with("No winner.\n"), "{board}");
+}
+
+#[tokio::test]
+async fn computer_keeps_to_its_time_budget() {
+    let mut config = common::config();
+    config.connect4.ai_time_budget_ms = 50;
+    let cli = common::client_with(&config);
+
+    let resp = cli
+        .post("/12/reset")
+        .query("width", &16)
+        .query("height", &16)
+        .query("connect", &5)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let started = std::time::Instant::now();
+    let resp = cli
+        .post("/12/ai/milk")
+        .query("difficulty", &"perfect")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    // Generously, as the tests may run on a busy machine.
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(resp.0.headers()["x-column"], "8");
+}
+
+#[tokio::test]
+async fn computer_needs_a_game_and_a_difficulty() {
+    let cli = common::client();
+
+    cli.post("/12/games/0f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f0/ai/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.post("/12/ai/milk")
+        .query("difficulty", &"impossible")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    // Depth-limited unless asked otherwise.
+    cli.post("/12/ai/milk").send().await.assert_status_is_ok();
+}
+
+#[tokio::test]
+async fn analysis_finds_wins_and_blocks() {
+    let cli = common::client();
+    for (team, column) in [
+        ("cookie", 1),
+        ("milk", 2),
+        ("cookie", 1),
+        ("milk", 2),
+        ("cookie", 1),
+    ] {
+        let (status, _) = place(&cli, team, column).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Cookie wins on the spot, with 11 cells still empty.
+    let resp = cli
+        .get("/12/analysis")
+        .query("team", &"cookie")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let analysis = resp.json().await;
+    let analysis = analysis.value().object();
+    analysis.get("team").assert_string("cookie");
+    analysis.get("winner").assert_string("cookie");
+    analysis.get("score").assert_i64(11);
+    analysis.get("moves").assert_i64(1);
+    analysis.get("best_columns").assert_i64_array(&[1]);
+    let variation = analysis.get("principal_variation").array();
+    variation.assert_len(1);
+    variation
+        .get(0)
+        .object()
+        .get("team")
+        .assert_string("cookie");
+    variation.get(0).object().get("column").assert_i64(1);
+
+    // Milk is to move, having fewer tiles, and loses right away unless it
+    // blocks, which draws.
+    let resp = cli.get("/12/analysis").send().await;
+    resp.assert_status_is_ok();
+    let analysis = resp.json().await;
+    let analysis = analysis.value().object();
+    analysis.get("team").assert_string("milk");
+    analysis.get("best_columns").assert_i64_array(&[1]);
+    let columns = analysis.get("columns").array();
+    columns.assert_len(4);
+    for i in 1..4 {
+        let column = columns.get(i).object();
+        column.get("column").assert_i64(i as i64 + 1);
+        column.get("winner").assert_string("cookie");
+        column.get("score").assert_i64(-10);
+        column.get("moves").assert_i64(2);
+    }
+    analysis.get("winner").assert_null();
+    analysis.get("score").assert_i64(0);
+}
+
+#[tokio::test]
+async fn analysis_of_an_empty_board() {
+    let cli = common::client();
+
+    let resp = cli
+        .post("/12/analysis")
+        .content_type("text/plain")
+        .body(EMPTY_BOARD)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let analysis = resp.json().await;
+    let analysis = analysis.value().object();
+    // Neither team can win a 4x4 game against perfect play.
+    analysis.get("team").assert_string("cookie");
+    analysis.get("winner").assert_null();
+    analysis.get("score").assert_i64(0);
+    analysis.get("moves").assert_i64(16);
+    analysis.get("best_columns").assert_i64_array(&[1, 2, 3, 4]);
+    analysis.get("principal_variation").array().assert_len(16);
+}
+
+#[tokio::test]
+async fn analysis_of_submitted_boards() {
+    let cli = common::client();
+    let analyse = |board: &'static str| {
+        cli.post("/12/analysis")
+            .content_type("text/plain")
+            .body(board)
+    };
+
+    // Milk can finish its line of three at either end.
+    let resp = analyse(
+        "\
+⬜⬛⬛⬛⬛⬛⬜
+⬜⬛🍪🍪⬛⬛⬜
+⬜⬛🥛🥛⬛🍪⬜
+⬜⬜⬜⬜⬜⬜⬜
+",
+    )
+    .query("team", &"milk")
+    .query("connect", &3)
+    .send()
+    .await;
+    resp.assert_status_is_ok();
+    let analysis = resp.json().await;
+    let analysis = analysis.value().object();
+    analysis.get("winner").assert_string("milk");
+    analysis.get("score").assert_i64(10);
+    analysis.get("moves").assert_i64(1);
+    analysis.get("best_columns").assert_i64_array(&[1, 4]);
+
+    for board in [
+        "",
+        "⬜⬛⬛⬜\n",
+        "⬜⬛⬛⬜\n⬜⬛⬜\n⬜⬜⬜⬜\n",
+        "⬜⬛🍩⬜\n⬜⬛⬛⬜\n⬜⬜⬜⬜\n",
+        "⬜🍪⬛⬜\n⬜⬛⬛⬜\n⬜⬜⬜⬜\n",
+    ] {
+        analyse(board)
+            .query("connect", &2)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+    // The line saying who won is optional, but the game must still be on.
+    analyse("⬜🍪⬛⬜\n⬜🍪⬛⬜\n⬜⬜⬜⬜\n🍪 wins!\n")
+        .query("connect", &2)
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    analyse("⬜🍪⬛⬜\n⬜🍪⬛⬜\n⬜⬜⬜⬜\n")
+        .query("connect", &2)
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    analyse(EMPTY_BOARD)
+        .query("connect", &5)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn analysis_of_games() {
+    let cli = common::client();
+    let id = create_game(&cli).await;
+    for (team, column) in [("cookie", 2), ("milk", 2), ("cookie", 3)] {
+        cli.post(format!("/12/games/{id}/place/{team}/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    let resp = cli.get(format!("/12/games/{id}/analysis")).send().await;
+    resp.assert_status_is_ok();
+    let analysis = resp.json().await;
+    let analysis = analysis.value().object();
+    analysis.get("team").assert_string("milk");
+    analysis.get("winner").assert_null();
+    analysis.get("moves").assert_i64(13);
+    // Each move of the variation is the other team's.
+    let variation = analysis.get("principal_variation").array();
+    variation.assert_len(13);
+    for i in 0..13 {
+        let team = if i % 2 == 0 { "milk" } else { "cookie" };
+        variation.get(i).object().get("team").assert_string(team);
+    }
+
+    cli.get("/12/games/0f1e2d3c-4b5a-4978-8695-a4b3c2d1e0f0/analysis")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn analysis_keeps_to_its_time_budget() {
+    let mut config = common::config();
+    config.connect4.analysis_time_budget_ms = 50;
+    let cli = common::client_with(&config);
+
+    let resp = cli
+        .post("/12/reset")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let started = std::time::Instant::now();
+    cli.get("/12/analysis")
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    // Generously, as the tests may run on a busy machine.
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn searches_are_limited() {
+    let mut config = common::config();
+    config.connect4.analysis_time_budget_ms = 500;
+    config.connect4.max_searches = 1;
+    let cli = common::client_with(&config);
+
+    cli.post("/12/reset")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await
+        .assert_status_is_ok();
+    let (first, second) = tokio::join!(
+        cli.get("/12/analysis").send(),
+        cli.get("/12/analysis").send()
+    );
+    let mut statuses = [first.0.status(), second.0.status()];
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::UNPROCESSABLE_ENTITY,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+
+    // The permit is back once the search is over.
+    cli.post("/12/ai/cookie")
+        .query("difficulty", &"greedy")
+        .send()
+        .await
+        .assert_status_is_ok();
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use poem::http::StatusCode;
+use serde_json::json;
+use shuttlings_cch24::config::Config;
+
+// A throwaway key pair standing in for Santa's, whose private half we don't
+// have.
+const TEST_PRIVATE_KEY: &[u8] = include_bytes!("fixtures/santa_test_key.pem");
+const TEST_PUBLIC_KEY: &str = include_str!("fixtures/santa_test_key.pub.pem");
+
+fn sign(algorithm: Algorithm, claims: &serde_json::Value) -> String {
+    jsonwebtoken::encode(
+        &Header::new(algorithm),
+        claims,
+        &EncodingKey::from_rsa_pem(TEST_PRIVATE_KEY).unwrap(),
+    )
+    .unwrap()
+}
+
+fn santa_config() -> Config {
+    let mut config = common::config();
+    config.gifts.santa_public_key = Some(TEST_PUBLIC_KEY.to_owned());
+    config
+}
+
+#[tokio::test]
+async fn wrap_unwrap_round_trip() {
+    let cli = common::client();
+    let gift = json!({"cookie": "yum", "count": 3, "nested": {"milk": true}});
+
+    let resp = cli.post("/16/wrap").body_json(&gift).send().await;
+    resp.assert_status_is_ok();
+    let cookie = resp.0.headers()["set-cookie"].to_str().unwrap().to_owned();
+    assert!(cookie.starts_with("gift="));
+
+    let resp = cli.get("/16/unwrap").header("cookie", cookie).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(gift).await;
+}
+
+#[tokio::test]
+async fn unwrap_without_cookie() {
+    common::client()
+        .get("/16/unwrap")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unwrap_tampered_cookie() {
+    let cli = common::client();
+
+    let resp = cli
+        .post("/16/wrap")
+        .body_json(&json!({"a": 1}))
+        .send()
+        .await;
+    let cookie = resp.0.headers()["set-cookie"].to_str().unwrap().to_owned();
+
+    let resp = cli
+        .get("/16/unwrap")
+        .header("cookie", format!("{cookie}x"))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn wrap_secret_is_configurable() {
+    let resp = common::client()
+        .post("/16/wrap")
+        .body_json(&json!({"a": 1}))
+        .send()
+        .await;
+    let cookie = resp.0.headers()["set-cookie"].to_str().unwrap().to_owned();
+
+    let mut config = common::config();
+    config.gifts.jwt_secret = "another secret".to_owned();
+    common::client_with(&config)
+        .get("/16/unwrap")
+        .header("cookie", cookie)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn decode_rs256() {
+    let claims = json!({"reindeerSnack": "carrots", "naughty": false});
+
+    let resp = common::client_with(&santa_config())
+        .post("/16/decode")
+        .body(sign(Algorithm::RS256, &claims))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(claims).await;
+}
+
+#[tokio::test]
+async fn decode_rs512() {
+    let claims = json!({"gifts": ["Naughty List", "Lump of Coal"]});
+
+    let resp = common::client_with(&santa_config())
+        .post("/16/decode")
+        .body(sign(Algorithm::RS512, &claims))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(claims).await;
+}
+
+#[tokio::test]
+async fn decode_bad_signature() {
+    // Signed with the test key, but verified with the real Santa's key.
+    let resp = common::client()
+        .post("/16/decode")
+        .body(sign(Algorithm::RS256, &json!({"a": 1})))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("code")
+        .assert_string("unauthorized");
+}
+
+#[tokio::test]
+async fn decode_garbage() {
+    common::client_with(&santa_config())
+        .post("/16/decode")
+        .body("definitely.not.a-jwt")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn decode_rejects_other_algorithms() {
+    let token = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &json!({"a": 1}),
+        &EncodingKey::from_secret(b"a"),
+    )
+    .unwrap();
+
+    common::client_with(&santa_config())
+        .post("/16/decode")
+        .body(token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,2281 @@
+//! Every scenario runs against both quote backends. The Postgres ones need a
+//! server: `#[sqlx::test]` creates a fresh, migrated database per test on the
+//! server at `DATABASE_URL`.
+
+mod common;
+
+use futures_util::{Stream, StreamExt as _};
+use poem::{
+    http::StatusCode,
+    listener::{Acceptor as _, Listener as _, TcpListener},
+    test::TestClient,
+    Endpoint, Server,
+};
+use serde_json::{json, Value};
+use shuttlings_cch24::config::Config;
+use sqlx::PgPool;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpStream,
+};
+
+macro_rules! backend_tests {
+    ($($scenario:ident),* $(,)?) => {
+        mod postgres {
+            use super::*;
+
+            $(
+                #[sqlx::test]
+                async fn $scenario(pool: PgPool) {
+                    super::$scenario(common::db_client(pool)).await;
+                }
+            )*
+        }
+
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $scenario() {
+                    super::$scenario(super::common::client()).await;
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    crud_lifecycle,
+    missing_quotes,
+    draft_requires_author_and_quote,
+    draft_limits_lengths,
+    draft_rejects_server_fields,
+    draft_normalizes_text,
+    draft_wrong_types,
+    update_validates,
+    reset,
+    pagination,
+    pagination_single_page,
+    pagination_unknown_token,
+    pagination_tampered_token,
+    pagination_limit,
+    pagination_limit_out_of_range,
+    pagination_sees_new_quotes,
+    versions_record_every_change,
+    revert_creates_new_version,
+    revert_deleted_quote,
+    restore_deleted_quote,
+    restore_live_quote,
+    missing_versions,
+    reset_clears_history,
+    etags_follow_version,
+    cite_not_modified,
+    update_if_match,
+    update_if_match_any,
+    delete_if_match,
+    if_match_missing_quote,
+    search_text,
+    search_ranks_authors_higher,
+    search_without_text,
+    search_filters,
+    search_pagination,
+    search_tokens_stay_with_their_search,
+    search_bad_date_range,
+    import_ndjson,
+    import_csv,
+    import_atomic,
+    import_csv_needs_columns,
+    export_formats,
+    export_in_batches,
+    export_not_acceptable,
+    export_reimports,
+    events_stream_changes,
+    events_resume_from_last_event_id,
+    events_follow_the_trash,
+    tags_normalized,
+    tags_blank,
+    update_tags,
+    tag_counts,
+    list_by_tag,
+    list_tag_tokens_stay_with_their_tag,
+    delete_moves_to_trash,
+    restore_from_trash,
+    trash_pagination,
+    purge,
+    reset_needs_confirmation,
+    random_quote,
+    random_quote_by_author,
+    quote_of_the_day,
+    quote_of_the_day_holds,
+    authors_match_spellings,
+    authors_leave_out_the_trash,
+    author_profile_pagination,
+    author_tokens_stay_with_their_author,
+    missing_author,
+    audit_records_changes,
+    audit_actor_and_client_ip,
+    audit_rejects_bad_tokens,
+    audit_skips_failed_changes,
+    audit_filters,
+    audit_pagination,
+    audit_records_imports,
+);
+
+async fn draft(cli: &TestClient<impl Endpoint>, author: &str, quote: &str) -> Value {
+    let resp = cli
+        .post("/19/draft")
+        .body_json(&json!({"author": author, "quote": quote}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    resp.json().await.value().deserialize()
+}
+
+async fn list(cli: &TestClient<impl Endpoint>, token: Option<&str>) -> Value {
+    list_with_limit(cli, token, None).await
+}
+
+async fn list_with_limit(
+    cli: &TestClient<impl Endpoint>,
+    token: Option<&str>,
+    limit: Option<usize>,
+) -> Value {
+    let mut req = cli.get("/19/list");
+    if let Some(token) = token {
+        req = req.query("token", &token);
+    }
+    if let Some(limit) = limit {
+        req = req.query("limit", &limit);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn crud_lifecycle(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    assert_eq!(quote["author"], "Santa");
+    assert_eq!(quote["quote"], "Ho ho ho!");
+    assert_eq!(quote["version"], 1);
+
+    let resp = cli.get(format!("/19/cite/{id}")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&quote).await;
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Mrs. Claus", "quote": "Cookies are ready"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let updated: Value = resp.json().await.value().deserialize();
+    assert_eq!(updated["author"], "Mrs. Claus");
+    assert_eq!(updated["version"], 2);
+    assert_eq!(updated["created_at"], quote["created_at"]);
+
+    let resp = cli.delete(format!("/19/remove/{id}")).send().await;
+    resp.assert_status_is_ok();
+    let mut deleted: Value = resp.json().await.value().deserialize();
+    assert!(deleted["deleted_at"].is_string());
+    deleted.as_object_mut().unwrap().remove("deleted_at");
+    assert_eq!(deleted, updated);
+
+    cli.get(format!("/19/cite/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn missing_quotes(cli: TestClient<impl Endpoint>) {
+    let id = "00000000-0000-0000-0000-000000000000";
+
+    let resp = cli.get(format!("/19/cite/{id}")).send().await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    resp.assert_content_type("application/problem+json");
+    cli.put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "a", "quote": "b"}))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.delete(format!("/19/remove/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.get("/19/cite/not-a-uuid")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+/// Posts `body` to `/19/draft`, expecting it to be rejected, and returns the
+/// field errors.
+async fn draft_errors(cli: &TestClient<impl Endpoint>, body: Value) -> Value {
+    let resp = cli.post("/19/draft").body_json(&body).send().await;
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    resp.assert_content_type("application/problem+json");
+    let problem: Value = resp.json().await.value().deserialize();
+    assert_eq!(problem["code"], "validation_failed");
+    problem["errors"].clone()
+}
+
+async fn draft_requires_author_and_quote(cli: TestClient<impl Endpoint>) {
+    let errors = draft_errors(&cli, json!({"author": "Santa"})).await;
+    assert_eq!(
+        errors,
+        json!([{"field": "quote", "message": "is required"}])
+    );
+
+    let errors = draft_errors(&cli, json!({"author": " \t", "quote": ""})).await;
+    assert_eq!(
+        errors,
+        json!([
+            {"field": "author", "message": "must not be blank"},
+            {"field": "quote", "message": "must not be blank"},
+        ])
+    );
+    assert!(all_quotes(&cli).await.is_empty());
+}
+
+async fn draft_limits_lengths(cli: TestClient<impl Endpoint>) {
+    let errors = draft_errors(
+        &cli,
+        json!({"author": "a".repeat(201), "quote": "b".repeat(2001)}),
+    )
+    .await;
+    let fields: Vec<_> = errors
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["field"])
+        .collect();
+    assert_eq!(fields, ["author", "quote"]);
+
+    // Limits count characters, not bytes.
+    let quote = draft(&cli, &"é".repeat(200), &"❄".repeat(2000)).await;
+    assert_eq!(quote["version"], 1);
+}
+
+async fn draft_rejects_server_fields(cli: TestClient<impl Endpoint>) {
+    let errors = draft_errors(
+        &cli,
+        json!({
+            "id": "00000000-0000-0000-0000-000000000000",
+            "author": "Santa",
+            "quote": "Ho ho ho!",
+            "version": 7,
+            "created_at": "2024-12-24T00:00:00Z",
+        }),
+    )
+    .await;
+    let fields: Vec<_> = errors
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["field"])
+        .collect();
+    assert_eq!(fields, ["id", "version", "created_at"]);
+}
+
+async fn draft_normalizes_text(cli: TestClient<impl Endpoint>) {
+    // "é" spelled as "e" and a combining accent.
+    let quote = draft(&cli, "  Ren\u{e9}e\n", " Cafe\u{301} au lait ").await;
+    assert_eq!(quote["author"], "Ren\u{e9}e");
+    assert_eq!(quote["quote"], "Caf\u{e9} au lait");
+}
+
+async fn draft_wrong_types(cli: TestClient<impl Endpoint>) {
+    let resp = cli
+        .post("/19/draft")
+        .body_json(&json!({"author": {"name": "Santa"}, "quote": "Ho ho ho!"}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_content_type("application/problem+json");
+}
+
+async fn update_validates(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Santa", "quote": " ", "version": 1}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: Value = resp.json().await.value().deserialize();
+    assert_eq!(
+        problem["errors"],
+        json!([
+            {"field": "version", "message": "is assigned by the server"},
+            {"field": "quote", "message": "must not be blank"},
+        ])
+    );
+
+    cli.get(format!("/19/cite/{id}"))
+        .send()
+        .await
+        .assert_json(&quote)
+        .await;
+}
+
+async fn reset(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.get(format!("/19/cite/{}", quote["id"].as_str().unwrap()))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn pagination(cli: TestClient<impl Endpoint>) {
+    let mut drafted = Vec::new();
+    for i in 0..7 {
+        drafted.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+    }
+
+    let mut seen = Vec::new();
+    let mut token: Option<String> = None;
+    for page in 1..=3 {
+        let body = list(&cli, token.as_deref()).await;
+        assert_eq!(body["page"], page);
+        seen.extend(body["quotes"].as_array().unwrap().iter().cloned());
+        token = body["next_token"].as_str().map(str::to_owned);
+        assert_eq!(token.is_some(), page < 3);
+    }
+    assert_eq!(seen, drafted);
+}
+
+async fn pagination_single_page(cli: TestClient<impl Endpoint>) {
+    draft(&cli, "Elf", "Only one").await;
+    let body = list(&cli, None).await;
+    assert_eq!(body["page"], 1);
+    assert_eq!(body["quotes"].as_array().unwrap().len(), 1);
+    assert_eq!(body["next_token"], Value::Null);
+}
+
+async fn pagination_unknown_token(cli: TestClient<impl Endpoint>) {
+    cli.get("/19/list")
+        .query("token", &"0123456789abcdef")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn pagination_tampered_token(cli: TestClient<impl Endpoint>) {
+    for i in 0..4 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+    let body = list(&cli, None).await;
+    let token = body["next_token"].as_str().unwrap();
+
+    // Swap the claims for another token's, keeping the original signature.
+    let mut parts: Vec<&str> = token.split('.').collect();
+    let forged_claims = "eyJwYWdlIjo5LCJjcmVhdGVkX2F0IjowLCJpZCI6IjAwMDAwMDAwLTAwMDAtMDAwMC0wMDAwLTAwMDAwMDAwMDAwMCJ9";
+    parts[1] = forged_claims;
+
+    cli.get("/19/list")
+        .query("token", &parts.join("."))
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn pagination_limit(cli: TestClient<impl Endpoint>) {
+    for i in 0..5 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+
+    let mut sizes = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        let body = list_with_limit(&cli, token.as_deref(), Some(2)).await;
+        sizes.push(body["quotes"].as_array().unwrap().len());
+        token = body["next_token"].as_str().map(str::to_owned);
+        if token.is_none() {
+            assert_eq!(body["page"], 3);
+            break;
+        }
+    }
+    assert_eq!(sizes, [2, 2, 1]);
+}
+
+async fn pagination_limit_out_of_range(cli: TestClient<impl Endpoint>) {
+    for limit in [0, 101] {
+        cli.get("/19/list")
+            .query("limit", &limit)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+async fn pagination_sees_new_quotes(cli: TestClient<impl Endpoint>) {
+    for i in 0..4 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+    let first = list(&cli, None).await;
+    let token = first["next_token"].as_str().unwrap();
+
+    let late = draft(&cli, "Elf", "Late arrival").await;
+    let second = list(&cli, Some(token)).await;
+    let quotes = second["quotes"].as_array().unwrap();
+    assert_eq!(quotes.len(), 2);
+    assert_eq!(quotes[1], late);
+}
+
+async fn update(cli: &TestClient<impl Endpoint>, id: &str, author: &str, quote: &str) -> Value {
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": author, "quote": quote}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn versions(cli: &TestClient<impl Endpoint>, id: &str) -> Vec<Value> {
+    let resp = cli.get(format!("/19/versions/{id}")).send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn versions_record_every_change(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    update(&cli, id, "Santa", "Ho ho ho ho!").await;
+    update(&cli, id, "Rudolph", "My nose glows").await;
+
+    let history = versions(&cli, id).await;
+    let texts: Vec<_> = history.iter().map(|v| v["quote"].clone()).collect();
+    assert_eq!(texts, ["Ho ho ho!", "Ho ho ho ho!", "My nose glows"]);
+    let numbers: Vec<_> = history.iter().map(|v| v["version"].clone()).collect();
+    assert_eq!(numbers, [1, 2, 3]);
+    assert!(history[..2].iter().all(|v| v["archived_at"].is_string()));
+    assert!(history[2].get("archived_at").is_none_or(Value::is_null));
+    assert!(history
+        .iter()
+        .all(|v| v["created_at"] == quote["created_at"]));
+
+    let resp = cli.get(format!("/19/versions/{id}/2")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&history[1]).await;
+}
+
+async fn revert_creates_new_version(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    update(&cli, id, "Grinch", "Bah!").await;
+
+    let resp = cli.post(format!("/19/versions/{id}/1/revert")).send().await;
+    resp.assert_status_is_ok();
+    let reverted: Value = resp.json().await.value().deserialize();
+    assert_eq!(reverted["author"], "Santa");
+    assert_eq!(reverted["quote"], "Ho ho ho!");
+    assert_eq!(reverted["version"], 3);
+
+    // The reverted-away version is still there to go back to.
+    let history = versions(&cli, id).await;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[1]["author"], "Grinch");
+
+    cli.post(format!("/19/versions/{id}/7/revert"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn revert_deleted_quote(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    cli.delete(format!("/19/remove/{id}")).send().await;
+
+    cli.post(format!("/19/versions/{id}/1/revert"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn restore_deleted_quote(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    let updated = update(&cli, id, "Santa", "Merry Christmas").await;
+    cli.delete(format!("/19/remove/{id}")).send().await;
+
+    // Deleted quotes keep their history, their last version archived.
+    let history = versions(&cli, id).await;
+    assert_eq!(history.len(), 2);
+    assert!(history[1]["archived_at"].is_string());
+
+    let resp = cli.post(format!("/19/versions/{id}/restore")).send().await;
+    resp.assert_status(StatusCode::CREATED);
+    let restored: Value = resp.json().await.value().deserialize();
+    assert_eq!(restored, updated);
+    assert_eq!(restored["created_at"], quote["created_at"]);
+
+    let resp = cli.get(format!("/19/cite/{id}")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&restored).await;
+    assert_eq!(list(&cli, None).await["quotes"][0], restored);
+    assert_eq!(versions(&cli, id).await.len(), 2);
+}
+
+async fn restore_live_quote(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    cli.post(format!("/19/versions/{id}/restore"))
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+}
+
+async fn missing_versions(cli: TestClient<impl Endpoint>) {
+    let id = "00000000-0000-0000-0000-000000000000";
+    cli.get(format!("/19/versions/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.post(format!("/19/versions/{id}/restore"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    cli.get(format!("/19/versions/{id}/2"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn reset_clears_history(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    update(&cli, id, "Santa", "Merry Christmas").await;
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    cli.get(format!("/19/versions/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn etags_follow_version(cli: TestClient<impl Endpoint>) {
+    let resp = cli
+        .post("/19/draft")
+        .body_json(&json!({"author": "Santa", "quote": "Ho ho ho!"}))
+        .send()
+        .await;
+    resp.assert_header("etag", "\"1\"");
+    let quote: Value = resp.json().await.value().deserialize();
+    let id = quote["id"].as_str().unwrap();
+
+    cli.get(format!("/19/cite/{id}"))
+        .send()
+        .await
+        .assert_header("etag", "\"1\"");
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Santa", "quote": "Merry Christmas"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_header("etag", "\"2\"");
+}
+
+async fn cite_not_modified(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    for current in ["\"1\"", "W/\"1\"", "\"7\", \"1\"", "*"] {
+        let resp = cli
+            .get(format!("/19/cite/{id}"))
+            .header("If-None-Match", current)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NOT_MODIFIED);
+        resp.assert_header("etag", "\"1\"");
+    }
+
+    update(&cli, id, "Santa", "Merry Christmas").await;
+    let resp = cli
+        .get(format!("/19/cite/{id}"))
+        .header("If-None-Match", "\"1\"")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_header("etag", "\"2\"");
+}
+
+async fn update_if_match(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .header("If-Match", "\"1\"")
+        .body_json(&json!({"author": "Santa", "quote": "First edit"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+
+    // A second editor still holding version 1 loses.
+    for stale in ["\"1\"", "W/\"2\"", "garbage"] {
+        let resp = cli
+            .put(format!("/19/undo/{id}"))
+            .header("If-Match", stale)
+            .body_json(&json!({"author": "Grinch", "quote": "Second edit"}))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PRECONDITION_FAILED);
+        resp.assert_content_type("application/problem+json");
+    }
+
+    let resp = cli.get(format!("/19/cite/{id}")).send().await;
+    let current: Value = resp.json().await.value().deserialize();
+    assert_eq!(current["quote"], "First edit");
+    assert_eq!(current["version"], 2);
+}
+
+async fn update_if_match_any(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    for tags in ["*", "\"9\", \"2\""] {
+        cli.put(format!("/19/undo/{id}"))
+            .header("If-Match", tags)
+            .body_json(&json!({"author": "Santa", "quote": "Again"}))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+}
+
+async fn delete_if_match(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    update(&cli, id, "Santa", "Merry Christmas").await;
+
+    cli.delete(format!("/19/remove/{id}"))
+        .header("If-Match", "\"1\"")
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+    cli.get(format!("/19/cite/{id}"))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    cli.delete(format!("/19/remove/{id}"))
+        .header("If-Match", "\"2\"")
+        .send()
+        .await
+        .assert_status_is_ok();
+}
+
+async fn if_match_missing_quote(cli: TestClient<impl Endpoint>) {
+    let id = "00000000-0000-0000-0000-000000000000";
+    cli.put(format!("/19/undo/{id}"))
+        .header("If-Match", "\"1\"")
+        .body_json(&json!({"author": "a", "quote": "b"}))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.delete(format!("/19/remove/{id}"))
+        .header("If-Match", "\"1\"")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn search(cli: &TestClient<impl Endpoint>, params: &[(&str, &str)]) -> Value {
+    let mut req = cli.get("/19/search");
+    for (name, value) in params {
+        req = req.query(*name, value);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+fn result_quotes(body: &Value) -> Vec<Value> {
+    body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["quote"]["quote"].clone())
+        .collect()
+}
+
+async fn search_text(cli: TestClient<impl Endpoint>) {
+    draft(&cli, "Santa", "Cookies and milk, please").await;
+    draft(&cli, "Rudolph", "My nose glows").await;
+    draft(&cli, "Elf", "Milk is the best").await;
+
+    let body = search(&cli, &[("q", "milk")]).await;
+    let mut found = result_quotes(&body);
+    found.sort_by_key(|quote| quote.to_string());
+    assert_eq!(found, ["Cookies and milk, please", "Milk is the best"]);
+    for hit in body["results"].as_array().unwrap() {
+        assert!(hit["rank"].as_f64().unwrap() > 0.0);
+        assert!(hit["snippet"].as_str().unwrap().contains("<mark>"));
+    }
+
+    let body = search(&cli, &[("q", "reindeer")]).await;
+    assert_eq!(body["results"], json!([]));
+    assert_eq!(body["next_token"], Value::Null);
+}
+
+async fn search_ranks_authors_higher(cli: TestClient<impl Endpoint>) {
+    draft(&cli, "Elf", "I like milk").await;
+    draft(&cli, "Milk Maid", "Hello there").await;
+
+    let body = search(&cli, &[("q", "milk")]).await;
+    assert_eq!(result_quotes(&body), ["Hello there", "I like milk"]);
+}
+
+async fn search_without_text(cli: TestClient<impl Endpoint>) {
+    let first = draft(&cli, "Santa", "Ho ho ho!").await;
+    let second = draft(&cli, "Elf", "Back to work").await;
+
+    let body = search(&cli, &[]).await;
+    let hits = body["results"].as_array().unwrap();
+    assert_eq!(hits[0]["quote"], first);
+    assert_eq!(hits[1]["quote"], second);
+    assert_eq!(hits[0]["rank"], Value::Null);
+    assert_eq!(hits[0]["snippet"], Value::Null);
+}
+
+async fn search_filters(cli: TestClient<impl Endpoint>) {
+    draft(&cli, "Santa", "Ho ho ho!").await;
+    let second = draft(&cli, "Elf", "Back to work").await;
+    let third = draft(&cli, "santa", "Where are my reindeer?").await;
+    update(&cli, second["id"].as_str().unwrap(), "Elf", "Back to work!").await;
+
+    let body = search(&cli, &[("author", "SANTA")]).await;
+    assert_eq!(
+        result_quotes(&body),
+        ["Ho ho ho!", "Where are my reindeer?"]
+    );
+
+    let body = search(&cli, &[("min_version", "2")]).await;
+    assert_eq!(result_quotes(&body), ["Back to work!"]);
+
+    let from = second["created_at"].as_str().unwrap();
+    let body = search(&cli, &[("from", from)]).await;
+    assert_eq!(
+        result_quotes(&body),
+        ["Back to work!", "Where are my reindeer?"]
+    );
+
+    let to = third["created_at"].as_str().unwrap();
+    let body = search(&cli, &[("to", to)]).await;
+    assert_eq!(result_quotes(&body), ["Ho ho ho!", "Back to work!"]);
+
+    let body = search(&cli, &[("from", from), ("to", to), ("author", "elf")]).await;
+    assert_eq!(result_quotes(&body), ["Back to work!"]);
+
+    let body = search(&cli, &[("q", "reindeer"), ("author", "Elf")]).await;
+    assert_eq!(body["results"], json!([]));
+}
+
+async fn search_pagination(cli: TestClient<impl Endpoint>) {
+    let mut drafted = Vec::new();
+    for i in 0..5 {
+        drafted.push(draft(&cli, "Elf", &format!("Cookie number {i}")).await);
+    }
+    draft(&cli, "Elf", "Milk").await;
+
+    let mut seen = Vec::new();
+    let mut token: Option<String> = None;
+    for page in 1..=3 {
+        let mut params = vec![("q", "cookie"), ("limit", "2")];
+        if let Some(token) = &token {
+            params.push(("token", token));
+        }
+        let body = search(&cli, &params).await;
+        assert_eq!(body["page"], page);
+        seen.extend(
+            body["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|hit| hit["quote"].clone()),
+        );
+        token = body["next_token"].as_str().map(str::to_owned);
+        assert_eq!(token.is_some(), page < 3);
+    }
+    assert_eq!(seen, drafted);
+}
+
+async fn search_tokens_stay_with_their_search(cli: TestClient<impl Endpoint>) {
+    for i in 0..4 {
+        draft(&cli, "Elf", &format!("Cookie number {i}")).await;
+    }
+    let body = search(&cli, &[("q", "cookie"), ("limit", "1")]).await;
+    let search_token = body["next_token"].as_str().unwrap();
+    let body = list(&cli, None).await;
+    let list_token = body["next_token"].as_str().unwrap();
+
+    for (path, query, token) in [
+        ("/19/search", Some("milk"), search_token),
+        ("/19/search", Some("cookie"), list_token),
+        ("/19/list", None, search_token),
+    ] {
+        let mut req = cli.get(path).query("token", &token);
+        if let Some(query) = query {
+            req = req.query("q", &query);
+        }
+        req.send().await.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+async fn search_bad_date_range(cli: TestClient<impl Endpoint>) {
+    cli.get("/19/search")
+        .query("from", &"2024-12-25T00:00:00Z")
+        .query("to", &"2024-12-24T00:00:00Z")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.get("/19/search")
+        .query("limit", &0)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn import(
+    cli: &TestClient<impl Endpoint>,
+    content_type: &str,
+    body: &str,
+    atomic: bool,
+) -> (StatusCode, Value) {
+    let resp = cli
+        .post("/19/import")
+        .query("atomic", &atomic)
+        .content_type(content_type)
+        .body(body.to_owned())
+        .send()
+        .await;
+    let status = resp.0.status();
+    (status, resp.json().await.value().deserialize())
+}
+
+async fn export(cli: &TestClient<impl Endpoint>, accept: &str) -> String {
+    let resp = cli.get("/19/export").header("Accept", accept).send().await;
+    resp.assert_status_is_ok();
+    resp.0.into_body().into_string().await.unwrap()
+}
+
+async fn all_quotes(cli: &TestClient<impl Endpoint>) -> Vec<Value> {
+    let body = list_with_limit(cli, None, Some(100)).await;
+    body["quotes"].as_array().unwrap().clone()
+}
+
+async fn import_ndjson(cli: TestClient<impl Endpoint>) {
+    let body = [
+        r#"{"author": "Santa", "quote": "Ho ho ho!"}"#,
+        r#"{"author": "Grinch"}"#,
+        "",
+        r#"{"author": "Elf", "quote": "Back to work", "version": 7}"#,
+        r#"{"author": " ", "quote": "Who said this?"}"#,
+        "not json",
+    ]
+    .join("\n");
+    let (status, report) = import(&cli, "application/x-ndjson", &body, false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"], 2);
+    let lines: Vec<_> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["line"].clone())
+        .collect();
+    assert_eq!(lines, [2, 5, 6]);
+
+    let quotes = all_quotes(&cli).await;
+    assert_eq!(quotes.len(), 2);
+    assert_eq!(quotes[0]["quote"], "Ho ho ho!");
+    assert_eq!(quotes[1]["quote"], "Back to work");
+    assert_eq!(quotes[1]["version"], 1);
+}
+
+async fn import_csv(cli: TestClient<impl Endpoint>) {
+    let body = "quote,author\n\
+        \"Cookies, milk, and more cookies\",Santa\n\
+        Back to work,\n\
+        \"He said \"\"hi\"\"\",Elf\n\
+        too,many,fields\n";
+    let (status, report) = import(&cli, "text/csv", body, false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"], 2);
+    let lines: Vec<_> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["line"].clone())
+        .collect();
+    assert_eq!(lines, [3, 5]);
+
+    let quotes = all_quotes(&cli).await;
+    assert_eq!(quotes[0]["author"], "Santa");
+    assert_eq!(quotes[0]["quote"], "Cookies, milk, and more cookies");
+    assert_eq!(quotes[1]["quote"], "He said \"hi\"");
+}
+
+async fn import_atomic(cli: TestClient<impl Endpoint>) {
+    let body = "author,quote\nSanta,Ho ho ho!\nGrinch,\n";
+    let (status, report) = import(&cli, "text/csv", body, true).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["errors"][0]["line"], 3);
+    assert!(all_quotes(&cli).await.is_empty());
+
+    let body = "author,quote\nSanta,Ho ho ho!\nElf,Back to work\n";
+    let (status, report) = import(&cli, "text/csv", body, true).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"], 2);
+}
+
+async fn import_csv_needs_columns(cli: TestClient<impl Endpoint>) {
+    let resp = cli
+        .post("/19/import")
+        .content_type("text/csv")
+        .body("name,text\nSanta,Ho ho ho!\n")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_content_type("application/problem+json");
+
+    cli.post("/19/import")
+        .content_type("application/xml")
+        .body("<quote/>")
+        .send()
+        .await
+        .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+async fn export_formats(cli: TestClient<impl Endpoint>) {
+    let santa = draft_tagged(&cli, "Santa", "Cookies, please", &["food", "wishes"]).await;
+    let elf = draft(&cli, "Elf", "Back to work").await;
+
+    let resp = cli.get("/19/export").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_content_type("application/json");
+    resp.assert_json(json!([santa, elf])).await;
+
+    let ndjson = export(&cli, "application/x-ndjson").await;
+    let lines: Vec<Value> = ndjson
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines, [santa.clone(), elf.clone()]);
+
+    let csv = export(&cli, "text/csv").await;
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,author,quote,version,created_at,tags")
+    );
+    let first = lines.next().unwrap();
+    assert!(first.starts_with(&format!(
+        "{},Santa,\"Cookies, please\",1,",
+        santa["id"].as_str().unwrap()
+    )));
+    assert!(first.ends_with(",food;wishes"));
+    assert!(lines.next().unwrap().ends_with(','));
+
+    // Quality values win over listing order, and media types are matched
+    // ignoring case.
+    for (accept, content_type) in [
+        ("application/json;q=0.5, text/csv", "text/csv"),
+        ("Text/CSV", "text/csv"),
+        (
+            "application/json; Q=0.2, application/X-NDJSON; q=0.9",
+            "application/x-ndjson",
+        ),
+        // The most specific range decides.
+        (
+            "*/*;q=0.1, application/x-ndjson;q=0.5",
+            "application/x-ndjson",
+        ),
+        (
+            "application/*, application/json;q=0",
+            "application/x-ndjson",
+        ),
+        ("text/csv;q=0.5, */*", "application/json"),
+    ] {
+        let resp = cli.get("/19/export").header("Accept", accept).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type(content_type);
+    }
+}
+
+async fn export_in_batches(cli: TestClient<impl Endpoint>) {
+    let body: Vec<String> = (0..1201)
+        .map(|i| json!({"author": "Elf", "quote": format!("Quote {i}")}).to_string())
+        .collect();
+    let (status, _) = import(&cli, "application/x-ndjson", &body.join("\n"), true).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let ndjson = export(&cli, "application/x-ndjson").await;
+    let quotes: Vec<String> = ndjson
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<Value>(line).unwrap()["quote"]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    let expected: Vec<String> = (0..1201).map(|i| format!("Quote {i}")).collect();
+    assert_eq!(quotes, expected);
+
+    let json: Vec<Value> = serde_json::from_str(&export(&cli, "application/json").await).unwrap();
+    assert_eq!(json.len(), 1201);
+}
+
+async fn export_not_acceptable(cli: TestClient<impl Endpoint>) {
+    let resp = cli
+        .get("/19/export")
+        .header("Accept", "application/xml, application/json;q=0")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_ACCEPTABLE);
+    resp.assert_content_type("application/problem+json");
+
+    // A more specific range turns down what a wider one would take.
+    for accept in ["text/*, text/csv;q=0", "not a media type"] {
+        cli.get("/19/export")
+            .header("Accept", accept)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_ACCEPTABLE);
+    }
+}
+
+async fn export_reimports(cli: TestClient<impl Endpoint>) {
+    draft_tagged(&cli, "Santa", "Cookies, please", &["food", "wishes"]).await;
+    draft(&cli, "Elf", "Back to work").await;
+
+    // Each round doubles the quotes, tags included.
+    for (format, imported) in [("text/csv", 2), ("application/x-ndjson", 4)] {
+        let exported = export(&cli, format).await;
+        let (status, report) = import(&cli, format, &exported, true).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["imported"], imported);
+    }
+    let quotes = all_quotes(&cli).await;
+    assert_eq!(quotes.len(), 8);
+    for quote in quotes {
+        let tags = match quote["author"].as_str().unwrap() {
+            "Santa" => json!(["food", "wishes"]),
+            _ => json!([]),
+        };
+        assert_eq!(quote["tags"], tags);
+    }
+}
+
+/// A server-sent event: its type, id and data.
+type Event = (String, i64, Value);
+
+/// Reads events off an event stream until `count` have arrived.
+async fn read_events(
+    body: &mut (impl Stream<Item = std::io::Result<impl AsRef<[u8]>>> + Unpin),
+    buffer: &mut String,
+    count: usize,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    while events.len() < count {
+        if let Some(end) = buffer.find("\n\n") {
+            let block: String = buffer.drain(..end + 2).collect();
+            let (mut kind, mut id, mut data) = (String::new(), 0, None);
+            for line in block.lines() {
+                if let Some(value) = line.strip_prefix("event: ") {
+                    kind = value.to_owned();
+                } else if let Some(value) = line.strip_prefix("id: ") {
+                    id = value.parse().unwrap();
+                } else if let Some(value) = line.strip_prefix("data: ") {
+                    data = Some(serde_json::from_str(value).unwrap());
+                }
+            }
+            // Keep-alives carry no data.
+            if let Some(data) = data {
+                events.push((kind, id, data));
+            }
+            continue;
+        }
+
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("Timed out waiting for an event")
+            .expect("Event stream ended")
+            .unwrap();
+        buffer.push_str(std::str::from_utf8(chunk.as_ref()).unwrap());
+    }
+
+    events
+}
+
+async fn events_stream_changes(cli: TestClient<impl Endpoint>) {
+    let resp = cli.get("/19/events").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_content_type("text/event-stream");
+    let mut body = Box::pin(resp.0.into_body().into_bytes_stream());
+    let mut buffer = String::new();
+
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    let updated = update(&cli, id, "Santa", "Merry Christmas").await;
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let events = read_events(&mut body, &mut buffer, 4).await;
+    let kinds: Vec<_> = events.iter().map(|(kind, _, _)| kind.as_str()).collect();
+    assert_eq!(kinds, ["created", "updated", "deleted", "reset"]);
+    // Events leave tags out.
+    let untagged = |mut quote: Value| {
+        quote.as_object_mut().unwrap().remove("tags");
+        quote
+    };
+    assert_eq!(events[0].2, untagged(quote));
+    assert_eq!(events[1].2, untagged(updated.clone()));
+    assert_eq!(events[2].2, untagged(updated));
+    assert_eq!(events[3].2, json!({}));
+    assert!(events.windows(2).all(|pair| pair[0].1 < pair[1].1));
+}
+
+async fn events_follow_the_trash(cli: TestClient<impl Endpoint>) {
+    let resp = cli.get("/19/events").send().await;
+    let mut body = Box::pin(resp.0.into_body().into_bytes_stream());
+    let mut buffer = String::new();
+
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    cli.post(format!("/19/restore/{id}")).send().await;
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    // Purging isn't news, as the quote was already gone.
+    cli.delete(format!("/19/purge/{id}")).send().await;
+    draft(&cli, "Elf", "Back to work").await;
+
+    let events = read_events(&mut body, &mut buffer, 5).await;
+    let kinds: Vec<_> = events.iter().map(|(kind, _, _)| kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        ["created", "deleted", "created", "deleted", "created"]
+    );
+    assert!(events[..4].iter().all(|(_, _, quote)| quote["id"] == id));
+    assert_eq!(events[4].2["quote"], "Back to work");
+}
+
+async fn events_resume_from_last_event_id(cli: TestClient<impl Endpoint>) {
+    let resp = cli.get("/19/events").send().await;
+    let mut body = Box::pin(resp.0.into_body().into_bytes_stream());
+    let mut buffer = String::new();
+    draft(&cli, "Santa", "First").await;
+    draft(&cli, "Santa", "Second").await;
+    let seen = read_events(&mut body, &mut buffer, 2).await;
+    drop(body);
+
+    // Missed while disconnected.
+    draft(&cli, "Santa", "Third").await;
+
+    let resp = cli
+        .get("/19/events")
+        .header("Last-Event-ID", seen[0].1)
+        .send()
+        .await;
+    let mut body = Box::pin(resp.0.into_body().into_bytes_stream());
+    let mut buffer = String::new();
+    let replayed = read_events(&mut body, &mut buffer, 2).await;
+    assert_eq!(replayed[0], seen[1]);
+    assert_eq!(replayed[1].2["quote"], "Third");
+
+    // Then it carries on live.
+    draft(&cli, "Santa", "Fourth").await;
+    let live = read_events(&mut body, &mut buffer, 1).await;
+    assert_eq!(live[0].2["quote"], "Fourth");
+    assert!(live[0].1 > replayed[1].1);
+}
+
+async fn draft_tagged(
+    cli: &TestClient<impl Endpoint>,
+    author: &str,
+    quote: &str,
+    tags: &[&str],
+) -> Value {
+    let resp = cli
+        .post("/19/draft")
+        .body_json(&json!({"author": author, "quote": quote, "tags": tags}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    resp.json().await.value().deserialize()
+}
+
+async fn list_tagged(
+    cli: &TestClient<impl Endpoint>,
+    tag: &str,
+    token: Option<&str>,
+    limit: usize,
+) -> Value {
+    let mut req = cli
+        .get("/19/list")
+        .query("tag", &tag)
+        .query("limit", &limit);
+    if let Some(token) = token {
+        req = req.query("token", &token);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn tags_normalized(cli: TestClient<impl Endpoint>) {
+    let quote = draft_tagged(&cli, "Santa", "Ho ho ho!", &[" Joy", "winter", "JOY"]).await;
+    assert_eq!(quote["tags"], json!(["joy", "winter"]));
+
+    let untagged = draft(&cli, "Elf", "Back to work").await;
+    assert_eq!(untagged["tags"], json!([]));
+
+    let resp = cli
+        .get(format!("/19/cite/{}", quote["id"].as_str().unwrap()))
+        .send()
+        .await;
+    resp.assert_json(&quote).await;
+}
+
+async fn tags_blank(cli: TestClient<impl Endpoint>) {
+    let errors = draft_errors(
+        &cli,
+        json!({"author": "Santa", "quote": "Ho ho ho!", "tags": ["joy", " "]}),
+    )
+    .await;
+    assert_eq!(
+        errors,
+        json!([{"field": "tags[1]", "message": "must not be blank"}])
+    );
+
+    let many: Vec<_> = (0..21).map(|i| format!("tag{i}")).collect();
+    let errors = draft_errors(
+        &cli,
+        json!({"author": "Santa", "quote": "Ho ho ho!", "tags": many}),
+    )
+    .await;
+    assert_eq!(errors[0]["field"], "tags");
+    assert!(all_quotes(&cli).await.is_empty());
+}
+
+async fn update_tags(cli: TestClient<impl Endpoint>) {
+    let quote = draft_tagged(&cli, "Santa", "Ho ho ho!", &["joy"]).await;
+    let id = quote["id"].as_str().unwrap();
+
+    // Leaving tags out keeps them.
+    let updated = update(&cli, id, "Santa", "Ho ho ho ho!").await;
+    assert_eq!(updated["tags"], json!(["joy"]));
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Santa", "quote": "Ho!", "tags": ["Winter", "cheer"]}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let retagged: Value = resp.json().await.value().deserialize();
+    assert_eq!(retagged["tags"], json!(["cheer", "winter"]));
+
+    let resp = cli
+        .put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Santa", "quote": "Ho!", "tags": []}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let cleared: Value = resp.json().await.value().deserialize();
+    assert_eq!(cleared["tags"], json!([]));
+    assert_eq!(cleared["version"], 4);
+}
+
+async fn tag_counts(cli: TestClient<impl Endpoint>) {
+    draft_tagged(&cli, "Santa", "Ho ho ho!", &["joy", "winter"]).await;
+    draft_tagged(&cli, "Elf", "Back to work", &["work"]).await;
+    let gone = draft_tagged(&cli, "Grinch", "Bah!", &["winter", "grumpy"]).await;
+    draft_tagged(&cli, "Rudolph", "My nose glows", &["winter"]).await;
+    cli.delete(format!("/19/remove/{}", gone["id"].as_str().unwrap()))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let resp = cli.get("/19/tags").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(json!([
+        {"name": "winter", "count": 2},
+        {"name": "joy", "count": 1},
+        {"name": "work", "count": 1},
+    ]))
+    .await;
+
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.get("/19/tags")
+        .send()
+        .await
+        .assert_json(json!([]))
+        .await;
+}
+
+async fn list_by_tag(cli: TestClient<impl Endpoint>) {
+    let mut tagged = Vec::new();
+    for i in 0..5 {
+        tagged.push(draft_tagged(&cli, "Elf", &format!("Tagged {i}"), &["toys"]).await);
+        draft(&cli, "Elf", &format!("Untagged {i}")).await;
+    }
+
+    let mut seen = Vec::new();
+    let mut token: Option<String> = None;
+    for page in 1..=3 {
+        let body = list_tagged(&cli, "Toys", token.as_deref(), 2).await;
+        assert_eq!(body["page"], page);
+        seen.extend(body["quotes"].as_array().unwrap().iter().cloned());
+        token = body["next_token"].as_str().map(str::to_owned);
+        assert_eq!(token.is_some(), page < 3);
+    }
+    assert_eq!(seen, tagged);
+
+    let body = list_tagged(&cli, "unused", None, 2).await;
+    assert_eq!(body["quotes"], json!([]));
+}
+
+async fn list_tag_tokens_stay_with_their_tag(cli: TestClient<impl Endpoint>) {
+    for i in 0..3 {
+        draft_tagged(&cli, "Elf", &format!("Quote {i}"), &["toys"]).await;
+    }
+    let body = list_tagged(&cli, "toys", None, 2).await;
+    let token = body["next_token"].as_str().unwrap();
+
+    cli.get("/19/list")
+        .query("tag", &"coal")
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.get("/19/list")
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn trash(cli: &TestClient<impl Endpoint>, token: Option<&str>, limit: usize) -> Value {
+    let mut req = cli.get("/19/trash").query("limit", &limit);
+    if let Some(token) = token {
+        req = req.query("token", &token);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn delete_moves_to_trash(cli: TestClient<impl Endpoint>) {
+    let kept = draft_tagged(&cli, "Santa", "Ho ho ho!", &["joy"]).await;
+    let gone = draft_tagged(&cli, "Grinch", "Bah!", &["joy"]).await;
+    let id = gone["id"].as_str().unwrap();
+    let resp = cli.delete(format!("/19/remove/{id}")).send().await;
+    resp.assert_status_is_ok();
+    let deleted: Value = resp.json().await.value().deserialize();
+
+    cli.get(format!("/19/cite/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.delete(format!("/19/remove/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.put(format!("/19/undo/{id}"))
+        .body_json(&json!({"author": "Grinch", "quote": "Humbug!"}))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    assert_eq!(all_quotes(&cli).await, [kept]);
+    assert!(result_quotes(&search(&cli, &[("q", "bah")]).await).is_empty());
+    cli.get("/19/tags")
+        .send()
+        .await
+        .assert_json(json!([{"name": "joy", "count": 1}]))
+        .await;
+
+    let body = trash(&cli, None, 10).await;
+    assert_eq!(body["quotes"], json!([deleted]));
+}
+
+async fn restore_from_trash(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+
+    cli.post(format!("/19/restore/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    let resp = cli.post(format!("/19/restore/{id}")).send().await;
+    resp.assert_status_is_ok();
+    resp.assert_header("ETag", "\"1\"");
+    resp.assert_json(&quote).await;
+
+    assert_eq!(all_quotes(&cli).await, [quote]);
+    assert_eq!(trash(&cli, None, 10).await["quotes"], json!([]));
+
+    cli.post("/19/restore/00000000-0000-0000-0000-000000000000")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn trash_pagination(cli: TestClient<impl Endpoint>) {
+    let mut trashed = Vec::new();
+    for i in 0..5 {
+        let quote = draft(&cli, "Elf", &format!("Quote {i}")).await;
+        if i % 2 == 0 {
+            let resp = cli
+                .delete(format!("/19/remove/{}", quote["id"].as_str().unwrap()))
+                .send()
+                .await;
+            trashed.push(resp.json().await.value().deserialize::<Value>());
+        }
+    }
+
+    let first = trash(&cli, None, 2).await;
+    let token = first["next_token"].as_str().unwrap();
+    let second = trash(&cli, Some(token), 2).await;
+    assert_eq!(second["page"], 2);
+    assert_eq!(second["next_token"], Value::Null);
+    let seen: Vec<Value> = [&first, &second]
+        .iter()
+        .flat_map(|body| body["quotes"].as_array().unwrap().clone())
+        .collect();
+    assert_eq!(seen, trashed);
+
+    // Tokens don't carry over between the trash and the list.
+    cli.get("/19/list")
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    let list_token = list_with_limit(&cli, None, Some(1)).await["next_token"].clone();
+    cli.get("/19/trash")
+        .query("token", &list_token.as_str().unwrap())
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn purge(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    let id = quote["id"].as_str().unwrap();
+    update(&cli, id, "Santa", "Merry Christmas").await;
+
+    // Only quotes in the trash can be purged.
+    cli.delete(format!("/19/purge/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    let resp = cli.delete(format!("/19/purge/{id}")).send().await;
+    resp.assert_status_is_ok();
+    let purged: Value = resp.json().await.value().deserialize();
+    assert_eq!(purged["quote"], "Merry Christmas");
+
+    assert_eq!(trash(&cli, None, 10).await["quotes"], json!([]));
+    cli.get(format!("/19/versions/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    for req in [
+        cli.delete(format!("/19/purge/{id}")),
+        cli.post(format!("/19/restore/{id}")),
+    ] {
+        req.send().await.assert_status(StatusCode::NOT_FOUND);
+    }
+}
+
+async fn reset_needs_confirmation(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+
+    cli.post("/19/reset")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.post("/19/reset")
+        .query("confirm", &false)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    assert_eq!(all_quotes(&cli).await, [quote]);
+}
+
+async fn random_quote(cli: TestClient<impl Endpoint>) {
+    cli.get("/19/random")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    let mut quotes = Vec::new();
+    for i in 0..3 {
+        quotes.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+    }
+    let gone = draft(&cli, "Grinch", "Bah!").await;
+    cli.delete(format!("/19/remove/{}", gone["id"].as_str().unwrap()))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let mut seen = Vec::new();
+    for _ in 0..100 {
+        let resp = cli.get("/19/random").send().await;
+        resp.assert_status_is_ok();
+        let quote: Value = resp.json().await.value().deserialize();
+        assert!(quotes.contains(&quote), "{quote}");
+        if !seen.contains(&quote) {
+            seen.push(quote);
+        }
+    }
+    assert_eq!(seen.len(), quotes.len());
+}
+
+async fn random_quote_by_author(cli: TestClient<impl Endpoint>) {
+    let santa = draft(&cli, "Santa", "Ho ho ho!").await;
+    for i in 0..5 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+
+    for _ in 0..10 {
+        let resp = cli.get("/19/random").query("author", &"santa").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_json(&santa).await;
+    }
+    cli.get("/19/random")
+        .query("author", &"Grinch")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+async fn quote_of_the_day(cli: TestClient<impl Endpoint>) {
+    cli.get("/19/today")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    let mut quotes = Vec::new();
+    for i in 0..10 {
+        quotes.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+    }
+
+    let todays = today(&cli, None).await;
+    assert!(quotes.contains(&todays["quote"]));
+    assert_eq!(today(&cli, None).await, todays);
+
+    let mut picks = Vec::new();
+    for day in 1..=24 {
+        let date = format!("2024-12-{day:02}");
+        let pick = today(&cli, Some(&date)).await;
+        assert_eq!(pick["date"], date);
+        assert_eq!(today(&cli, Some(&date)).await, pick);
+        if !picks.contains(&pick["quote"]) {
+            picks.push(pick["quote"].clone());
+        }
+    }
+    // Not every day should get the same quote.
+    assert!(picks.len() > 1);
+
+    cli.get("/19/today")
+        .query("date", &"2024-13-01")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn quote_of_the_day_holds(cli: TestClient<impl Endpoint>) {
+    let mut quotes = Vec::new();
+    for i in 0..5 {
+        quotes.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+    }
+    let todays = today(&cli, None).await;
+
+    // Quotes coming and going leave the pick be.
+    for i in 5..10 {
+        quotes.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+    }
+    assert_eq!(today(&cli, None).await, todays);
+    let other = quotes
+        .iter()
+        .find(|quote| **quote != todays["quote"])
+        .unwrap();
+    let other = other["id"].as_str().unwrap();
+    cli.delete(format!("/19/remove/{other}")).send().await;
+    assert_eq!(today(&cli, None).await, todays);
+    cli.post(format!("/19/restore/{other}")).send().await;
+    assert_eq!(today(&cli, None).await, todays);
+
+    // Unless the pick itself goes to the trash. The next one holds too, even
+    // once the first is back.
+    let id = todays["quote"]["id"].as_str().unwrap();
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    let next = today(&cli, None).await;
+    assert_ne!(next["quote"]["id"], todays["quote"]["id"]);
+    cli.post(format!("/19/restore/{id}")).send().await;
+    assert_eq!(today(&cli, None).await, next);
+}
+
+async fn today(cli: &TestClient<impl Endpoint>, date: Option<&str>) -> Value {
+    let mut req = cli.get("/19/today");
+    if let Some(date) = date {
+        req = req.query("date", &date);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn authors(cli: &TestClient<impl Endpoint>) -> Vec<Value> {
+    let resp = cli.get("/19/authors").send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn author(
+    cli: &TestClient<impl Endpoint>,
+    id: &str,
+    token: Option<&str>,
+    limit: usize,
+) -> Value {
+    let mut req = cli.get(format!("/19/authors/{id}")).query("limit", &limit);
+    if let Some(token) = token {
+        req = req.query("token", &token);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+async fn authors_match_spellings(cli: TestClient<impl Endpoint>) {
+    let first = draft(&cli, "Santa", "Ho ho ho!").await;
+    draft(&cli, "Elf", "Back to work").await;
+    let last = draft(&cli, "SANTA", "Merry Christmas").await;
+    // Quotes keep their own spelling.
+    assert_eq!(last["author"], "SANTA");
+    assert!(last.get("author_id").is_none());
+
+    let listed = authors(&cli).await;
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0]["name"], "Santa");
+    assert_eq!(listed[0]["quote_count"], 2);
+    assert_eq!(listed[0]["first_quote_at"], first["created_at"]);
+    assert_eq!(listed[0]["last_quote_at"], last["created_at"]);
+    assert_eq!(listed[1]["name"], "Elf");
+    assert_eq!(listed[1]["quote_count"], 1);
+
+    // Changing the author moves the quote over.
+    update(
+        &cli,
+        last["id"].as_str().unwrap(),
+        " elf ",
+        "Merry Christmas",
+    )
+    .await;
+    let listed = authors(&cli).await;
+    assert_eq!(listed[0]["name"], "Elf");
+    assert_eq!(listed[0]["quote_count"], 2);
+    assert_eq!(listed[1]["name"], "Santa");
+    assert_eq!(listed[1]["quote_count"], 1);
+
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+    assert_eq!(authors(&cli).await, Vec::<Value>::new());
+}
+
+async fn authors_leave_out_the_trash(cli: TestClient<impl Endpoint>) {
+    draft(&cli, "Santa", "Ho ho ho!").await;
+    let gone = draft(&cli, "Grinch", "Bah!").await;
+    cli.delete(format!("/19/remove/{}", gone["id"].as_str().unwrap()))
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let listed = authors(&cli).await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["name"], "Santa");
+
+    // The author is still there, just without quotes to count.
+    let grinch = draft(&cli, "Grinch", "Humbug").await;
+    let listed = authors(&cli).await;
+    let id = listed
+        .iter()
+        .find(|author| author["name"] == "Grinch")
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    cli.delete(format!("/19/remove/{}", grinch["id"].as_str().unwrap()))
+        .send()
+        .await
+        .assert_status_is_ok();
+    let profile = author(&cli, &id, None, 10).await;
+    assert_eq!(profile["name"], "Grinch");
+    assert_eq!(profile["quote_count"], 0);
+    assert_eq!(profile["first_quote_at"], Value::Null);
+    assert_eq!(profile["quotes"], json!([]));
+}
+
+async fn author_profile_pagination(cli: TestClient<impl Endpoint>) {
+    let mut quotes = Vec::new();
+    for i in 0..5 {
+        quotes.push(draft(&cli, "Elf", &format!("Quote {i}")).await);
+        draft(&cli, "Santa", &format!("Ho {i}")).await;
+    }
+    let id = authors(&cli).await[0]["id"].as_str().unwrap().to_owned();
+
+    let first = author(&cli, &id, None, 2).await;
+    assert_eq!(first["name"], "Elf");
+    assert_eq!(first["quote_count"], 5);
+    assert_eq!(first["page"], 1);
+    assert_eq!(first["quotes"], json!(quotes[..2]));
+    let second = author(&cli, &id, first["next_token"].as_str(), 2).await;
+    assert_eq!(second["page"], 2);
+    assert_eq!(second["quotes"], json!(quotes[2..4]));
+    let third = author(&cli, &id, second["next_token"].as_str(), 2).await;
+    assert_eq!(third["quotes"], json!(quotes[4..]));
+    assert_eq!(third["next_token"], Value::Null);
+}
+
+async fn author_tokens_stay_with_their_author(cli: TestClient<impl Endpoint>) {
+    for i in 0..3 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+    draft(&cli, "Santa", "Ho ho ho!").await;
+    let listed = authors(&cli).await;
+    let elf = listed[0]["id"].as_str().unwrap();
+    let santa = listed[1]["id"].as_str().unwrap();
+    let body = author(&cli, elf, None, 2).await;
+    let token = body["next_token"].as_str().unwrap();
+
+    for req in [
+        cli.get(format!("/19/authors/{santa}")),
+        cli.get("/19/list"),
+        cli.get("/19/trash"),
+    ] {
+        req.query("token", &token)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+    let token = list_with_limit(&cli, None, Some(2)).await["next_token"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    cli.get(format!("/19/authors/{elf}"))
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn missing_author(cli: TestClient<impl Endpoint>) {
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+
+    // Quote ids aren't author ids.
+    for id in [
+        "00000000-0000-0000-0000-000000000000",
+        quote["id"].as_str().unwrap(),
+    ] {
+        cli.get(format!("/19/authors/{id}"))
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
+
+async fn audit_log(cli: &TestClient<impl Endpoint>, query: &[(&str, &str)]) -> Value {
+    let mut req = cli.get("/19/audit");
+    for (name, value) in query {
+        req = req.query(*name, value);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().deserialize()
+}
+
+fn operations(log: &Value) -> Vec<&str> {
+    log["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["operation"].as_str().unwrap())
+        .collect()
+}
+
+fn actor_token(sub: &str, secret: &str) -> String {
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &json!({"sub": sub}),
+        &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+async fn audit_records_changes(cli: TestClient<impl Endpoint>) {
+    let drafted = draft_tagged(&cli, "Santa", "Ho ho ho!", &["joy"]).await;
+    let id = drafted["id"].as_str().unwrap();
+    let updated = update(&cli, id, "Santa", "Merry Christmas").await;
+    let reverted: Value = cli
+        .post(format!("/19/versions/{id}/1/revert"))
+        .send()
+        .await
+        .json()
+        .await
+        .value()
+        .deserialize();
+    let trashed: Value = cli
+        .delete(format!("/19/remove/{id}"))
+        .send()
+        .await
+        .json()
+        .await
+        .value()
+        .deserialize();
+    cli.post(format!("/19/restore/{id}"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.delete(format!("/19/remove/{id}")).send().await;
+    cli.delete(format!("/19/purge/{id}"))
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/19/reset")
+        .query("confirm", &true)
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    // The log outlives the reset.
+    let log = audit_log(&cli, &[("limit", "100")]).await;
+    assert_eq!(
+        operations(&log),
+        ["reset", "purge", "remove", "restore", "remove", "revert", "undo", "draft"]
+    );
+    let entries = log["entries"].as_array().unwrap();
+    assert!(entries
+        .windows(2)
+        .all(|w| w[0]["id"].as_i64() > w[1]["id"].as_i64()));
+    assert_eq!(entries[0]["quote_id"], Value::Null);
+    assert_eq!(entries[0]["before"], Value::Null);
+    assert_eq!(entries[0]["after"], Value::Null);
+    for entry in &entries[1..] {
+        assert_eq!(entry["quote_id"], id);
+        assert_eq!(entry["actor"], Value::Null);
+    }
+
+    let draft = &entries[7];
+    assert_eq!(draft["before"], Value::Null);
+    assert_eq!(draft["after"], drafted);
+    let undo = &entries[6];
+    assert_eq!(undo["before"], drafted);
+    assert_eq!(undo["after"], updated);
+    let revert = &entries[5];
+    assert_eq!(revert["before"], updated);
+    assert_eq!(revert["after"], reverted);
+    let remove = &entries[4];
+    assert_eq!(remove["before"], reverted);
+    assert_eq!(remove["after"], trashed);
+    let restore = &entries[3];
+    assert_eq!(restore["before"], trashed);
+    assert_eq!(restore["after"], reverted);
+    let purge = &entries[1];
+    assert_eq!(purge["before"]["quote"], "Ho ho ho!");
+    assert_eq!(purge["after"], Value::Null);
+}
+
+async fn audit_actor_and_client_ip(cli: TestClient<impl Endpoint>) {
+    // Nobody is trusted to say who they are, or where they're from.
+    cli.post("/19/draft")
+        .header("X-Actor", "elf")
+        .header("X-Forwarded-For", "203.0.113.7")
+        .body_json(&json!({"author": "Santa", "quote": "Ho ho ho!"}))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    // Unlike a bearer token.
+    cli.post("/19/draft")
+        .header(
+            "Authorization",
+            format!("Bearer {}", actor_token("santa", "actors")),
+        )
+        .header("X-Actor", "elf")
+        .body_json(&json!({"author": "Santa", "quote": "Merry Christmas"}))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let log = audit_log(&cli, &[]).await;
+    let entries = log["entries"].as_array().unwrap();
+    assert_eq!(entries[0]["actor"], "santa");
+    assert_eq!(entries[1]["actor"], Value::Null);
+    assert_eq!(entries[1]["client_ip"], Value::Null);
+}
+
+async fn audit_rejects_bad_tokens(cli: TestClient<impl Endpoint>) {
+    for token in [actor_token("grinch", "forged"), "garbage".to_owned()] {
+        cli.post("/19/draft")
+            .header("Authorization", format!("Bearer {token}"))
+            .body_json(&json!({"author": "Grinch", "quote": "Bah!"}))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    assert_eq!(all_quotes(&cli).await, Vec::<Value>::new());
+    assert_eq!(audit_log(&cli, &[]).await["entries"], json!([]));
+}
+
+async fn audit_skips_failed_changes(cli: TestClient<impl Endpoint>) {
+    let missing = "00000000-0000-0000-0000-000000000000";
+    cli.delete(format!("/19/remove/{missing}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.post("/19/draft")
+        .body_json(&json!({"author": "Santa"}))
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    cli.post("/19/reset")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    let quote = draft(&cli, "Santa", "Ho ho ho!").await;
+    cli.put(format!("/19/undo/{}", quote["id"].as_str().unwrap()))
+        .header("If-Match", "\"7\"")
+        .body_json(&json!({"author": "Santa", "quote": "Merry Christmas"}))
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+
+    assert_eq!(operations(&audit_log(&cli, &[]).await), ["draft"]);
+}
+
+async fn audit_records_imports(cli: TestClient<impl Endpoint>) {
+    let resp = cli
+        .post("/19/import")
+        .header(
+            "Authorization",
+            format!("Bearer {}", actor_token("santa", "actors")),
+        )
+        .content_type("application/x-ndjson")
+        .body("{\"author\": \"Santa\", \"quote\": \"Ho ho ho!\"}\n{\"author\": \"Elf\", \"quote\": \"Back to work\"}")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+
+    let quotes = all_quotes(&cli).await;
+    let log = audit_log(&cli, &[]).await;
+    assert_eq!(operations(&log), ["import", "import"]);
+    // Newest first, like the log.
+    for (entry, quote) in log["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .zip(quotes.iter().rev())
+    {
+        assert_eq!(entry["actor"], "santa");
+        assert_eq!(entry["quote_id"], quote["id"]);
+        assert_eq!(entry["before"], Value::Null);
+        assert_eq!(&entry["after"], quote);
+    }
+
+    // Nothing is logged when nothing is imported.
+    let (status, _) = import(&cli, "application/x-ndjson", "not json", true).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let log = audit_log(&cli, &[("operation", "import")]).await;
+    assert_eq!(log["entries"].as_array().unwrap().len(), 2);
+}
+
+async fn audit_filters(cli: TestClient<impl Endpoint>) {
+    let mut ids = Vec::new();
+    for actor in ["elf", "santa", "elf"] {
+        let resp = cli
+            .post("/19/draft")
+            .header(
+                "Authorization",
+                format!("Bearer {}", actor_token(actor, "actors")),
+            )
+            .body_json(&json!({"author": "Santa", "quote": format!("By {actor}")}))
+            .send()
+            .await;
+        let quote: Value = resp.json().await.value().deserialize();
+        ids.push(quote["id"].as_str().unwrap().to_owned());
+    }
+    cli.delete(format!("/19/remove/{}", ids[0]))
+        .header(
+            "Authorization",
+            format!("Bearer {}", actor_token("santa", "actors")),
+        )
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    let by_elf = audit_log(&cli, &[("actor", "elf")]).await;
+    assert_eq!(operations(&by_elf), ["draft", "draft"]);
+    let removes = audit_log(&cli, &[("operation", "remove")]).await;
+    assert_eq!(removes["entries"][0]["actor"], "santa");
+    assert_eq!(removes["entries"].as_array().unwrap().len(), 1);
+    let first = audit_log(&cli, &[("quote_id", &ids[0])]).await;
+    assert_eq!(operations(&first), ["remove", "draft"]);
+    let both = audit_log(&cli, &[("actor", "santa"), ("operation", "draft")]).await;
+    assert_eq!(both["entries"][0]["quote_id"], ids[1]);
+
+    let at = removes["entries"][0]["at"].as_str().unwrap();
+    let since = audit_log(&cli, &[("from", at)]).await;
+    assert_eq!(operations(&since), ["remove"]);
+    let until = audit_log(&cli, &[("to", at), ("limit", "10")]).await;
+    assert_eq!(operations(&until), ["draft", "draft", "draft"]);
+
+    cli.get("/19/audit")
+        .query("operation", &"steal")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.get("/19/audit")
+        .query("from", &"2024-12-25T00:00:00Z")
+        .query("to", &"2024-12-24T00:00:00Z")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn audit_pagination(cli: TestClient<impl Endpoint>) {
+    for i in 0..5 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+
+    let first = audit_log(&cli, &[("limit", "2")]).await;
+    assert_eq!(first["page"], 1);
+    assert_eq!(first["entries"][0]["after"]["quote"], "Quote 4");
+    let token = first["next_token"].as_str().unwrap();
+    let second = audit_log(&cli, &[("limit", "2"), ("token", token)]).await;
+    assert_eq!(second["page"], 2);
+    assert_eq!(second["entries"][0]["after"]["quote"], "Quote 2");
+    let third = audit_log(
+        &cli,
+        &[
+            ("limit", "2"),
+            ("token", second["next_token"].as_str().unwrap()),
+        ],
+    )
+    .await;
+    assert_eq!(third["entries"][0]["after"]["quote"], "Quote 0");
+    assert_eq!(third["next_token"], Value::Null);
+
+    // Tokens stay with their filters, and with the audit log.
+    cli.get("/19/audit")
+        .query("actor", &"elf")
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.get("/19/list")
+        .query("token", &token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    let list_token = list_with_limit(&cli, None, Some(2)).await["next_token"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    cli.get("/19/audit")
+        .query("token", &list_token)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+/// Transactions can commit in another order than their events were recorded
+/// in. The stream still carries every event, in order.
+#[sqlx::test]
+async fn events_wait_for_slower_transactions(pool: PgPool) {
+    let cli = common::db_client(pool.clone());
+    let resp = cli.get("/19/events").send().await;
+    resp.assert_status_is_ok();
+    let mut body = Box::pin(resp.0.into_body().into_bytes_stream());
+    let mut buffer = String::new();
+    draft(&cli, "Santa", "Ho ho ho!").await;
+    read_events(&mut body, &mut buffer, 1).await;
+
+    let mut first = pool.begin().await.unwrap();
+    let mut second = pool.begin().await.unwrap();
+    // By different authors, lest the second wait on the first to add theirs.
+    for (tx, author, quote) in [
+        (&mut first, "Elf", "First"),
+        (&mut second, "Santa", "Second"),
+    ] {
+        sqlx::query("INSERT INTO quotes (id, author, quote) VALUES (gen_random_uuid(), $1, $2)")
+            .bind(author)
+            .bind(quote)
+            .execute(&mut **tx)
+            .await
+            .unwrap();
+    }
+    second.commit().await.unwrap();
+    // Long enough for the second event to be announced on its own.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    first.commit().await.unwrap();
+
+    let events = read_events(&mut body, &mut buffer, 2).await;
+    let quotes: Vec<_> = events.iter().map(|(_, _, quote)| &quote["quote"]).collect();
+    assert_eq!(quotes, ["First", "Second"]);
+    assert!(events[0].1 < events[1].1);
+}
+
+/// Every replica must agree on the quote of the day.
+#[sqlx::test]
+async fn today_works_across_instances(pool: PgPool) {
+    let first = common::db_client(pool.clone());
+    let second = common::db_client(pool);
+
+    for i in 0..10 {
+        draft(&first, "Elf", &format!("Quote {i}")).await;
+    }
+    for date in [None, Some("2024-12-25")] {
+        assert_eq!(today(&first, date).await, today(&second, date).await);
+    }
+}
+
+/// Tokens hold no server-side state, so any instance can redeem them.
+#[sqlx::test]
+async fn token_works_across_instances(pool: PgPool) {
+    let first = common::db_client(pool.clone());
+    let second = common::db_client(pool);
+
+    for i in 0..4 {
+        draft(&first, "Elf", &format!("Quote {i}")).await;
+    }
+    let body = list(&first, None).await;
+    let token = body["next_token"].as_str().unwrap();
+
+    let body = list(&second, Some(token)).await;
+    assert_eq!(body["page"], 2);
+    assert_eq!(body["quotes"][0]["quote"], "Quote 3");
+}
+
+/// Without configured secrets, nothing signed with a guessable one is taken.
+#[tokio::test]
+async fn secrets_have_no_default() {
+    let mut config = common::config();
+    config.quotes.cursor_secret = None;
+    config.quotes.actor_token_secret = None;
+    let cli = common::client_with(&config);
+
+    cli.post("/19/draft")
+        .header(
+            "Authorization",
+            format!("Bearer {}", actor_token("santa", "actors")),
+        )
+        .body_json(&json!({"author": "Santa", "quote": "Ho ho ho!"}))
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+
+    // Tokens still work within the instance that made up the secret.
+    for i in 0..4 {
+        draft(&cli, "Elf", &format!("Quote {i}")).await;
+    }
+    let body = list(&cli, None).await;
+    let body = list(&cli, Some(body["next_token"].as_str().unwrap())).await;
+    assert_eq!(body["page"], 2);
+
+    config.quotes.actor_token_secret = Some(String::new());
+    assert!(config.validate().is_err());
+}
+
+/// Serves the app on a local port, as only then do requests come from an
+/// address. The client reaches the same app without going through it.
+async fn serve(config: &Config) -> (SocketAddr, TestClient<impl Endpoint>) {
+    let acceptor = TcpListener::bind("127.0.0.1:0")
+        .into_acceptor()
+        .await
+        .unwrap();
+    let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+    let app = Arc::new(shuttlings_cch24::app(None, config));
+    tokio::spawn(Server::new_with_acceptor(acceptor).run(app.clone()));
+
+    (addr, TestClient::new(app))
+}
+
+/// Drafts a quote over a plain HTTP/1.1 connection, with `headers`.
+async fn draft_over_tcp(addr: SocketAddr, headers: &str) {
+    let body = json!({"author": "Santa", "quote": "Ho ho ho!"}).to_string();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            format!(
+                "POST /19/draft HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+                Content-Type: application/json\r\nContent-Length: {}\r\n{headers}\r\n{body}",
+                body.len()
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 201"), "{response}");
+}
+
+#[tokio::test]
+async fn audit_trusts_only_configured_proxies() {
+    let headers = "X-Actor: elf\r\nX-Forwarded-For: 198.51.100.1, 203.0.113.7\r\n";
+
+    let (addr, cli) = serve(&common::config()).await;
+    draft_over_tcp(addr, headers).await;
+    let log = audit_log(&cli, &[]).await;
+    assert_eq!(log["entries"][0]["actor"], Value::Null);
+    assert_eq!(log["entries"][0]["client_ip"], "127.0.0.1");
+
+    let mut config = common::config();
+    config.quotes.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    let (addr, cli) = serve(&config).await;
+    draft_over_tcp(addr, headers).await;
+    // The first address wasn't added by the trusted proxy, so it may be
+    // made up.
+    let log = audit_log(&cli, &[]).await;
+    assert_eq!(log["entries"][0]["actor"], "elf");
+    assert_eq!(log["entries"][0]["client_ip"], "203.0.113.7");
+
+    draft_over_tcp(addr, "X-Real-IP: 192.0.2.1\r\n").await;
+    let log = audit_log(&cli, &[]).await;
+    assert_eq!(log["entries"][0]["actor"], Value::Null);
+    assert_eq!(log["entries"][0]["client_ip"], "192.0.2.1");
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use poem::http::StatusCode;
+
+async fn get_text(path: &str, params: &[(&str, &str)]) -> String {
+    let cli = common::client();
+    let mut req = cli.get(path);
+    for (name, value) in params {
+        req = req.query(*name, value);
+    }
+    let resp = req.send().await;
+    resp.assert_status_is_ok();
+    resp.0.into_body().into_string().await.unwrap()
+}
+
+#[tokio::test]
+async fn ipv4_dest() {
+    let dest = get_text("/2/dest", &[("from", "10.0.0.0"), ("key", "1.2.3.255")]).await;
+    assert_eq!(dest, "11.2.3.255");
+}
+
+#[tokio::test]
+async fn ipv4_dest_wraps_around() {
+    let dest = get_text(
+        "/2/dest",
+        &[("from", "128.128.33.0"), ("key", "255.0.255.33")],
+    )
+    .await;
+    assert_eq!(dest, "127.128.32.33");
+}
+
+#[tokio::test]
+async fn ipv4_round_trip() {
+    let from = "192.168.0.1";
+    let key = "72.96.8.7";
+    let to = get_text("/2/dest", &[("from", from), ("key", key)]).await;
+    let recovered = get_text("/2/key", &[("from", from), ("to", &to)]).await;
+    assert_eq!(recovered, key);
+}
+
+#[tokio::test]
+async fn ipv6_dest() {
+    let dest = get_text("/2/v6/dest", &[("from", "fe80::1"), ("key", "5:6:7::3333")]).await;
+    assert_eq!(dest, "fe85:6:7::3332");
+}
+
+#[tokio::test]
+async fn ipv6_round_trip() {
+    let from = "aaaa::aaaa";
+    let key = "ffff:ffff:c::c:1234:ffff";
+    let to = get_text("/2/v6/dest", &[("from", from), ("key", key)]).await;
+    let recovered = get_text("/2/v6/key", &[("from", from), ("to", &to)]).await;
+    assert_eq!(recovered, key);
+}
+
+#[tokio::test]
+async fn invalid_address_is_rejected() {
+    let resp = common::client()
+        .get("/2/dest")
+        .query("from", &"not-an-ip")
+        .query("key", &"1.2.3.4")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.assert_content_type("application/problem+json");
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use poem::{
+    http::StatusCode,
+    test::{TestForm, TestFormField},
+};
+
+#[tokio::test]
+async fn assets_page() {
+    let resp = common::client().get("/assets/23.html").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_content_type("text/html; charset=utf-8");
+}
+
+#[tokio::test]
+async fn star() {
+    let resp = common::client().get("/23/star").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text(r#"<div id="star" class="lit"></div>"#)
+        .await;
+}
+
+#[tokio::test]
+async fn present_cycles_colors() {
+    let cli = common::client();
+
+    for (color, next) in [("red", "blue"), ("blue", "purple"), ("purple", "red")] {
+        let resp = cli.get(format!("/23/present/{color}")).send().await;
+        resp.assert_status_is_ok();
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains(&format!(r#"class="present {color}""#)));
+        assert!(html.contains(&format!(r#"hx-get="/23/present/{next}""#)));
+    }
+}
+
+#[tokio::test]
+async fn present_unknown_color() {
+    common::client()
+        .get("/23/present/green")
+        .send()
+        .await
+        .assert_status(StatusCode::IM_A_TEAPOT);
+}
+
+#[tokio::test]
+async fn ornament_toggles() {
+    let cli = common::client();
+
+    cli.get("/23/ornament/on/1").send().await.assert_text(
+        r#"<div class="ornament on" id="ornament1" hx-trigger="load delay:2s once" hx-get="/23/ornament/off/1" hx-swap="outerHTML"></div>"#,
+    ).await;
+    cli.get("/23/ornament/off/1").send().await.assert_text(
+        r#"<div class="ornament" id="ornament1" hx-trigger="load delay:2s once" hx-get="/23/ornament/on/1" hx-swap="outerHTML"></div>"#,
+    ).await;
+}
+
+#[tokio::test]
+async fn ornament_escapes_id() {
+    let resp = common::client()
+        .get("/23/ornament/on/%22%3E%3Cscript%3E")
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let html = resp.0.into_body().into_string().await.unwrap();
+    assert!(!html.contains("<script>"));
+    assert!(html.contains(r#"id="ornament&quot;&gt;&lt;script&gt;""#));
+}
+
+#[tokio::test]
+async fn ornament_unknown_state() {
+    common::client()
+        .get("/23/ornament/dim/1")
+        .send()
+        .await
+        .assert_status(StatusCode::IM_A_TEAPOT);
+}
+
+fn lockfile_form(contents: &str) -> TestForm {
+    TestForm::new().field(
+        TestFormField::text(contents)
+            .name("lockfile")
+            .filename("Cargo.lock"),
+    )
+}
+
+#[tokio::test]
+async fn lockfile_sprinkles() {
+    let lockfile = r#"
+[[package]]
+name = "shuttlings-cch24"
+version = "0.1.0"
+
+[[package]]
+name = "addr2line"
+version = "0.24.2"
+checksum = "dfbe277e56a376000877090da837660b4427aad530e3028d44e0bffe4f89a1c1"
+
+[[package]]
+name = "adler2"
+version = "2.0.0"
+checksum = "512761e0bb2578dd7380c6baaa0f4ce03e84f95e960231d1dec8bf4d7d6e2627"
+"#;
+
+    let resp = common::client()
+        .post("/23/lockfile")
+        .multipart(lockfile_form(lockfile))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_text(
+        "<div style=\"background-color:#dfbe27;top:126px;left:86px;\"></div>\n\
+         <div style=\"background-color:#512761;top:224px;left:187px;\"></div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn lockfile_invalid_checksum() {
+    let lockfile = r#"
+[[package]]
+name = "bad"
+checksum = "not-hex-at-all"
+"#;
+
+    common::client()
+        .post("/23/lockfile")
+        .multipart(lockfile_form(lockfile))
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn lockfile_invalid_toml() {
+    common::client()
+        .post("/23/lockfile")
+        .multipart(lockfile_form("this is [not toml"))
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn lockfile_without_checksums() {
+    let lockfile = r#"
+[[package]]
+name = "local-only"
+version = "0.1.0"
+"#;
+
+    common::client()
+        .post("/23/lockfile")
+        .multipart(lockfile_form(lockfile))
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
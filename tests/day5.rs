@@ -0,0 +1,170 @@
+mod common;
+
+use poem::http::StatusCode;
+
+const TOML_MANIFEST: &str = r#"
+[package]
+name = "not-a-gift-order"
+authors = ["Not Santa"]
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[[package.metadata.orders]]
+item = "Lego brick"
+quantity = 230
+"#;
+
+async fn post_manifest(content_type: &str, body: &str) -> poem::test::TestResponse {
+    common::client()
+        .post("/5/manifest")
+        .content_type(content_type)
+        .body(body.to_owned())
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn toml_orders() {
+    let resp = post_manifest("application/toml", TOML_MANIFEST).await;
+    resp.assert_status_is_ok();
+    resp.assert_text("Toy car: 2\nLego brick: 230").await;
+}
+
+#[tokio::test]
+async fn json_orders() {
+    let body = r#"{
+        "package": {
+            "name": "gifts",
+            "keywords": ["Christmas 2024"],
+            "metadata": {"orders": [{"item": "Sled", "quantity": 1}]}
+        }
+    }"#;
+    let resp = post_manifest("application/json", body).await;
+    resp.assert_status_is_ok();
+    resp.assert_text("Sled: 1").await;
+}
+
+#[tokio::test]
+async fn yaml_orders() {
+    let body = "
+package:
+  name: gifts
+  keywords:
+    - Christmas 2024
+  metadata:
+    orders:
+      - item: Doll
+        quantity: 3
+";
+    let resp = post_manifest("application/yaml", body).await;
+    resp.assert_status_is_ok();
+    resp.assert_text("Doll: 3").await;
+}
+
+#[tokio::test]
+async fn invalid_orders_are_skipped() {
+    let body = r#"
+[package]
+name = "gifts"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = "two"
+
+[[package.metadata.orders]]
+item = "Ball"
+quantity = 1
+"#;
+    let resp = post_manifest("application/toml", body).await;
+    resp.assert_status_is_ok();
+    resp.assert_text("Ball: 1").await;
+}
+
+#[tokio::test]
+async fn missing_keyword() {
+    let body = r#"
+[package]
+name = "gifts"
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+"#;
+    let resp = post_manifest("application/toml", body).await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("detail")
+        .assert_string("Magic keyword not provided");
+}
+
+#[tokio::test]
+async fn no_orders() {
+    let body = r#"
+[package]
+name = "gifts"
+keywords = ["Christmas 2024"]
+"#;
+    post_manifest("application/toml", body)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn only_invalid_orders() {
+    let body = r#"
+[package]
+name = "gifts"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+"#;
+    post_manifest("application/toml", body)
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn no_package() {
+    post_manifest("application/toml", "[workspace]\nmembers = []\n")
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn invalid_manifest() {
+    let body = r#"
+[package]
+name = false
+keywords = ["Christmas 2024"]
+"#;
+    post_manifest("application/toml", body)
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn malformed_json() {
+    let resp = post_manifest("application/json", "{ not json").await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("code")
+        .assert_string("bad_json");
+}
+
+#[tokio::test]
+async fn unsupported_content_type() {
+    post_manifest("text/plain", TOML_MANIFEST)
+        .await
+        .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use poem::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn withdraw_until_empty() {
+    let cli = common::client();
+
+    for _ in 0..5 {
+        let resp = cli.post("/9/milk").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("Milk withdrawn\n").await;
+    }
+
+    let resp = cli.post("/9/milk").send().await;
+    resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    resp.assert_content_type("application/problem+json");
+}
+
+#[tokio::test]
+async fn refill() {
+    let cli = common::client();
+
+    for _ in 0..5 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+
+    cli.post("/9/refill").send().await.assert_status_is_ok();
+    cli.post("/9/milk").send().await.assert_status_is_ok();
+}
+
+#[tokio::test]
+async fn bucket_size_is_configurable() {
+    let mut config = common::config();
+    config.milk.initial = 1;
+    config.milk.max = 1;
+    let cli = common::client_with(&config);
+
+    cli.post("/9/milk").send().await.assert_status_is_ok();
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn conversions() {
+    let cli = common::client();
+
+    let cases = [
+        (json!({"liters": 5}), "gallons", 1.320_860_2),
+        (json!({"gallons": 5}), "liters", 18.927_06),
+        (json!({"litres": 2}), "pints", 3.5195),
+        (json!({"pints": 2}), "litres", 1.136_52),
+    ];
+    for (body, unit, expected) in cases {
+        let resp = cli.post("/9/milk").body_json(&body).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let amount = json.value().object().get(unit).f64();
+        assert!((amount - expected).abs() < 1e-3, "{unit}: {amount}");
+    }
+}
+
+#[tokio::test]
+async fn invalid_conversions() {
+    let cli = common::client();
+
+    for body in [
+        json!({"liters": 1, "gallons": 1}),
+        json!({"ounces": 1}),
+        json!({}),
+    ] {
+        let resp = cli.post("/9/milk").body_json(&body).send().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        resp.json()
+            .await
+            .value()
+            .object()
+            .get("code")
+            .assert_string("bad_json");
+    }
+}
+
+#[tokio::test]
+async fn empty_bucket_before_bad_json() {
+    let cli = common::client();
+
+    for _ in 0..5 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .content_type("application/json")
+        .body("{ not json")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
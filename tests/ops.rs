@@ -0,0 +1,151 @@
+//! Cross-cutting endpoints: error rendering, the OpenAPI spec, health checks
+//! and metrics.
+
+mod common;
+
+use poem::http::StatusCode;
+use sqlx::PgPool;
+
+#[tokio::test]
+async fn unknown_route_is_a_problem() {
+    let resp = common::client().get("/nope").send().await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    resp.assert_content_type("application/problem+json");
+    let json = resp.json().await;
+    let problem = json.value().object();
+    problem.get("status").assert_i64(404);
+    problem.get("code").assert_string("not_found");
+    problem
+        .get("type")
+        .assert_string("urn:cch24:problem:not_found");
+}
+
+#[tokio::test]
+async fn openapi_spec() {
+    let resp = common::client().get("/openapi.json").send().await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let paths = json.value().object().get("paths").object();
+    for path in [
+        "/",
+        "/2/dest",
+        "/12/place/{team}/{column}",
+        "/19/list",
+        "/metrics",
+    ] {
+        paths.get(path).assert_not_null();
+    }
+}
+
+#[tokio::test]
+async fn healthz() {
+    let resp = common::client().get("/healthz").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(serde_json::json!({"status": "ok"})).await;
+}
+
+#[sqlx::test]
+async fn readyz(pool: PgPool) {
+    let resp = common::db_client(pool).get("/readyz").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(serde_json::json!({
+        "status": "ok",
+        "checks": {"database": {"status": "ok"}, "migrations": {"status": "ok"}}
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn readyz_without_database() {
+    let resp = common::client().get("/readyz").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_json(serde_json::json!({
+        "status": "ok",
+        "checks": {"database": {"status": "skipped"}, "migrations": {"status": "skipped"}}
+    }))
+    .await;
+}
+
+#[sqlx::test(migrations = false)]
+async fn readyz_without_migrations(pool: PgPool) {
+    let resp = common::db_client(pool).get("/readyz").send().await;
+    resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    let json = resp.json().await;
+    let body = json.value().object();
+    body.get("status").assert_string("failing");
+    let checks = body.get("checks").object();
+    checks
+        .get("database")
+        .object()
+        .get("status")
+        .assert_string("ok");
+    checks
+        .get("migrations")
+        .object()
+        .get("status")
+        .assert_string("failing");
+}
+
+#[sqlx::test]
+async fn metrics(pool: PgPool) {
+    let cli = common::db_client(pool);
+
+    cli.post("/12/place/cookie/1")
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.get("/does/not/exist").send().await;
+    cli.post("/19/draft")
+        .body_json(&serde_json::json!({"author": "a", "quote": "b"}))
+        .send()
+        .await;
+
+    let resp = cli.get("/metrics").send().await;
+    resp.assert_status_is_ok();
+    let text = resp.0.into_body().into_string().await.unwrap();
+
+    for line in [
+        r#"cch24_http_requests_total{method="POST",route="/12/place/:team/:column",status="200"} 1"#,
+        r#"cch24_http_requests_total{method="GET",route="unmatched",status="404"} 1"#,
+        "cch24_quotes 1",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "missing {line:?} in\n{text}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn milk_tokens_metric() {
+    let mut config = common::config();
+    // Long enough that nothing trickles back in during the test.
+    config.milk.refill_interval_ms = 3_600_000;
+    let cli = common::client_with(&config);
+
+    for _ in 0..4 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+
+    let resp = cli.get("/metrics").send().await;
+    let text = resp.0.into_body().into_string().await.unwrap();
+    assert!(text.lines().any(|l| l == "cch24_milk_tokens 1"));
+}
+
+#[tokio::test]
+async fn connect4_finished_games_metric() {
+    let cli = common::client();
+
+    for _ in 0..4 {
+        cli.post("/12/place/milk/2")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    let resp = cli.get("/metrics").send().await;
+    let text = resp.0.into_body().into_string().await.unwrap();
+    assert!(text
+        .lines()
+        .any(|l| l == r#"cch24_connect4_games_finished_total{winner="milk"} 1"#));
+}